@@ -0,0 +1,619 @@
+//! GraphQL schema over the stored blocks, served on `/graphql`, e.g.
+//!
+//! ```graphql
+//! {
+//!   block(height: 5000000) {
+//!     hash
+//!     transactions(address: "addr1...") {
+//!       id
+//!       outputs { address { outputs(first: 10) { data { txId coins } next } } }
+//!       mints { quantity asset { policy } }
+//!     }
+//!   }
+//! }
+//! ```
+//!
+//! Lists of the whole chain are pages `{data, next}`, the next one being
+//! requested with `after: next` until `next` is null, as a page of a scan
+//! can hold fewer results than `first` while there are more.
+//!
+//! Queries deeper than `MAX_DEPTH` or more complex than `MAX_COMPLEXITY` are
+//! rejected, a page counting as `first` times its fields, plus `SCAN_COST`
+//! for a scan, and a transaction lookup as `LOOKUP_COST`.
+use std::sync::Arc;
+
+use async_graphql::{
+    ComplexObject, Context, EmptyMutation, EmptySubscription, Json, Object, OutputType, Result,
+    Schema, SimpleObject,
+};
+
+use crate::api::{find_tx, State};
+use crate::chain::{Chain, SyncProgress};
+use crate::data::shelley::Certificate;
+use crate::data::{Block, EpochLayout, Minted, Output};
+use crate::query::{self, Certified, Found};
+
+pub type ChainSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+pub const MAX_DEPTH: usize = 12;
+pub const MAX_COMPLEXITY: usize = 2000;
+
+const DEFAULT_LIMIT: usize = 20;
+const MAX_LIMIT: usize = 100;
+/// Complexity of a scan, which reads up to `SCAN_BLOCKS` blocks for a page.
+const SCAN_COST: usize = 100;
+/// Complexity of looking a transaction up, which reads the whole chain when
+/// it is not there.
+const LOOKUP_COST: usize = 500;
+
+pub fn schema(state: State) -> ChainSchema {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .data(state.layout)
+        .data(state)
+        .limit_depth(MAX_DEPTH)
+        .limit_complexity(MAX_COMPLEXITY)
+        .finish()
+}
+
+/// Height to start from and number of results of a page.
+fn page(after: Option<String>, first: Option<usize>) -> Result<(u64, usize)> {
+    let cursor = match after {
+        Some(after) => after
+            .parse()
+            .map_err(|_| format!("Invalid cursor '{}'.", after))?,
+        None => 0,
+    };
+    let limit = first.unwrap_or(DEFAULT_LIMIT);
+    if limit == 0 || limit > MAX_LIMIT {
+        return Err(format!("first must be between 1 and {}.", MAX_LIMIT).into());
+    }
+    Ok((cursor, limit))
+}
+
+/// Complexity of a page of `first` nodes of `child` each, `first` being
+/// bounded as it is when the page is read.
+fn page_cost(first: Option<usize>, child: usize) -> usize {
+    first
+        .unwrap_or(DEFAULT_LIMIT)
+        .min(MAX_LIMIT)
+        .saturating_mul(child)
+}
+
+fn state<'a>(ctx: &Context<'a>) -> &'a State {
+    ctx.data_unchecked::<State>()
+}
+
+/// Results of a scan, with the cursor of the next page when there is one.
+#[derive(SimpleObject)]
+#[graphql(concrete(name = "BlockPage", params(BlockNode)))]
+#[graphql(concrete(name = "TxOutPage", params(TxOutNode)))]
+#[graphql(concrete(name = "CertificatePage", params(CertificateNode)))]
+#[graphql(concrete(name = "MintPage", params(MintNode)))]
+pub struct Page<T: OutputType> {
+    data: Vec<T>,
+    next: Option<String>,
+}
+
+impl<T: OutputType> Page<T> {
+    fn from<U>(page: query::Page<Found<U>>, node: impl Fn(Found<U>) -> T) -> Self {
+        Self {
+            data: page.data.into_iter().map(node).collect(),
+            next: page.next,
+        }
+    }
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    async fn tip(&self, ctx: &Context<'_>) -> TipNode {
+        let chain = state(ctx).chain.lock().await;
+        TipNode {
+            slot: chain.tip.as_ref().map(|tip| tip.slot),
+            hash: chain.tip.as_ref().map(|tip| tip.hash.clone()),
+            height: chain.height(),
+            synchronized: matches!(chain.sync(), SyncProgress::Synchronized(_)),
+        }
+    }
+
+    /// The stored block with exactly one of `hash`, `height` or `slot`.
+    async fn block(
+        &self,
+        ctx: &Context<'_>,
+        hash: Option<String>,
+        height: Option<u64>,
+        slot: Option<u64>,
+    ) -> Result<Option<BlockNode>> {
+        let chain = state(ctx).chain.lock().await;
+        let block = match (hash, height, slot) {
+            (Some(hash), None, None) => chain.get_block_by_hash(&hash)?,
+            (None, Some(height), None) => chain.get_block_by_height(height)?,
+            (None, None, Some(slot)) => chain.get_block_by_slot(slot)?,
+            _ => return Err("Give one of hash, height or slot.".into()),
+        };
+        Ok(block.map(BlockNode::new))
+    }
+
+    /// The stored blocks from the height `after`, of `epoch` if given.
+    #[graphql(
+        complexity = "page_cost(first, child_complexity).saturating_add(epoch.map_or(0, |_| SCAN_COST))"
+    )]
+    async fn blocks(
+        &self,
+        ctx: &Context<'_>,
+        epoch: Option<u64>,
+        after: Option<String>,
+        first: Option<usize>,
+    ) -> Result<Page<BlockNode>> {
+        let (cursor, limit) = page(after, first)?;
+        let chain = state(ctx).chain.lock().await;
+        let (from, last) = match epoch.map(|epoch| chain.epoch_heights(epoch..=epoch)) {
+            Some(Some((first, last))) => (cursor.max(first), last),
+            Some(None) => {
+                return Ok(Page {
+                    data: Vec::new(),
+                    next: None,
+                })
+            }
+            None => (cursor, u64::MAX),
+        };
+        let mut data = Vec::new();
+        for block in chain.blocks_from(from, limit + 1) {
+            let block = block?;
+            if block.height() > last {
+                break;
+            }
+            if data.len() == limit {
+                return Ok(Page {
+                    data,
+                    next: Some(block.height().to_string()),
+                });
+            }
+            data.push(BlockNode::new(block));
+        }
+        Ok(Page { data, next: None })
+    }
+
+    #[graphql(complexity = "LOOKUP_COST.saturating_add(child_complexity)")]
+    async fn transaction(&self, ctx: &Context<'_>, id: String) -> Result<Option<TxNode>> {
+        Ok(find_tx(&state(ctx).chain, &id)
+            .await?
+            .map(|(block, _)| TxNode {
+                block: Arc::new(block),
+                id,
+            }))
+    }
+
+    async fn address(&self, address: String) -> AddressNode {
+        AddressNode(address)
+    }
+
+    async fn pool(&self, id: String) -> PoolNode {
+        PoolNode(id)
+    }
+
+    /// An asset as `policy.name`, or all the assets of a policy.
+    async fn asset(&self, id: String) -> AssetNode {
+        AssetNode(id)
+    }
+
+    /// The summary of an epoch, whose transactions are counted from the
+    /// height `after`, a page at a time.
+    #[graphql(complexity = "SCAN_COST.saturating_add(child_complexity)")]
+    async fn epoch(
+        &self,
+        ctx: &Context<'_>,
+        number: u64,
+        after: Option<String>,
+    ) -> Result<EpochNode> {
+        let (cursor, _) = page(after, None)?;
+        let chain = state(ctx).chain.lock().await;
+        let epoch = query::epoch_page(&chain, number, cursor)?;
+        Ok(EpochNode {
+            number: epoch.epoch,
+            first_slot: epoch.first_slot,
+            block_count: epoch.blocks,
+            tx_count: epoch.txs,
+            first_block: epoch.first_block,
+            last_block: epoch.last_block,
+            next: epoch.next,
+        })
+    }
+}
+
+#[derive(SimpleObject)]
+#[graphql(name = "Tip")]
+pub struct TipNode {
+    slot: Option<u64>,
+    hash: Option<String>,
+    /// Height of the last stored block.
+    height: Option<u64>,
+    synchronized: bool,
+}
+
+#[derive(SimpleObject)]
+#[graphql(name = "Epoch")]
+pub struct EpochNode {
+    number: u64,
+    first_slot: u64,
+    block_count: usize,
+    /// Transactions of the blocks read for this page.
+    tx_count: usize,
+    first_block: Option<String>,
+    last_block: Option<String>,
+    /// Cursor of the next page of transactions, if any.
+    next: Option<String>,
+}
+
+/// The block `hash`, looked up when a joined field asks for it.
+async fn block(ctx: &Context<'_>, hash: &str) -> Result<Option<BlockNode>> {
+    let chain = state(ctx).chain.lock().await;
+    Ok(chain.get_block_by_hash(hash)?.map(BlockNode::new))
+}
+
+pub struct BlockNode(Arc<Block>);
+
+impl BlockNode {
+    fn new(block: Block) -> Self {
+        Self(Arc::new(block))
+    }
+}
+
+#[Object(name = "Block")]
+impl BlockNode {
+    async fn hash(&self) -> String {
+        self.0.hash()
+    }
+
+    async fn prev_hash(&self) -> String {
+        self.0.prev_hash()
+    }
+
+    async fn height(&self) -> u64 {
+        self.0.height()
+    }
+
+    async fn slot(&self) -> u64 {
+        self.0.slot()
+    }
+
+    async fn epoch(&self, ctx: &Context<'_>) -> u64 {
+        self.0.epoch(ctx.data_unchecked::<EpochLayout>())
+    }
+
+    async fn era(&self) -> String {
+        format!("{:?}", self.0.era())
+    }
+
+    async fn timestamp(&self) -> String {
+        self.0.timestamp().to_rfc3339()
+    }
+
+    /// Transactions paying `address` or minting assets of `policy` when
+    /// given, or all of them.
+    async fn transactions(&self, address: Option<String>, policy: Option<String>) -> Vec<TxNode> {
+        let outputs = self.0.outputs();
+        let mints = self.0.mints();
+        self.0
+            .tx_ids()
+            .into_iter()
+            .filter(|id| {
+                address.as_ref().map_or(true, |address| {
+                    outputs
+                        .iter()
+                        .any(|o| o.tx_id == *id && o.address == *address)
+                }) && policy.as_ref().map_or(true, |policy| {
+                    mints.iter().any(|m| m.tx_id == *id && m.policy() == policy)
+                })
+            })
+            .map(|id| TxNode {
+                block: self.0.clone(),
+                id,
+            })
+            .collect()
+    }
+
+    async fn certificates(&self) -> Vec<CertificateNode> {
+        self.0
+            .certificates()
+            .into_iter()
+            .map(|(tx_id, certificate)| CertificateNode::new(&self.0, tx_id, certificate))
+            .collect()
+    }
+
+    async fn mints(&self) -> Vec<MintNode> {
+        self.0
+            .mints()
+            .into_iter()
+            .map(|minted| MintNode::new(&self.0, minted))
+            .collect()
+    }
+}
+
+pub struct TxNode {
+    block: Arc<Block>,
+    id: String,
+}
+
+#[Object(name = "Tx")]
+impl TxNode {
+    async fn id(&self) -> &str {
+        &self.id
+    }
+
+    async fn block(&self) -> BlockNode {
+        BlockNode(self.block.clone())
+    }
+
+    async fn outputs(&self) -> Vec<TxOutNode> {
+        self.block
+            .outputs()
+            .into_iter()
+            .filter(|output| output.tx_id == self.id)
+            .map(|output| TxOutNode::new(&self.block, output))
+            .collect()
+    }
+
+    async fn certificates(&self) -> Vec<CertificateNode> {
+        self.block
+            .certificates()
+            .into_iter()
+            .filter(|(tx_id, _)| *tx_id == self.id)
+            .map(|(tx_id, certificate)| CertificateNode::new(&self.block, tx_id, certificate))
+            .collect()
+    }
+
+    async fn mints(&self) -> Vec<MintNode> {
+        self.block
+            .mints()
+            .into_iter()
+            .filter(|minted| minted.tx_id == self.id)
+            .map(|minted| MintNode::new(&self.block, minted))
+            .collect()
+    }
+
+    /// The whole transaction, as decoded from the node.
+    async fn json(&self) -> Option<Json<serde_json::Value>> {
+        self.block.tx(&self.id).map(Json)
+    }
+}
+
+#[derive(SimpleObject)]
+#[graphql(name = "TxOut", complex)]
+pub struct TxOutNode {
+    tx_id: String,
+    index: u64,
+    coins: u64,
+    block_hash: String,
+    height: u64,
+    #[graphql(skip)]
+    address: String,
+}
+
+impl TxOutNode {
+    fn new(block: &Block, output: Output) -> Self {
+        Self::from(Found {
+            block: block.hash(),
+            height: block.height(),
+            item: output,
+        })
+    }
+}
+
+impl From<Found<Output>> for TxOutNode {
+    fn from(found: Found<Output>) -> Self {
+        Self {
+            tx_id: found.item.tx_id,
+            index: found.item.index,
+            coins: found.item.coins,
+            block_hash: found.block,
+            height: found.height,
+            address: found.item.address,
+        }
+    }
+}
+
+#[ComplexObject]
+impl TxOutNode {
+    async fn address(&self) -> AddressNode {
+        AddressNode(self.address.clone())
+    }
+
+    async fn block(&self, ctx: &Context<'_>) -> Result<Option<BlockNode>> {
+        block(ctx, &self.block_hash).await
+    }
+}
+
+#[derive(SimpleObject)]
+#[graphql(name = "Certificate", complex)]
+pub struct CertificateNode {
+    tx_id: String,
+    block_hash: String,
+    height: u64,
+    /// As named by the node, e.g. `poolRegistration`.
+    kind: String,
+    /// The pool registered, retired or delegated to.
+    pool: Option<String>,
+    /// The whole certificate.
+    json: Json<Certificate>,
+}
+
+impl CertificateNode {
+    fn new(block: &Block, tx_id: String, certificate: Certificate) -> Self {
+        Self::from(Found {
+            block: block.hash(),
+            height: block.height(),
+            item: Certified { tx_id, certificate },
+        })
+    }
+}
+
+impl From<Found<Certified>> for CertificateNode {
+    fn from(found: Found<Certified>) -> Self {
+        let certificate = found.item.certificate;
+        let kind = certificate.kind();
+        let pool = certificate.pool().map(str::to_string);
+        Self {
+            tx_id: found.item.tx_id,
+            block_hash: found.block,
+            height: found.height,
+            kind,
+            pool,
+            json: Json(certificate),
+        }
+    }
+}
+
+#[ComplexObject]
+impl CertificateNode {
+    async fn block(&self, ctx: &Context<'_>) -> Result<Option<BlockNode>> {
+        block(ctx, &self.block_hash).await
+    }
+}
+
+#[derive(SimpleObject)]
+#[graphql(name = "Mint", complex)]
+pub struct MintNode {
+    tx_id: String,
+    /// Negative when burnt.
+    quantity: i64,
+    block_hash: String,
+    height: u64,
+    #[graphql(skip)]
+    asset: String,
+}
+
+impl MintNode {
+    fn new(block: &Block, minted: Minted) -> Self {
+        Self::from(Found {
+            block: block.hash(),
+            height: block.height(),
+            item: minted,
+        })
+    }
+}
+
+impl From<Found<Minted>> for MintNode {
+    fn from(found: Found<Minted>) -> Self {
+        Self {
+            tx_id: found.item.tx_id,
+            quantity: found.item.quantity,
+            block_hash: found.block,
+            height: found.height,
+            asset: found.item.asset,
+        }
+    }
+}
+
+#[ComplexObject]
+impl MintNode {
+    async fn asset(&self) -> AssetNode {
+        AssetNode(self.asset.clone())
+    }
+
+    async fn block(&self, ctx: &Context<'_>) -> Result<Option<BlockNode>> {
+        block(ctx, &self.block_hash).await
+    }
+}
+
+/// Runs a paginated lookup of the chain.
+async fn scan<T: OutputType, U>(
+    ctx: &Context<'_>,
+    after: Option<String>,
+    first: Option<usize>,
+    lookup: impl FnOnce(&Chain, u64, usize) -> color_eyre::eyre::Result<query::Page<Found<U>>>,
+    node: impl Fn(Found<U>) -> T,
+) -> Result<Page<T>> {
+    let (cursor, limit) = page(after, first)?;
+    let chain = state(ctx).chain.lock().await;
+    Ok(Page::from(lookup(&chain, cursor, limit)?, node))
+}
+
+pub struct AddressNode(String);
+
+#[Object(name = "Address")]
+impl AddressNode {
+    async fn address(&self) -> &str {
+        &self.0
+    }
+
+    /// Outputs paid to the address, in chain order.
+    #[graphql(complexity = "SCAN_COST.saturating_add(page_cost(first, child_complexity))")]
+    async fn outputs(
+        &self,
+        ctx: &Context<'_>,
+        after: Option<String>,
+        first: Option<usize>,
+    ) -> Result<Page<TxOutNode>> {
+        let address = &self.0;
+        scan(
+            ctx,
+            after,
+            first,
+            |chain, from, limit| query::payments(chain, address, from, limit),
+            TxOutNode::from,
+        )
+        .await
+    }
+}
+
+pub struct PoolNode(String);
+
+#[Object(name = "Pool")]
+impl PoolNode {
+    async fn id(&self) -> &str {
+        &self.0
+    }
+
+    /// Registrations and retirements of the pool, and delegations to it.
+    #[graphql(complexity = "SCAN_COST.saturating_add(page_cost(first, child_complexity))")]
+    async fn certificates(
+        &self,
+        ctx: &Context<'_>,
+        after: Option<String>,
+        first: Option<usize>,
+    ) -> Result<Page<CertificateNode>> {
+        let id = &self.0;
+        scan(
+            ctx,
+            after,
+            first,
+            |chain, from, limit| query::pool(chain, id, from, limit),
+            CertificateNode::from,
+        )
+        .await
+    }
+}
+
+pub struct AssetNode(String);
+
+#[Object(name = "Asset")]
+impl AssetNode {
+    async fn id(&self) -> &str {
+        &self.0
+    }
+
+    async fn policy(&self) -> &str {
+        self.0.split('.').next().unwrap_or_default()
+    }
+
+    /// Mints and burns of the asset, or of all those of the policy.
+    #[graphql(complexity = "SCAN_COST.saturating_add(page_cost(first, child_complexity))")]
+    async fn mints(
+        &self,
+        ctx: &Context<'_>,
+        after: Option<String>,
+        first: Option<usize>,
+    ) -> Result<Page<MintNode>> {
+        let asset = &self.0;
+        scan(
+            ctx,
+            after,
+            first,
+            |chain, from, limit| query::asset(chain, asset, from, limit),
+            MintNode::from,
+        )
+        .await
+    }
+}
@@ -0,0 +1,398 @@
+//! Chain events pushed to websocket clients connected to `/live`.
+//!
+//! Every client receives the blocks added to the chain, the rollbacks and
+//! the sync progress, and the payments, certificates and mints of its
+//! subscription, given as query parameters when connecting:
+//!
+//! ```text
+//! /live?addresses=addr1...,addr1...&policies=<policy id>&pools=pool1...
+//! ```
+//!
+//! and replaced by sending `{"addresses": [...], "policies": [...],
+//! "pools": [...]}`. With `from=<slot>.<hash>`, the stored blocks after that
+//! point are sent first, to resume after a disconnection. When that point
+//! was rolled back since, a rollback to the last block stored before its
+//! slot is sent first, and the blocks are sent from there.
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use color_eyre::eyre::{eyre, Result};
+use futures::{SinkExt, StreamExt};
+use hyper::upgrade::Upgraded;
+use hyper::{header, Body, Request, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+use tokio_tungstenite::tungstenite::protocol::Role;
+use tokio_tungstenite::tungstenite::Message as Frame;
+use tokio_tungstenite::WebSocketStream;
+use tracing::{debug, warn};
+
+use crate::api::{params, ApiError, ApiResult, State};
+use crate::chain::{ChainEvent, SyncProgress};
+use crate::data::{Block, EpochLayout, Minted, Output, Point, PointOrOrigin, Tip};
+use crate::query::{Certified, Found};
+use crate::synchronization::{Lagged, Watched};
+
+/// Updates kept for clients slower than the chain, before they miss some.
+pub const BUFFER: usize = 1024;
+
+/// Blocks read from the storage at once when resuming.
+const REPLAY_BATCH: usize = 100;
+
+/// What the clients are sent.
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Message {
+    Block {
+        hash: String,
+        height: u64,
+        slot: u64,
+        epoch: u64,
+    },
+    Payment(Found<Output>),
+    Certificate(Found<Certified>),
+    Mint(Found<Minted>),
+    /// The chain went back to the block `hash`, reverting the blocks listed.
+    Rollback {
+        hash: String,
+        height: u64,
+        reverted: Vec<String>,
+    },
+    Progress {
+        synchronized: bool,
+        progress: f32,
+        tip: Option<Tip>,
+    },
+    /// Updates were dropped as the client or the server did not keep up.
+    Lagged {
+        missed: u64,
+    },
+    Error {
+        message: String,
+    },
+}
+
+/// An event of the chain, before it is filtered for each client.
+#[derive(Debug, Clone)]
+pub enum Update {
+    Block(Arc<Block>),
+    Broadcast(Arc<Message>),
+}
+
+impl Update {
+    /// The updates the clients get from `event`.
+    pub fn of(event: &ChainEvent) -> Vec<Self> {
+        match event {
+            ChainEvent::Collection(blocks) => blocks
+                .iter()
+                .map(|block| Self::Block(Arc::new(block.clone())))
+                .collect(),
+            ChainEvent::RevertFork(reverted) => match reverted.first() {
+                Some(first) => vec![Self::Broadcast(Arc::new(Message::Rollback {
+                    hash: first.prev_hash(),
+                    height: first.height().saturating_sub(1),
+                    reverted: reverted.iter().map(Block::hash).collect(),
+                }))],
+                None => Vec::new(),
+            },
+            ChainEvent::Synchronizing(progress) => {
+                let (synchronized, progress, tip) = match progress {
+                    SyncProgress::Synchronized(tip) => (true, 100.0, Some(tip.clone())),
+                    SyncProgress::Synchronizing(progress, _, tip) => {
+                        (false, *progress, Some(tip.clone()))
+                    }
+                    SyncProgress::Unsynchronized => (false, 0.0, None),
+                };
+                vec![Self::Broadcast(Arc::new(Message::Progress {
+                    synchronized,
+                    progress,
+                    tip,
+                }))]
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Sends the updates of `events` to the clients until the engine stops.
+pub async fn forward(mut events: Watched, live: broadcast::Sender<Update>) {
+    while let Some(event) = events.next().await {
+        let updates = match event {
+            Ok(event) => Update::of(&event),
+            Err(Lagged(missed)) => {
+                vec![Update::Broadcast(Arc::new(Message::Lagged { missed }))]
+            }
+        };
+        for update in updates {
+            // Fails when no client is connected.
+            let _ = live.send(update);
+        }
+    }
+}
+
+/// What a client is sent of the transactions of the blocks.
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Subscription {
+    pub addresses: Vec<String>,
+    pub policies: Vec<String>,
+    pub pools: Vec<String>,
+}
+
+impl Subscription {
+    fn from_params(params: &HashMap<String, String>) -> Self {
+        let list = |name: &str| -> Vec<String> {
+            params
+                .get(name)
+                .map(|list| {
+                    list.split(',')
+                        .filter(|item| !item.is_empty())
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+        Self {
+            addresses: list("addresses"),
+            policies: list("policies"),
+            pools: list("pools"),
+        }
+    }
+
+    /// The block, followed by what the subscription asks for of it.
+    pub fn messages(&self, block: &Block, layout: &EpochLayout) -> Vec<Message> {
+        let mut messages = vec![Message::Block {
+            hash: block.hash(),
+            height: block.height(),
+            slot: block.slot(),
+            epoch: block.epoch(layout),
+        }];
+        messages.extend(
+            block
+                .outputs()
+                .into_iter()
+                .filter(|output| self.addresses.contains(&output.address))
+                .map(|output| {
+                    Message::Payment(Found {
+                        block: block.hash(),
+                        height: block.height(),
+                        item: output,
+                    })
+                }),
+        );
+        messages.extend(
+            block
+                .certificates()
+                .into_iter()
+                .filter(|(_, certificate)| {
+                    certificate
+                        .pool()
+                        .map_or(false, |pool| self.pools.iter().any(|p| p == pool))
+                })
+                .map(|(tx_id, certificate)| {
+                    Message::Certificate(Found {
+                        block: block.hash(),
+                        height: block.height(),
+                        item: Certified { tx_id, certificate },
+                    })
+                }),
+        );
+        messages.extend(
+            block
+                .mints()
+                .into_iter()
+                .filter(|minted| self.policies.iter().any(|p| p == minted.policy()))
+                .map(|minted| {
+                    Message::Mint(Found {
+                        block: block.hash(),
+                        height: block.height(),
+                        item: minted,
+                    })
+                }),
+        );
+        messages
+    }
+}
+
+type Socket = WebSocketStream<Upgraded>;
+
+async fn send(socket: &mut Socket, message: &Message) -> Result<()> {
+    socket
+        .send(Frame::Text(serde_json::to_string(message)?))
+        .await?;
+    Ok(())
+}
+
+/// Sends the stored blocks after `from`, returning the height of the last
+/// one sent.
+async fn replay(
+    socket: &mut Socket,
+    state: &State,
+    subscription: &Subscription,
+    from: &PointOrOrigin,
+) -> Result<Option<u64>> {
+    let (mut next, rollback) = match from {
+        PointOrOrigin::Origin(_) => (0, None),
+        PointOrOrigin::Point(Point { slot, hash }) => {
+            let chain = state.chain.lock().await;
+            match chain.get_block_by_hash(hash)? {
+                Some(block) => (block.height() + 1, None),
+                // Rolled back since, so the client goes back to the last
+                // block it can have in common with the chain.
+                None => match chain.get_block_before_slot(*slot)? {
+                    Some(ancestor) => (
+                        ancestor.height() + 1,
+                        Some(Message::Rollback {
+                            hash: ancestor.hash(),
+                            height: ancestor.height(),
+                            reverted: vec![hash.clone()],
+                        }),
+                    ),
+                    None => {
+                        return Err(eyre!(
+                            "The block {} is not stored, nor any before slot {}.",
+                            hash,
+                            slot
+                        ))
+                    }
+                },
+            }
+        }
+    };
+    if let Some(rollback) = rollback {
+        send(socket, &rollback).await?;
+    }
+    let mut last = None;
+    loop {
+        // The chain is only locked while reading, not while sending.
+        let (blocks, layout) = {
+            let chain = state.chain.lock().await;
+            let blocks = chain
+                .blocks_from(next, REPLAY_BATCH)
+                .collect::<Result<Vec<Block>>>()?;
+            (blocks, chain.layout)
+        };
+        for block in &blocks {
+            for message in subscription.messages(block, &layout) {
+                send(socket, &message).await?;
+            }
+            last = Some(block.height());
+            next = block.height() + 1;
+        }
+        if blocks.len() < REPLAY_BATCH {
+            return Ok(last);
+        }
+    }
+}
+
+/// Pushes the updates to a client until it disconnects.
+async fn session(
+    mut socket: Socket,
+    state: State,
+    mut subscription: Subscription,
+    from: Option<PointOrOrigin>,
+) -> Result<()> {
+    let mut updates = state.live.subscribe();
+    let layout = state.chain.lock().await.layout;
+    // Blocks added while resuming are received both ways.
+    let mut replayed = None;
+    if let Some(from) = &from {
+        match replay(&mut socket, &state, &subscription, from).await {
+            Ok(last) => replayed = last,
+            Err(e) => {
+                let message = Message::Error {
+                    message: e.to_string(),
+                };
+                send(&mut socket, &message).await?;
+                socket.close(None).await?;
+                return Ok(());
+            }
+        }
+    }
+
+    loop {
+        tokio::select! {
+            frame = socket.next() => match frame {
+                Some(Ok(Frame::Text(text))) => match serde_json::from_str(&text) {
+                    Ok(replaced) => subscription = replaced,
+                    Err(e) => {
+                        let message = Message::Error {
+                            message: format!("Invalid subscription: {}", e),
+                        };
+                        send(&mut socket, &message).await?;
+                    }
+                },
+                Some(Ok(Frame::Close(_))) | None => return Ok(()),
+                Some(Ok(_)) => (),
+                Some(Err(e)) => return Err(e.into()),
+            },
+            update = updates.recv() => match update {
+                Ok(Update::Block(block)) => {
+                    if replayed.map_or(false, |height| block.height() <= height) {
+                        continue;
+                    }
+                    for message in subscription.messages(&block, &layout) {
+                        send(&mut socket, &message).await?;
+                    }
+                }
+                Ok(Update::Broadcast(message)) => {
+                    if let Message::Rollback { .. } = *message {
+                        replayed = None;
+                    }
+                    send(&mut socket, &message).await?;
+                }
+                Err(RecvError::Lagged(missed)) => {
+                    send(&mut socket, &Message::Lagged { missed }).await?;
+                }
+                Err(RecvError::Closed) => {
+                    socket.close(None).await?;
+                    return Ok(());
+                }
+            },
+        }
+    }
+}
+
+/// Accepts the websocket connection of `req`, served in the background.
+pub fn upgrade(req: Request<Body>, state: &State) -> ApiResult {
+    let upgrading = req.headers().get(header::UPGRADE).map_or(false, |upgrade| {
+        upgrade.as_bytes().eq_ignore_ascii_case(b"websocket")
+    });
+    let key = match req.headers().get(header::SEC_WEBSOCKET_KEY) {
+        Some(key) if upgrading => derive_accept_key(key.as_bytes()),
+        _ => return Err(ApiError::bad_request("Expected a websocket connection.")),
+    };
+    let params = params(&req);
+    let subscription = Subscription::from_params(&params);
+    let from = match params.get("from") {
+        Some(from) => Some(
+            from.parse::<PointOrOrigin>()
+                .map_err(|e| ApiError::bad_request(e.to_string()))?,
+        ),
+        None => None,
+    };
+
+    let state = state.clone();
+    tokio::spawn(async move {
+        let socket = match hyper::upgrade::on(req).await {
+            Ok(upgraded) => WebSocketStream::from_raw_socket(upgraded, Role::Server, None).await,
+            Err(e) => {
+                warn!("Cannot accept a websocket connection: {}", e);
+                return;
+            }
+        };
+        if let Err(e) = session(socket, state, subscription, from).await {
+            debug!("Websocket client disconnected: {}", e);
+        }
+    });
+
+    Ok(Response::builder()
+        .status(StatusCode::SWITCHING_PROTOCOLS)
+        .header(header::UPGRADE, "websocket")
+        .header(header::CONNECTION, "Upgrade")
+        .header(header::SEC_WEBSOCKET_ACCEPT, key)
+        .body(Body::empty())
+        .unwrap())
+}
@@ -0,0 +1,207 @@
+//! HTTP API over the synced chain.
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use color_eyre::eyre::{Report, Result};
+use hyper::{header, Body, Method, Request, Response, StatusCode};
+use serde::Serialize;
+use tokio::sync::{broadcast, Mutex};
+use tracing::warn;
+
+use crate::chain::Chain;
+use crate::data::{Block, EpochLayout};
+use crate::query::{self, Lookup};
+use crate::server::Listener;
+use graphql::ChainSchema;
+use live::Update;
+
+pub mod graphql;
+pub mod live;
+pub mod rest;
+
+/// What the handlers have access to.
+#[derive(Debug, Clone)]
+pub struct State {
+    pub chain: Arc<Mutex<Chain>>,
+    /// Layout of the chain, read without locking it.
+    pub layout: EpochLayout,
+    /// Sends what the websocket clients are pushed.
+    pub live: broadcast::Sender<Update>,
+}
+
+impl State {
+    pub fn new(chain: Arc<Mutex<Chain>>, layout: EpochLayout) -> Self {
+        let (live, _) = broadcast::channel(live::BUFFER);
+        Self {
+            chain,
+            layout,
+            live,
+        }
+    }
+}
+
+/// Error answered with its status and a JSON body
+/// `{"error": {"status": 404, "message": "..."}}`.
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub message: String,
+}
+
+impl ApiError {
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::BAD_REQUEST,
+            message: message.into(),
+        }
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::NOT_FOUND,
+            message: message.into(),
+        }
+    }
+
+    fn response(&self) -> Response<Body> {
+        let body = serde_json::json!({
+            "error": { "status": self.status.as_u16(), "message": self.message }
+        });
+        json(self.status, &body)
+    }
+}
+
+/// Failures of the storage are internal errors.
+impl From<Report> for ApiError {
+    fn from(e: Report) -> Self {
+        Self {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            message: e.to_string(),
+        }
+    }
+}
+
+pub type ApiResult = Result<Response<Body>, ApiError>;
+
+pub fn json<T: Serialize>(status: StatusCode, value: &T) -> Response<Body> {
+    let body = serde_json::to_vec(value).unwrap_or_default();
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body))
+        .unwrap()
+}
+
+/// The first stored transaction with `id`, looked up `SCAN_BLOCKS` blocks
+/// at a time so that the chain is released in between.
+pub async fn find_tx(chain: &Mutex<Chain>, id: &str) -> Result<Option<(Block, serde_json::Value)>> {
+    let mut from = 0;
+    loop {
+        let lookup = query::tx_from(&*chain.lock().await, id, from)?;
+        match lookup {
+            Lookup::Found(found) => return Ok(Some(found)),
+            Lookup::Next(next) => from = next,
+            Lookup::Missing => return Ok(None),
+        }
+    }
+}
+
+/// Parameters of the query string.
+pub fn params(req: &Request<Body>) -> HashMap<String, String> {
+    req.uri()
+        .query()
+        .map(|query| {
+            url::form_urlencoded::parse(query.as_bytes())
+                .into_owned()
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Runs the GraphQL request of `req`, given as the JSON body of a POST or
+/// as the `query` and `variables` parameters of a GET.
+async fn graphql(req: Request<Body>, schema: &ChainSchema) -> ApiResult {
+    let request: async_graphql::Request = match *req.method() {
+        Method::GET => {
+            let params = params(&req);
+            let query = params
+                .get("query")
+                .ok_or_else(|| ApiError::bad_request("The query is missing."))?;
+            let variables = match params.get("variables") {
+                Some(variables) => serde_json::from_str(variables)
+                    .map_err(|e| ApiError::bad_request(format!("Invalid variables: {}", e)))?,
+                None => async_graphql::Variables::default(),
+            };
+            async_graphql::Request::new(query).variables(variables)
+        }
+        Method::POST => {
+            let body = hyper::body::to_bytes(req.into_body())
+                .await
+                .map_err(|e| ApiError::bad_request(e.to_string()))?;
+            serde_json::from_slice(&body)
+                .map_err(|e| ApiError::bad_request(format!("Invalid request: {}", e)))?
+        }
+        _ => {
+            return Err(ApiError {
+                status: StatusCode::METHOD_NOT_ALLOWED,
+                message: format!("{} is not allowed.", req.method()),
+            })
+        }
+    };
+    Ok(json(StatusCode::OK, &schema.execute(request).await))
+}
+
+async fn handle(req: Request<Body>, state: State, schema: ChainSchema) -> Response<Body> {
+    let method = req.method().clone();
+    let path = req.uri().path().to_string();
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    let result = match segments.as_slice() {
+        ["graphql"] => graphql(req, &schema).await,
+        ["live"] => live::upgrade(req, &state),
+        _ => rest::route(&req, &segments, &state).await,
+    };
+    match result {
+        Ok(response) => response,
+        Err(e) => {
+            if e.status.is_server_error() {
+                warn!("{} {}: {}", method, path, e.message);
+            }
+            e.response()
+        }
+    }
+}
+
+/// The API bound to its address, served by `run`.
+pub struct Api {
+    listener: Listener,
+    state: State,
+    schema: ChainSchema,
+}
+
+impl Api {
+    pub fn bind(addr: &SocketAddr, state: State) -> Result<Self> {
+        let schema = graphql::schema(state.clone());
+        Ok(Self {
+            listener: Listener::bind(addr)?,
+            state,
+            schema,
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.listener.local_addr()
+    }
+
+    /// Serves requests until `shutdown` resolves.
+    pub async fn run(self, shutdown: impl Future<Output = ()>) -> Result<()> {
+        let (state, schema) = (self.state, self.schema);
+        self.listener
+            .serve(
+                move |req| handle(req, state.clone(), schema.clone()),
+                shutdown,
+            )
+            .await
+    }
+}
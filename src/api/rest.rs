@@ -0,0 +1,169 @@
+//! JSON endpoints:
+//!
+//! ```text
+//! GET /tip
+//! GET /blocks?cursor=<height>&limit=<n>
+//! GET /blocks/<hash>
+//! GET /blocks/height/<height>
+//! GET /blocks/slot/<slot>
+//! GET /txs/<id>
+//! GET /addresses/<address>/outputs?cursor=<height>&limit=<n>
+//! GET /pools/<id>/certificates?cursor=<height>&limit=<n>
+//! GET /assets/<policy or policy.name>/mints?cursor=<height>&limit=<n>
+//! GET /epochs/<epoch>?cursor=<height>
+//! ```
+//!
+//! Lists are pages `{"data": [...], "next": "<cursor>"}`, the next one
+//! being requested with `?cursor=<cursor>` until `next` is null. A page
+//! reads `SCAN_BLOCKS` blocks at most, so it can hold fewer results than
+//! the limit, or none. An epoch is summed up the same way: each of its
+//! pages counts the transactions of the blocks it read.
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use hyper::{Body, Method, Request, StatusCode};
+use serde_json::json;
+
+use crate::api::{find_tx, json, params, ApiError, ApiResult, State};
+use crate::chain::{Chain, SyncProgress};
+use crate::data::Block;
+use crate::query::{self, Page};
+
+const DEFAULT_LIMIT: usize = 20;
+const MAX_LIMIT: usize = 100;
+
+fn parse<T: FromStr>(name: &str, value: &str) -> Result<T, ApiError> {
+    value
+        .parse()
+        .map_err(|_| ApiError::bad_request(format!("Invalid {} '{}'.", name, value)))
+}
+
+/// Height to start from and number of results of a page.
+fn page(params: &HashMap<String, String>) -> Result<(u64, usize), ApiError> {
+    let cursor = match params.get("cursor") {
+        Some(cursor) => parse("cursor", cursor)?,
+        None => 0,
+    };
+    let limit = match params.get("limit") {
+        Some(limit) => parse("limit", limit)?,
+        None => DEFAULT_LIMIT,
+    };
+    if limit == 0 || limit > MAX_LIMIT {
+        return Err(ApiError::bad_request(format!(
+            "The limit must be between 1 and {}.",
+            MAX_LIMIT
+        )));
+    }
+    Ok((cursor, limit))
+}
+
+fn found(block: Option<Block>, what: &str) -> ApiResult {
+    match block {
+        Some(block) => Ok(json(StatusCode::OK, &block)),
+        None => Err(ApiError::not_found(format!("No block {}.", what))),
+    }
+}
+
+fn tip(chain: &Chain) -> ApiResult {
+    let (synchronized, progress) = match chain.sync() {
+        SyncProgress::Synchronized(_) => (true, 100.0),
+        SyncProgress::Synchronizing(progress, _, _) => (false, progress),
+        SyncProgress::Unsynchronized => (false, 0.0),
+    };
+    Ok(json(
+        StatusCode::OK,
+        &json!({
+            "tip": chain.tip,
+            "height": chain.height(),
+            "synchronized": synchronized,
+            "progress": progress,
+        }),
+    ))
+}
+
+fn blocks(chain: &Chain, params: &HashMap<String, String>) -> ApiResult {
+    let (cursor, limit) = page(params)?;
+    let mut data = Vec::with_capacity(limit);
+    let mut next = None;
+    for block in chain.blocks_from(cursor, limit + 1) {
+        let block = block?;
+        if data.len() == limit {
+            next = Some(block.height().to_string());
+            break;
+        }
+        data.push(block);
+    }
+    Ok(json(StatusCode::OK, &Page { data, next }))
+}
+
+async fn tx(state: &State, id: &str) -> ApiResult {
+    match find_tx(&state.chain, id).await? {
+        Some((block, tx)) => Ok(json(
+            StatusCode::OK,
+            &json!({
+                "block": block.hash(),
+                "height": block.height(),
+                "slot": block.slot(),
+                "tx": tx,
+            }),
+        )),
+        None => Err(ApiError::not_found(format!("No transaction {}.", id))),
+    }
+}
+
+/// Answers `req`, whose path is split into `segments`.
+pub async fn route(req: &Request<Body>, segments: &[&str], state: &State) -> ApiResult {
+    if req.method() != Method::GET {
+        return Err(ApiError {
+            status: StatusCode::METHOD_NOT_ALLOWED,
+            message: format!("{} is not allowed.", req.method()),
+        });
+    }
+    let params = params(req);
+    // Looked up without holding the chain throughout.
+    if let ["txs", id] = segments {
+        return tx(state, id).await;
+    }
+    let chain = state.chain.lock().await;
+    match segments {
+        ["tip"] => tip(&chain),
+        ["blocks"] => blocks(&chain, &params),
+        ["blocks", "height", height] => {
+            let block = chain.get_block_by_height(parse("height", height)?)?;
+            found(block, &format!("at height {}", height))
+        }
+        ["blocks", "slot", slot] => {
+            let block = chain.get_block_by_slot(parse("slot", slot)?)?;
+            found(block, &format!("at slot {}", slot))
+        }
+        ["blocks", hash] => found(chain.get_block_by_hash(hash)?, hash),
+        ["addresses", address, "outputs"] => {
+            let (cursor, limit) = page(&params)?;
+            let page = query::payments(&chain, address, cursor, limit)?;
+            Ok(json(StatusCode::OK, &page))
+        }
+        ["pools", id, "certificates"] => {
+            let (cursor, limit) = page(&params)?;
+            Ok(json(
+                StatusCode::OK,
+                &query::pool(&chain, id, cursor, limit)?,
+            ))
+        }
+        ["assets", asset, "mints"] => {
+            let (cursor, limit) = page(&params)?;
+            Ok(json(
+                StatusCode::OK,
+                &query::asset(&chain, asset, cursor, limit)?,
+            ))
+        }
+        ["epochs", epoch] => {
+            let (cursor, _) = page(&params)?;
+            let epoch = query::epoch_page(&chain, parse("epoch", epoch)?, cursor)?;
+            Ok(json(StatusCode::OK, &epoch))
+        }
+        _ => Err(ApiError::not_found(format!(
+            "No endpoint at {}.",
+            req.uri().path()
+        ))),
+    }
+}
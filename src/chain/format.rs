@@ -0,0 +1,275 @@
+//! On-disk format of the chunks:
+//!
+//! ```text
+//! "MXCK" | version: u16 | blocks: u32 | table crc: u32
+//! for each block: offset: u64 | length: u32 | crc: u32
+//! for each block: zstd compressed JSON of the block
+//! ```
+//!
+//! Integers are little endian and offsets start from the beginning of the
+//! file, so that a single block is read without the others. Blocks are
+//! stored as JSON because bincode cannot read back the untagged enums and
+//! JSON metadata of `data`.
+//!
+//! Chunks written before this format have no header and hold the bincode
+//! of `Option<Vec<Block>>`: they are read as version 0 and `migrate`
+//! rewrites them. Those holding a transaction with metadata cannot be read
+//! back, as bincode cannot read its JSON blob, and their epoch must be
+//! synced again. A change to the structs of `data` bumps `VERSION`, with
+//! `decode` keeping an arm to read blocks of older versions.
+use std::convert::TryInto;
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use color_eyre::eyre::{eyre, Result};
+
+use crate::chain::index::{self, Index};
+use crate::chain::Chunk;
+use crate::data::Block;
+
+const MAGIC: &[u8; 4] = b"MXCK";
+pub const VERSION: u16 = 1;
+const HEADER_LEN: usize = 14;
+const ENTRY_LEN: usize = 16;
+const LEVEL: i32 = 3;
+
+/// Where a block is in the file.
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    offset: u64,
+    length: u32,
+    crc: u32,
+}
+
+#[derive(Debug)]
+struct Header {
+    version: u16,
+    entries: Vec<Entry>,
+}
+
+fn u16_at(bytes: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes(bytes[at..at + 2].try_into().unwrap())
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+}
+
+fn u64_at(bytes: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap())
+}
+
+/// Reads the header and block table, or `None` for a chunk written before
+/// this format.
+fn read_header(file: &mut File) -> Result<Option<Header>> {
+    let mut header = [0; HEADER_LEN];
+    if file.read_exact(&mut header).is_err() || &header[..4] != MAGIC {
+        return Ok(None);
+    }
+    let version = u16_at(&header, 4);
+    let count = u32_at(&header, 6) as usize;
+    let crc = u32_at(&header, 10);
+
+    // A corrupted count must not allocate more than the file holds.
+    let table_len = count * ENTRY_LEN;
+    if file.metadata()?.len() < (HEADER_LEN + table_len) as u64 {
+        return Err(eyre!(
+            "Corrupted chunk: the block table is longer than the file."
+        ));
+    }
+    let mut table = vec![0; table_len];
+    file.read_exact(&mut table)?;
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(&header[..10]);
+    hasher.update(&table);
+    if hasher.finalize() != crc {
+        return Err(eyre!(
+            "Corrupted chunk: the block table checksum does not match."
+        ));
+    }
+
+    let entries = table
+        .chunks(ENTRY_LEN)
+        .map(|entry| Entry {
+            offset: u64_at(entry, 0),
+            length: u32_at(entry, 8),
+            crc: u32_at(entry, 12),
+        })
+        .collect();
+    Ok(Some(Header { version, entries }))
+}
+
+fn read_entry(file: &mut File, version: u16, index: usize, entry: Entry) -> Result<Block> {
+    let mut bytes = vec![0; entry.length as usize];
+    file.seek(SeekFrom::Start(entry.offset))?;
+    file.read_exact(&mut bytes)?;
+    if crc32fast::hash(&bytes) != entry.crc {
+        return Err(eyre!(
+            "Corrupted chunk: the checksum of block {} does not match.",
+            index
+        ));
+    }
+    decode(version, &bytes)
+}
+
+fn decode(version: u16, bytes: &[u8]) -> Result<Block> {
+    match version {
+        1 => Ok(serde_json::from_slice(&zstd::decode_all(bytes)?)?),
+        _ => Err(eyre!("Unsupported chunk version {}.", version)),
+    }
+}
+
+fn read_legacy(path: &Path) -> Result<Vec<Block>> {
+    let data: Option<Vec<Block>> = bincode::deserialize(&fs::read(path)?).map_err(|e| {
+        eyre!(
+            "Cannot read the legacy chunk {} ({}): if it holds transaction metadata, sync its epoch again.",
+            path.display(),
+            e
+        )
+    })?;
+    Ok(data.unwrap_or_default())
+}
+
+/// Writes `blocks` in the current version.
+pub fn write(path: &Path, blocks: &[Block]) -> Result<()> {
+    let mut entries = Vec::with_capacity(blocks.len());
+    let mut body = Vec::new();
+    let mut offset = (HEADER_LEN + blocks.len() * ENTRY_LEN) as u64;
+    for block in blocks {
+        let bytes = zstd::encode_all(&serde_json::to_vec(block)?[..], LEVEL)?;
+        entries.push(Entry {
+            offset,
+            length: bytes.len() as u32,
+            crc: crc32fast::hash(&bytes),
+        });
+        offset += bytes.len() as u64;
+        body.extend(bytes);
+    }
+
+    let mut header = Vec::with_capacity(HEADER_LEN + entries.len() * ENTRY_LEN);
+    header.extend(MAGIC);
+    header.extend(VERSION.to_le_bytes());
+    header.extend((blocks.len() as u32).to_le_bytes());
+    let mut table = Vec::with_capacity(entries.len() * ENTRY_LEN);
+    for entry in &entries {
+        table.extend(entry.offset.to_le_bytes());
+        table.extend(entry.length.to_le_bytes());
+        table.extend(entry.crc.to_le_bytes());
+    }
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(&header);
+    hasher.update(&table);
+    header.extend(hasher.finalize().to_le_bytes());
+
+    header.extend(table);
+    header.extend(body);
+    write_atomic(path, &header)
+}
+
+/// Writes `bytes` to a temporary file renamed to `path` once synced, so that
+/// a crash never leaves a truncated file behind.
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let mut file = File::create(&tmp)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)?;
+
+    // The rename itself is only durable once the directory is synced.
+    #[cfg(unix)]
+    {
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+/// Reads every block of a chunk.
+pub fn read(path: &Path) -> Result<Vec<Block>> {
+    let mut file = File::open(path)?;
+    match read_header(&mut file)? {
+        Some(header) => header
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| read_entry(&mut file, header.version, i, *entry))
+            .collect(),
+        None => read_legacy(path),
+    }
+}
+
+/// Reads the block at `index` of a chunk, without reading the others.
+pub fn read_block(path: &Path, index: usize) -> Result<Option<Block>> {
+    Reader::open(path)?.block(index)
+}
+
+/// An open chunk, whose blocks are read one at a time through its block
+/// table. Legacy chunks are read whole, as they have no table.
+#[derive(Debug)]
+pub struct Reader {
+    file: File,
+    content: Content,
+}
+
+#[derive(Debug)]
+enum Content {
+    Table(Header),
+    Legacy(Vec<Block>),
+}
+
+impl Reader {
+    pub fn open(path: &Path) -> Result<Self> {
+        let mut file = File::open(path)?;
+        let content = match read_header(&mut file)? {
+            Some(header) => Content::Table(header),
+            None => Content::Legacy(read_legacy(path)?),
+        };
+        Ok(Self { file, content })
+    }
+
+    pub fn block(&mut self, index: usize) -> Result<Option<Block>> {
+        match &self.content {
+            Content::Table(header) => match header.entries.get(index) {
+                Some(entry) => read_entry(&mut self.file, header.version, index, *entry).map(Some),
+                None => Ok(None),
+            },
+            Content::Legacy(blocks) => Ok(blocks.get(index).cloned()),
+        }
+    }
+}
+
+/// Version of a chunk, 0 when written before this format.
+pub fn version(path: &Path) -> Result<u16> {
+    let mut file = File::open(path)?;
+    Ok(read_header(&mut file)?.map(|h| h.version).unwrap_or(0))
+}
+
+/// Rewrites the chunk of `epoch` in `dir` in the current version, with its
+/// index entries, returning whether it was of an older one.
+pub fn migrate(dir: &Path, epoch: u64) -> Result<bool> {
+    let path = Chunk::path(dir, epoch);
+    if version(&path)? == VERSION {
+        return Ok(false);
+    }
+    let blocks = read(&path)?;
+    write(&path, &blocks)?;
+    let entries: Vec<index::Entry> = blocks.iter().map(index::Entry::from).collect();
+    Index::dump(dir, epoch, &entries)?;
+    Ok(true)
+}
+
+/// Migrates every chunk of `dir`, returning how many were.
+pub fn migrate_all(dir: &Path) -> Result<usize> {
+    let mut migrated = 0;
+    for epoch in Chunk::epochs(dir)? {
+        if migrate(dir, epoch)? {
+            migrated += 1;
+        }
+    }
+    Ok(migrated)
+}
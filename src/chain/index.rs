@@ -0,0 +1,152 @@
+//! Block lookups by hash, height and slot. The entries of immutable blocks
+//! are written next to their chunk as `{epoch}.idx` and read back by `load`.
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::ops::RangeBounds;
+use std::path::Path;
+
+use color_eyre::eyre::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::chain::format;
+use crate::data::Block;
+
+/// Where a block is kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Location {
+    /// Position in the chunk of an epoch.
+    Immutable { epoch: u64, index: usize },
+    /// Number of the block among all the blocks added since the start.
+    Volatile(u64),
+}
+
+/// What is persisted for each block of a chunk, in chunk order.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub hash: String,
+    pub height: u64,
+    pub slot: u64,
+}
+
+impl From<&Block> for Entry {
+    fn from(block: &Block) -> Self {
+        Self {
+            hash: block.hash(),
+            height: block.height(),
+            slot: block.slot(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Index {
+    locations: HashMap<String, Location>,
+    heights: BTreeMap<u64, String>,
+    slots: BTreeMap<u64, String>,
+    /// First and last heights of the immutable blocks of each epoch.
+    epochs: BTreeMap<u64, (u64, u64)>,
+}
+
+impl Index {
+    /// Byron epoch boundary blocks share the height of the block before
+    /// them, so the first block added at a height or slot keeps it.
+    pub fn insert(&mut self, entry: Entry, location: Location) {
+        let at_height = self
+            .heights
+            .entry(entry.height)
+            .or_insert_with(|| entry.hash.clone());
+        if let (true, Location::Immutable { epoch, .. }) = (*at_height == entry.hash, location) {
+            let heights = self
+                .epochs
+                .entry(epoch)
+                .or_insert((entry.height, entry.height));
+            heights.0 = heights.0.min(entry.height);
+            heights.1 = heights.1.max(entry.height);
+        }
+        self.slots
+            .entry(entry.slot)
+            .or_insert_with(|| entry.hash.clone());
+        self.locations.insert(entry.hash, location);
+    }
+
+    pub fn remove(&mut self, entry: &Entry) {
+        if self.heights.get(&entry.height) == Some(&entry.hash) {
+            self.heights.remove(&entry.height);
+        }
+        if self.slots.get(&entry.slot) == Some(&entry.hash) {
+            self.slots.remove(&entry.slot);
+        }
+        self.locations.remove(&entry.hash);
+    }
+
+    pub fn locate(&self, hash: &str) -> Option<Location> {
+        self.locations.get(hash).copied()
+    }
+
+    pub fn hash_at_height(&self, height: u64) -> Option<&String> {
+        self.heights.get(&height)
+    }
+
+    pub fn hash_at_slot(&self, slot: u64) -> Option<&String> {
+        self.slots.get(&slot)
+    }
+
+    /// Hashes of the blocks within `heights`, in chain order.
+    pub fn heights<R: RangeBounds<u64>>(&self, heights: R) -> impl Iterator<Item = &String> {
+        self.heights.range(heights).map(|(_, hash)| hash)
+    }
+
+    /// Hashes of the blocks within `slots`, in chain order.
+    pub fn slots<R: RangeBounds<u64>>(&self, slots: R) -> impl DoubleEndedIterator<Item = &String> {
+        self.slots.range(slots).map(|(_, hash)| hash)
+    }
+
+    /// First and last heights of the immutable blocks of the epochs within
+    /// `epochs`.
+    pub fn epoch_heights<R: RangeBounds<u64>>(&self, epochs: R) -> Option<(u64, u64)> {
+        self.epochs
+            .range(epochs)
+            .map(|(_, heights)| *heights)
+            .reduce(|a, b| (a.0.min(b.0), a.1.max(b.1)))
+    }
+
+    pub fn last_height(&self) -> Option<u64> {
+        self.heights.keys().next_back().copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.locations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.locations.is_empty()
+    }
+
+    /// Writes the entries of the chunk of `epoch` to `dir`.
+    pub fn dump(dir: &Path, epoch: u64, entries: &[Entry]) -> Result<()> {
+        let bin = bincode::serialize(entries)?;
+        format::write_atomic(&dir.join(format!("{}.idx", epoch)), &bin)
+    }
+
+    /// Reads the entries of every chunk of `dir`.
+    pub fn load(dir: &Path) -> Result<Self> {
+        let mut epochs: Vec<u64> = fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().map(|e| e == "idx").unwrap_or(false))
+            .filter_map(|path| path.file_stem()?.to_str()?.parse().ok())
+            .collect();
+        // Chunks are read in order for the first block at a height to win.
+        epochs.sort_unstable();
+
+        let mut index = Self::default();
+        for epoch in epochs {
+            let bin = fs::read(dir.join(format!("{}.idx", epoch)))?;
+            let entries: Vec<Entry> = bincode::deserialize(&bin)?;
+            for (i, entry) in entries.into_iter().enumerate() {
+                index.insert(entry, Location::Immutable { epoch, index: i });
+            }
+        }
+        Ok(index)
+    }
+}
@@ -1,30 +1,165 @@
-use std::{collections::HashMap, io::Write};
+use std::collections::{HashMap, VecDeque};
+use std::ops::RangeBounds;
 
-use color_eyre::eyre::{eyre, Result};
-use std::fs::File;
-use tracing::{info, warn};
+use color_eyre::eyre::{eyre, Report, Result};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{error, warn};
 
-use crate::data::{Block, Point, PointOrOrigin, RResult, Tip};
+use crate::data::{Block, EpochLayout, Point, PointOrOrigin, RResult, Tip};
+use crate::mempool::MempoolEvent;
+use crate::ws::{ConnectionEvent, Throughput};
+
+pub mod format;
+pub mod index;
+pub mod verify;
+
+use index::{Entry, Index, Location};
+
+/// Number of blocks after which a block can no longer be rolled back.
+pub const SECURITY_PARAM: usize = 2160;
+
+/// Number of immutable blocks after which the chunk being filled is written.
+pub const FLUSH_EVERY: u64 = 1000;
 
 #[derive(Debug)]
 pub struct Chain {
+    /// Immutable blocks, by epoch.
     data: HashMap<u64, Chunk>,
+    /// The last `security_param` blocks, which may still be rolled back.
+    volatile: VecDeque<Block>,
+    security_param: usize,
+    /// Directory the chunks are written to.
+    dir: PathBuf,
+    /// Epoch of the chunk immutable blocks are added to.
+    immutable_epoch: Option<u64>,
+    /// Hash of the last immutable block, or of the intersection before any
+    /// block is final: rollbacks cannot go further back.
+    anchor: Option<String>,
+    /// Number of blocks which became immutable since the start.
+    finalized: u64,
+    /// The chunk being filled is written each time this many blocks became
+    /// immutable, so that a crash loses no more of them.
+    flush_every: u64,
+    index: Index,
     pub tip: Option<Tip>,
     pub current_epoch: u64,
+    /// Hash of the last block, which the next one must follow.
+    last: Option<String>,
+    pub on_break: LinkPolicy,
+    /// How the network splits its slots into epochs, and so blocks into
+    /// chunks.
+    pub layout: EpochLayout,
+    /// Approximate size of the blocks held in memory, in bytes.
+    memory: usize,
+    /// Blocks before this slot are followed but not added.
+    start_slot: u64,
+    /// The last chunk read from disk, kept open so that reading its blocks
+    /// one lookup after another does not read its block table again.
+    reader: Mutex<Option<(u64, format::Reader)>>,
 }
 
-#[derive(Debug)]
+/// What happens when a block does not follow the last one: stop syncing,
+/// intersect again from the last known blocks, or only log it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkPolicy {
+    Halt,
+    Reintersect,
+    Log,
+}
+
+impl Default for LinkPolicy {
+    fn default() -> Self {
+        Self::Halt
+    }
+}
+
+impl FromStr for LinkPolicy {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "halt" => Ok(Self::Halt),
+            "reintersect" => Ok(Self::Reintersect),
+            "log" => Ok(Self::Log),
+            _ => Err(eyre!(
+                "Unknown link policy '{}', expected halt, reintersect or log.",
+                s
+            )),
+        }
+    }
+}
+
+/// A block whose `prev_hash` is not the hash of the block before it.
+#[derive(Debug, Clone)]
+pub struct LinkBreak {
+    pub slot: u64,
+    pub hash: String,
+    pub prev_hash: String,
+    pub expected: String,
+}
+
+impl fmt::Display for LinkBreak {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Block {} at slot {} follows {} instead of {}",
+            self.hash, self.slot, self.prev_hash, self.expected
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
 pub enum SyncProgress<T> {
-    Synchronizing(T, Block, Tip),
+    Synchronizing(T, Box<Block>, Tip),
     Synchronized(Tip),
     Unsynchronized,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ChainEvent {
+    /// Blocks added to the chain.
     Collection(Vec<Block>),
     Synchronizing(SyncProgress<f32>),
     RevertFork(Vec<Block>),
+    Mempool(MempoolEvent),
+    Connection(ConnectionEvent),
+    Throughput(Throughput),
+    LinkBroken(LinkBreak),
+    /// Blocks which became immutable, oldest first.
+    Finalized(Vec<Block>),
+    /// A rollback to a point outside of the volatile blocks.
+    RollbackTooDeep(PointOrOrigin),
+    /// None of the points the source started from is on its chain, whose
+    /// tip is given when known: v6 servers do not report it.
+    IntersectionNotFound(Option<Tip>),
+    /// A complete chunk was written and dropped from memory.
+    Dumped {
+        epoch: u64,
+        blocks: usize,
+        elapsed: Duration,
+    },
+    /// A chunk could not be written, it is tried again when the next one
+    /// is, or for the chunk being filled at its next flush.
+    DumpFailed {
+        epoch: u64,
+        error: String,
+    },
+    /// The source is run again, on a new connection, from this many of the
+    /// last blocks.
+    Reintersecting(usize),
+    /// The engine stopped following the chain.
+    Halted(String),
+    /// Blocks held in memory, volatile or not written yet, and their
+    /// approximate size in bytes.
+    Memory {
+        blocks: usize,
+        bytes: usize,
+    },
 }
 
 #[derive(Debug)]
@@ -45,108 +180,578 @@ impl Chunk {
     //     self.data.is_some()
     // }
 
-    pub fn dump(&mut self) -> Result<()> {
-        let mut file = File::create(format!("data/{}.bin", self.epoch))?;
+    pub fn path(dir: &Path, epoch: u64) -> PathBuf {
+        dir.join(format!("{}.bin", epoch))
+    }
 
-        let bin = bincode::serialize(&self.data).unwrap();
+    /// Writes the chunk and its index entries to `dir`.
+    pub fn write(&self, dir: &Path) -> Result<()> {
+        if let Some(blocks) = &self.data {
+            fs::create_dir_all(dir)?;
+            format::write(&Self::path(dir, self.epoch), blocks)?;
+            let entries: Vec<Entry> = blocks.iter().map(Entry::from).collect();
+            Index::dump(dir, self.epoch, &entries)?;
+        }
+        Ok(())
+    }
 
-        file.write_all(&bin)?;
+    /// Writes the chunk, then drops the blocks from memory.
+    pub fn dump(&mut self, dir: &Path) -> Result<()> {
+        self.write(dir)?;
         self.data = None;
 
         Ok(())
     }
 
-    // pub fn load(&mut self) -> Result<()> {
-    //     let mut file = File::open(format!("data/{}.bin", self.epoch))?;
-    //     let mut buf = Vec::new();
-    //     file.read_to_end(&mut buf);
+    /// Epochs of the chunks written to `dir`, in order.
+    pub fn epochs(dir: &Path) -> Result<Vec<u64>> {
+        let mut epochs: Vec<u64> = fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().map(|e| e == "bin").unwrap_or(false))
+            .filter_map(|path| path.file_stem()?.to_str()?.parse().ok())
+            .collect();
+        epochs.sort_unstable();
+        Ok(epochs)
+    }
 
-    //     self.data = Some(bincode::deserialize(&buf)?);
-    //     Ok(())
-    // }
+    /// Reads the blocks of a chunk written by `dump`.
+    pub fn load(path: &Path) -> Result<Vec<Block>> {
+        format::read(path)
+    }
 }
 
 impl Chain {
-    pub fn new(_buffer_capacity: usize) -> Self {
+    pub fn new(security_param: usize, on_break: LinkPolicy) -> Self {
         Self {
             data: HashMap::new(),
+            volatile: VecDeque::new(),
+            security_param,
+            dir: PathBuf::from("data"),
+            immutable_epoch: None,
+            anchor: None,
+            finalized: 0,
+            flush_every: FLUSH_EVERY,
+            index: Index::default(),
             tip: None,
             current_epoch: 0,
+            last: None,
+            on_break,
+            layout: EpochLayout::default(),
+            memory: 0,
+            start_slot: 0,
+            reader: Mutex::new(None),
+        }
+    }
+
+    /// The blocks stored in `dir`, to look them up without syncing.
+    pub fn open(dir: &Path) -> Result<Self> {
+        let mut chain = Self::new(SECURITY_PARAM, LinkPolicy::default()).data_dir(dir.to_owned());
+        chain.index = Index::load(dir)?;
+        Ok(chain)
+    }
+
+    /// Goes on from the blocks stored in the data directory: the chunk of
+    /// the last epoch is read back as the one being filled.
+    pub fn resume(mut self) -> Result<Self> {
+        if !self.dir.exists() {
+            return Ok(self);
+        }
+        self.index = Index::load(&self.dir)?;
+        let epoch = match Chunk::epochs(&self.dir)?.last() {
+            Some(epoch) => *epoch,
+            None => return Ok(self),
+        };
+        let blocks = Chunk::load(&Chunk::path(&self.dir, epoch))?;
+        if let Some(last) = blocks.last() {
+            self.last = Some(last.hash());
+            self.anchor = Some(last.hash());
+            self.current_epoch = epoch;
+        }
+        self.memory += blocks.iter().map(size).sum::<usize>();
+        self.immutable_epoch = Some(epoch);
+        self.data.insert(
+            epoch,
+            Chunk {
+                data: Some(blocks),
+                epoch,
+            },
+        );
+        Ok(self)
+    }
+
+    /// The point a resumed chain starts from: its last stored block, or
+    /// origin when none is.
+    pub fn resume_point(&self) -> Result<PointOrOrigin> {
+        match self.last_immutable() {
+            None => Ok(PointOrOrigin::origin()),
+            Some(Block::Byron(_)) => Err(eyre!(
+                "The last stored block is a Byron block, whose slot is not known: start from a point after its epoch."
+            )),
+            Some(block) => Ok(PointOrOrigin::point(block.slot(), block.hash())),
         }
     }
 
-    pub fn add(&mut self, action: RResult) -> ChainEvent {
+    /// Checks that starting from `point` only adds blocks after the stored
+    /// ones: the chunk of an epoch already stored would be written again
+    /// with the blocks following the point only.
+    pub fn check_start(&self, point: &PointOrOrigin) -> Result<()> {
+        let (epoch, last) = match (self.immutable_epoch, self.last_immutable()) {
+            (Some(epoch), Some(last)) => (epoch, last),
+            _ => return Ok(()),
+        };
+        match point {
+            PointOrOrigin::Point(Point { hash, .. }) if *hash == last.hash() => Ok(()),
+            PointOrOrigin::Point(Point { slot, .. }) if self.layout.epoch(*slot) > epoch => Ok(()),
+            _ => Err(eyre!(
+                "Starting from {} would write the stored epoch {} again, start from the last stored block instead.",
+                point,
+                epoch
+            )),
+        }
+    }
+
+    /// Writes the chunks to `dir` instead of `data`.
+    pub fn data_dir(mut self, dir: PathBuf) -> Self {
+        self.dir = dir;
+        self
+    }
+
+    /// Splits the blocks into epochs with `layout` instead of the mainnet
+    /// one.
+    pub fn epoch_layout(mut self, layout: EpochLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// Leaves out the blocks before `slot`, as a source can only intersect
+    /// at a known block: the ones up to `slot` are followed, not added.
+    pub fn start_at(mut self, slot: u64) -> Self {
+        self.start_slot = slot;
+        self
+    }
+
+    /// Writes the chunk being filled every `blocks` immutable blocks.
+    pub fn flush_every(mut self, blocks: u64) -> Self {
+        self.flush_every = blocks.max(1);
+        self
+    }
+
+    /// The last immutable block still held in memory.
+    fn last_immutable(&self) -> Option<&Block> {
+        self.immutable_epoch
+            .and_then(|epoch| self.data.get(&epoch))
+            .and_then(|chunk| chunk.data.as_ref())
+            .and_then(|blocks| blocks.last())
+    }
+
+    fn last_block(&self) -> Option<&Block> {
+        self.volatile.back().or_else(|| self.last_immutable())
+    }
+
+    /// Points of the last blocks held in memory, most recent first, to
+    /// intersect again from. Byron blocks are left out as their slot is
+    /// not known.
+    pub fn points(&self) -> Vec<PointOrOrigin> {
+        let immutable = self
+            .immutable_epoch
+            .and_then(|epoch| self.data.get(&epoch))
+            .and_then(|chunk| chunk.data.as_ref())
+            .into_iter()
+            .flat_map(|blocks| blocks.iter().rev());
+        self.volatile
+            .iter()
+            .rev()
+            .chain(immutable)
+            .filter(|block| !matches!(block, Block::Byron(_)))
+            .take(10)
+            .map(|block| PointOrOrigin::point(block.slot(), block.hash()))
+            .collect()
+    }
+
+    pub fn add(&mut self, action: RResult) -> Vec<ChainEvent> {
         // info!("{:?}", &action);
         match action {
+            RResult::RollForward { block, tip } if self.before_start(&block) => {
+                self.skip(block.hash(), tip)
+            }
             RResult::RollForward { block, tip } => {
-                self.tip = Some(tip);
-                let epoch = block.epoch();
-                // dbg!(block.epoch());
-                // dbg!(!self.data.contains_key(&block.epoch()));
-                if !self.data.contains_key(&block.epoch()) {
-                    self.data.insert(epoch, Chunk::new(epoch));
-                    self.current_epoch = epoch;
-                    if self.current_epoch != 0 {
-                        self.data.get_mut(&(self.current_epoch - 1)).unwrap().dump();
+                let broken = match &self.last {
+                    Some(last) if block.prev_hash() != *last => Some(LinkBreak {
+                        slot: block.slot(),
+                        hash: block.hash(),
+                        prev_hash: block.prev_hash(),
+                        expected: last.clone(),
+                    }),
+                    _ => None,
+                };
+                if let Some(broken) = broken {
+                    warn!("{}", broken);
+                    let mut events = vec![ChainEvent::LinkBroken(broken)];
+                    if self.on_break == LinkPolicy::Log {
+                        events.extend(self.append(block, tip));
                     }
+                    return events;
                 }
-                // dbg!(self.data.get_mut(&epoch));
-                match self.data.get_mut(&epoch) {
-                    Some(chunk) => chunk.data.as_mut().unwrap().push(block),
-                    None => (),
+                let mut events = self.append(block, tip);
+                events.push(ChainEvent::Synchronizing(self.sync()));
+                events
+            }
+            RResult::RollBackward {
+                point: PointOrOrigin::Point(Point { slot, hash }),
+                tip,
+            } if slot < self.start_slot && self.volatile.is_empty() => self.skip(hash, tip),
+            RResult::RollBackward { point, tip } => vec![self.rollback(point, tip)],
+            RResult::IntersectionNotFound { tip } => vec![ChainEvent::IntersectionNotFound(tip)],
+            // Only a new intersection once blocks were added moves the chain
+            // back, e.g. when intersecting again after a break.
+            RResult::IntersectionFound { point, tip } => {
+                if self.finalized > 0 || !self.volatile.is_empty() {
+                    return vec![self.rollback(point, tip)];
+                }
+                let hash = match &point {
+                    PointOrOrigin::Point(Point { hash, .. }) => Some(hash.clone()),
+                    PointOrOrigin::Origin(_) => None,
+                };
+                // A resumed chain found elsewhere than its last block no
+                // longer fills the chunk read back.
+                if hash != self.last {
+                    self.release();
                 }
+                self.last = hash.clone();
+                self.anchor = hash;
+                self.tip = Some(tip);
+                vec![ChainEvent::Synchronizing(self.sync())]
+            }
+        }
+    }
 
-                // if let Some(c) = self.collect() {
-                //     Some(ChainEvent::Collection(c))
-                // } else {
-                //     None
-                // }
-                ChainEvent::Synchronizing(self.sync())
+    /// Whether `block` comes before the slot the chain starts at. Byron
+    /// blocks are placed by the first slot of their epoch.
+    fn before_start(&self, block: &Block) -> bool {
+        let slot = match block {
+            Block::Byron(_) => self.layout.first_slot(block.epoch(&self.layout)),
+            _ => block.slot(),
+        };
+        slot < self.start_slot
+    }
+
+    /// Follows a block before the start without adding it, so that the
+    /// first one added links to it.
+    fn skip(&mut self, hash: String, tip: Tip) -> Vec<ChainEvent> {
+        self.last = Some(hash.clone());
+        self.anchor = Some(hash);
+        self.tip = Some(tip);
+        Vec::new()
+    }
+
+    /// Adds a block to the volatile ones, moving the oldest to the
+    /// immutable chunks once there are more than `security_param`.
+    fn append(&mut self, block: Block, tip: Tip) -> Vec<ChainEvent> {
+        self.tip = Some(tip);
+        self.last = Some(block.hash());
+        self.current_epoch = block.epoch(&self.layout);
+        let number = self.finalized + self.volatile.len() as u64;
+        self.index
+            .insert(Entry::from(&block), Location::Volatile(number));
+        self.memory += size(&block);
+        let mut events = vec![ChainEvent::Collection(vec![block.clone()])];
+        self.volatile.push_back(block);
+
+        let excess = self.volatile.len().saturating_sub(self.security_param);
+        if excess == 0 {
+            return events;
+        }
+        let finalized: Vec<Block> = self.volatile.drain(..excess).collect();
+        for block in &finalized {
+            let started = self.immutable_epoch != Some(block.epoch(&self.layout));
+            self.finalized += 1;
+            let location = self.store(block.clone());
+            self.index.insert(Entry::from(block), location);
+            if started {
+                events.extend(self.dump_complete());
             }
-            RResult::RollBackward { point, tip } => {
-                let epoch = point.epoch();
-                let idx = if !self.data.is_empty() {
-                    self.data
-                        .get(&epoch)
-                        .unwrap()
-                        .data
-                        .as_ref()
-                        .unwrap()
-                        .iter()
-                        .position(|block| {
-                            if let PointOrOrigin::Point(Point { hash, .. }) = &point {
-                                block.hash() == *hash
-                            } else {
-                                false
-                            }
-                        })
-                } else {
-                    None
-                };
+            if self.finalized % self.flush_every == 0 {
+                events.extend(self.write_partial());
+            }
+        }
+        events.push(ChainEvent::Finalized(finalized));
+        events
+    }
 
-                self.tip = Some(tip);
+    /// Adds an immutable block to the chunk of its epoch.
+    fn store(&mut self, block: Block) -> Location {
+        let epoch = block.epoch(&self.layout);
+        self.anchor = Some(block.hash());
+        if self.immutable_epoch != Some(epoch) {
+            self.immutable_epoch = Some(epoch);
+            self.data.insert(epoch, Chunk::new(epoch));
+            // Its file is written again, and the open one stale.
+            *self.reader.get_mut().unwrap() = None;
+        }
+        let blocks = self
+            .data
+            .get_mut(&epoch)
+            .and_then(|c| c.data.as_mut())
+            .expect("the chunk being filled is in memory");
+        blocks.push(block);
+        Location::Immutable {
+            epoch,
+            index: blocks.len() - 1,
+        }
+    }
 
-                match idx {
-                    Some(i) => {
-                        warn!(
-                            "Reverting block: {} / {}",
-                            i,
-                            self.data.get(&epoch).unwrap().data.as_ref().unwrap().len()
-                        );
-                        let d = self
-                            .data
-                            .get_mut(&epoch)
-                            .unwrap()
-                            .data
-                            .as_mut()
-                            .unwrap()
-                            .drain(i..);
-                        ChainEvent::RevertFork(d.collect())
-                    }
-                    None => ChainEvent::Synchronizing(self.sync()),
+    /// Drops the chunk being filled from memory, as it is already written.
+    fn release(&mut self) {
+        if let Some(chunk) = self
+            .immutable_epoch
+            .take()
+            .and_then(|e| self.data.remove(&e))
+        {
+            let bytes: usize = chunk.data.iter().flatten().map(size).sum();
+            self.memory = self.memory.saturating_sub(bytes);
+        }
+    }
+
+    /// Blocks become immutable in order, so the chunks before the one being
+    /// filled are complete and dumped, retrying those which failed before.
+    fn dump_complete(&mut self) -> Vec<ChainEvent> {
+        let mut epochs: Vec<u64> = self
+            .data
+            .values()
+            .filter(|chunk| chunk.data.is_some() && Some(chunk.epoch) != self.immutable_epoch)
+            .map(|chunk| chunk.epoch)
+            .collect();
+        epochs.sort_unstable();
+
+        let mut events = Vec::new();
+        for epoch in epochs {
+            let chunk = self.data.get_mut(&epoch).unwrap();
+            let blocks = chunk.data.as_deref().unwrap_or_default();
+            let (count, bytes) = (blocks.len(), blocks.iter().map(size).sum::<usize>());
+            let started = Instant::now();
+            match chunk.dump(&self.dir) {
+                Ok(()) => {
+                    self.memory = self.memory.saturating_sub(bytes);
+                    events.push(ChainEvent::Dumped {
+                        epoch,
+                        blocks: count,
+                        elapsed: started.elapsed(),
+                    });
+                }
+                Err(e) => {
+                    warn!("Cannot dump epoch {}: {}", epoch, e);
+                    events.push(ChainEvent::DumpFailed {
+                        epoch,
+                        error: e.to_string(),
+                    });
                 }
             }
-            _ => unimplemented!(),
+        }
+        events
+    }
+
+    /// Writes the chunk being filled, keeping its blocks in memory.
+    fn write_partial(&self) -> Option<ChainEvent> {
+        let epoch = self.immutable_epoch?;
+        let error = self.data.get(&epoch)?.write(&self.dir).err()?;
+        warn!("Cannot write epoch {}: {}", epoch, error);
+        Some(ChainEvent::DumpFailed {
+            epoch,
+            error: error.to_string(),
+        })
+    }
+
+    /// Writes every immutable block still in memory, including those of the
+    /// epoch being filled, e.g. before exiting.
+    pub fn flush(&mut self) -> Result<()> {
+        let failed = self
+            .dump_complete()
+            .into_iter()
+            .find_map(|event| match event {
+                ChainEvent::DumpFailed { epoch, error } => Some((epoch, error)),
+                _ => None,
+            });
+        if let Some((epoch, error)) = failed {
+            return Err(eyre!("Cannot write epoch {}: {}", epoch, error));
+        }
+        match self.immutable_epoch.and_then(|epoch| self.data.get(&epoch)) {
+            Some(chunk) => chunk.write(&self.dir),
+            None => Ok(()),
+        }
+    }
+
+    /// Reverts the volatile blocks after `point`. Immutable blocks are never
+    /// reverted, so a rollback past them is refused.
+    fn rollback(&mut self, point: PointOrOrigin, tip: Tip) -> ChainEvent {
+        let hash = match &point {
+            PointOrOrigin::Point(Point { hash, .. }) => Some(hash.clone()),
+            PointOrOrigin::Origin(_) => None,
+        };
+        let location = hash.as_deref().and_then(|h| self.index.locate(h));
+        let keep = match location {
+            Some(Location::Volatile(number)) => (number - self.finalized) as usize + 1,
+            _ if hash == self.anchor => 0,
+            _ => {
+                error!(
+                    "Cannot roll back to {}, past the last {} blocks",
+                    point, self.security_param
+                );
+                return ChainEvent::RollbackTooDeep(point);
+            }
+        };
+
+        self.tip = Some(tip);
+        self.last = hash;
+        let reverted: Vec<Block> = self.volatile.drain(keep..).collect();
+        for block in &reverted {
+            self.index.remove(&Entry::from(block));
+            self.memory = self.memory.saturating_sub(size(block));
+        }
+        if let Some(epoch) = self.last_block().map(|b| b.epoch(&self.layout)) {
+            self.current_epoch = epoch;
+        }
+
+        if reverted.is_empty() {
+            ChainEvent::Synchronizing(self.sync())
+        } else {
+            warn!("Reverting {} blocks", reverted.len());
+            ChainEvent::RevertFork(reverted)
+        }
+    }
+
+    pub fn get_block_by_hash(&self, hash: &str) -> Result<Option<Block>> {
+        match self.index.locate(hash) {
+            Some(location) => self.get(location),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_block_by_height(&self, height: u64) -> Result<Option<Block>> {
+        match self.index.hash_at_height(height) {
+            Some(hash) => self.get_block_by_hash(hash),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_block_by_slot(&self, slot: u64) -> Result<Option<Block>> {
+        match self.index.hash_at_slot(slot) {
+            Some(hash) => self.get_block_by_hash(hash),
+            None => Ok(None),
+        }
+    }
+
+    /// The last block before `slot`, Byron blocks left aside.
+    pub fn get_block_before_slot(&self, slot: u64) -> Result<Option<Block>> {
+        match self.index.slots(..slot).next_back() {
+            Some(hash) => self.get_block_by_hash(hash),
+            None => Ok(None),
+        }
+    }
+
+    /// Number of blocks held in memory, volatile or not written yet.
+    pub fn blocks_in_memory(&self) -> usize {
+        let immutable: usize = self
+            .data
+            .values()
+            .filter_map(|chunk| chunk.data.as_ref())
+            .map(Vec::len)
+            .sum();
+        self.volatile.len() + immutable
+    }
+
+    /// Approximate size of the blocks held in memory, in bytes.
+    pub fn memory(&self) -> usize {
+        self.memory
+    }
+
+    /// Height of the last block known.
+    pub fn height(&self) -> Option<u64> {
+        self.index.last_height()
+    }
+
+    /// First and last heights of the blocks of the epochs within `epochs`.
+    /// Epochs are looked up by height, as Byron blocks are not indexed by
+    /// their slot.
+    pub fn epoch_heights<R: RangeBounds<u64>>(&self, epochs: R) -> Option<(u64, u64)> {
+        let epochs = (epochs.start_bound().cloned(), epochs.end_bound().cloned());
+        let volatile = self
+            .volatile
+            .iter()
+            .filter(|block| epochs.contains(&block.epoch(&self.layout)))
+            .map(|block| (block.height(), block.height()));
+        self.index
+            .epoch_heights(epochs)
+            .into_iter()
+            .chain(volatile)
+            .reduce(|a, b| (a.0.min(b.0), a.1.max(b.1)))
+    }
+
+    /// Blocks within `heights`, in chain order.
+    pub fn blocks_by_height<R: RangeBounds<u64>>(&self, heights: R) -> Blocks<'_> {
+        Blocks::new(self, self.index.heights(heights).cloned().collect())
+    }
+
+    /// Where the block `hash` is stored, if it is.
+    pub fn locate(&self, hash: &str) -> Option<Location> {
+        self.index.locate(hash)
+    }
+
+    /// Hashes of the blocks from height `from`, in chain order, read from
+    /// the index alone.
+    pub fn hashes_from(&self, from: u64) -> Vec<String> {
+        self.index.heights(from..).cloned().collect()
+    }
+
+    /// The first `count` blocks from height `from`, in chain order.
+    pub fn blocks_from(&self, from: u64, count: usize) -> Blocks<'_> {
+        Blocks::new(
+            self,
+            self.index.heights(from..).take(count).cloned().collect(),
+        )
+    }
+
+    /// Blocks of the epochs within `epochs`, in chain order.
+    pub fn blocks_by_epoch<R: RangeBounds<u64>>(&self, epochs: R) -> Blocks<'_> {
+        match self.epoch_heights(epochs) {
+            Some((first, last)) => self.blocks_by_height(first..=last),
+            None => Blocks::new(self, Vec::new()),
+        }
+    }
+
+    /// Blocks within `slots`, in chain order.
+    pub fn blocks_by_slot<R: RangeBounds<u64>>(&self, slots: R) -> Blocks<'_> {
+        Blocks::new(self, self.index.slots(slots).cloned().collect())
+    }
+
+    /// Reads a block from memory, or from its chunk once dumped.
+    fn get(&self, location: Location) -> Result<Option<Block>> {
+        match location {
+            Location::Volatile(number) => {
+                let i = number.checked_sub(self.finalized);
+                Ok(i.and_then(|i| self.volatile.get(i as usize)).cloned())
+            }
+            Location::Immutable { epoch, index } => {
+                match self.data.get(&epoch).and_then(|c| c.data.as_ref()) {
+                    Some(blocks) => Ok(blocks.get(index).cloned()),
+                    None => self.read(epoch, index),
+                }
+            }
+        }
+    }
+
+    /// Reads a block of a dumped chunk, opening it unless it is the last
+    /// one read.
+    fn read(&self, epoch: u64, index: usize) -> Result<Option<Block>> {
+        let mut reader = self.reader.lock().unwrap();
+        match &mut *reader {
+            Some((open, reader)) if *open == epoch => reader.block(index),
+            _ => {
+                let mut opened = format::Reader::open(&Chunk::path(&self.dir, epoch))?;
+                let block = opened.block(index);
+                *reader = Some((epoch, opened));
+                block
+            }
         }
     }
 
@@ -161,13 +766,8 @@ impl Chain {
 
     pub fn sync(&self) -> SyncProgress<f32> {
         // dbg!(&self.data.get(&self.current_epoch));
-        match (
-            self.data
-                .get(&self.current_epoch)
-                .map(|c| c.data.as_ref().unwrap().last().unwrap()),
-            &self.tip,
-        ) {
-            (Some(ref block), Some(ref tip)) => {
+        match (self.last_block(), &self.tip) {
+            (Some(block), Some(tip)) => {
                 if block.hash() == tip.hash {
                     // info!("{} {}", block.slot(), block.hash());
                     // info!("{} {}", tip.slot, tip.hash);
@@ -181,16 +781,50 @@ impl Chain {
 
                     SyncProgress::Synchronizing(
                         block.slot() as f32 / tip.slot as f32 * 100.0,
-                        (**block).clone(),
+                        Box::new(block.clone()),
                         tip.clone(),
                     )
                 }
             }
+            // Started from the tip, before any block was added.
+            (None, Some(tip)) if self.last.as_ref() == Some(&tip.hash) => {
+                SyncProgress::Synchronized(tip.clone())
+            }
             _ => SyncProgress::Unsynchronized,
         }
     }
 }
 
+/// Size of `block` once encoded, as an estimate of the memory it takes.
+fn size(block: &Block) -> usize {
+    bincode::serialized_size(block).unwrap_or_default() as usize
+}
+
+/// Iterator over blocks found in the index, read one at a time.
+pub struct Blocks<'a> {
+    chain: &'a Chain,
+    hashes: std::vec::IntoIter<String>,
+}
+
+impl<'a> Blocks<'a> {
+    fn new(chain: &'a Chain, hashes: Vec<String>) -> Self {
+        Self {
+            chain,
+            hashes: hashes.into_iter(),
+        }
+    }
+}
+
+impl<'a> Iterator for Blocks<'a> {
+    type Item = Result<Block>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let hash = self.hashes.next()?;
+        let location = self.chain.index.locate(&hash)?;
+        self.chain.get(location).transpose()
+    }
+}
+
 // impl<H, E> iced_native::subscription::Recipe<H, E> for Chain
 // where
 //     H: Hasher,
@@ -0,0 +1,107 @@
+//! Offline check of the chunks dumped by `Chain`, looking for blocks which
+//! do not follow each other and for missing blocks or epochs.
+use std::fmt;
+use std::path::Path;
+
+use color_eyre::eyre::Result;
+
+use crate::chain::{Chunk, LinkBreak};
+use crate::data::Block;
+
+#[derive(Debug)]
+pub enum Issue {
+    Unreadable { epoch: u64, error: String },
+    MissingEpochs { from: u64, to: u64 },
+    Break(LinkBreak),
+    Gap { after: String, missing: u64 },
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unreadable { epoch, error } => {
+                write!(f, "Cannot read epoch {}: {}", epoch, error)
+            }
+            Self::MissingEpochs { from, to } if from == to => {
+                write!(f, "Epoch {} is missing", from)
+            }
+            Self::MissingEpochs { from, to } => write!(f, "Epochs {} to {} are missing", from, to),
+            Self::Break(broken) => write!(f, "{}", broken),
+            Self::Gap { after, missing } => {
+                write!(f, "{} blocks are missing after {}", missing, after)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Report {
+    pub chunks: usize,
+    pub blocks: usize,
+    pub issues: Vec<Issue>,
+}
+
+/// Byron epoch boundary blocks share the height of the block before them.
+fn expected_height(block: &Block, last: u64) -> u64 {
+    match block {
+        Block::Byron(b) if b.body.is_none() => last,
+        _ => last + 1,
+    }
+}
+
+/// Scans the `{epoch}.bin` chunks of `dir` in epoch order.
+pub fn verify(dir: &Path) -> Result<Report> {
+    let epochs = Chunk::epochs(dir)?;
+
+    let mut report = Report::default();
+    let mut previous_epoch: Option<u64> = None;
+    let mut last: Option<(String, u64)> = None;
+
+    for epoch in epochs {
+        if let Some(previous) = previous_epoch {
+            if epoch > previous + 1 {
+                report.issues.push(Issue::MissingEpochs {
+                    from: previous + 1,
+                    to: epoch - 1,
+                });
+            }
+        }
+        previous_epoch = Some(epoch);
+        report.chunks += 1;
+
+        let blocks = match Chunk::load(&Chunk::path(dir, epoch)) {
+            Ok(blocks) => blocks,
+            Err(e) => {
+                report.issues.push(Issue::Unreadable {
+                    epoch,
+                    error: e.to_string(),
+                });
+                last = None;
+                continue;
+            }
+        };
+
+        for block in blocks {
+            report.blocks += 1;
+            if let Some((hash, height)) = &last {
+                let expected = expected_height(&block, *height);
+                if block.height() > expected {
+                    report.issues.push(Issue::Gap {
+                        after: hash.clone(),
+                        missing: block.height() - expected,
+                    });
+                } else if block.prev_hash() != *hash {
+                    report.issues.push(Issue::Break(LinkBreak {
+                        slot: block.slot(),
+                        hash: block.hash(),
+                        prev_hash: block.prev_hash(),
+                        expected: hash.clone(),
+                    }));
+                }
+            }
+            last = Some((block.hash(), block.height()));
+        }
+    }
+
+    Ok(report)
+}
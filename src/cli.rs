@@ -1,20 +1,405 @@
+use std::fmt::Display;
+use std::net::SocketAddr;
+use std::ops::Bound;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use color_eyre::eyre::{eyre, Result};
+use structopt::clap::AppSettings;
 use structopt::StructOpt;
 use tokio_tungstenite::tungstenite::http::Uri;
 
+use crate::chain::{Blocks, Chain, LinkPolicy};
+use crate::data::protocol::OgmiosVersion;
+use crate::data::{EpochLayout, PointOrOrigin};
+use crate::export::Format;
+use crate::n2c::NodeClient;
+use crate::source::{ChainSource, Recording, Replay};
+use crate::ws::{ErrorPolicy, Ogmios, Pipelining};
+
 #[derive(Debug, StructOpt)]
 #[structopt(
     name = "Mini-Explorer",
-    about = "Tool to connect to an Ogmios server to process data."
+    about = "Tool to connect to an Ogmios server to process data.",
+    setting = AppSettings::SubcommandRequiredElseHelp
 )]
 pub struct CLI {
+    /// Configuration file, mini-explorer.toml when present.
+    #[structopt(long, global = true, env = "MINI_EXPLORER_CONFIG")]
+    pub config: Option<PathBuf>,
+    /// Profile of the configuration file: mainnet, preprod or one it
+    /// defines.
+    #[structopt(long, global = true, env = "MINI_EXPLORER_PROFILE")]
+    pub profile: Option<String>,
+    /// Log level: error, warn, info, debug or trace.
+    #[structopt(long, global = true, env = "MINI_EXPLORER_LOG", default_value = "info")]
+    pub log: tracing::Level,
+    #[structopt(subcommand)]
+    pub command: Command,
+}
+
+impl CLI {
+    /// Checks the options which structopt cannot check on its own.
+    pub fn validate(&self) -> Result<()> {
+        match &self.command {
+            Command::Gui(sync) | Command::Sync(sync) | Command::Serve { sync, .. } => {
+                sync.source.validate()
+            }
+            Command::Query { .. }
+            | Command::Export(_)
+            | Command::Verify(_)
+            | Command::Migrate(_)
+            | Command::Config(_) => Ok(()),
+        }
+    }
+}
+
+/// Parses a count which must not be 0.
+fn positive<T: FromStr + Default + PartialEq>(s: &str) -> Result<T>
+where
+    T::Err: Display,
+{
+    match s.parse::<T>() {
+        Ok(n) if n == T::default() => Err(eyre!("must be at least 1")),
+        Ok(n) => Ok(n),
+        Err(e) => Err(eyre!("{}", e)),
+    }
+}
+
+#[derive(Debug, StructOpt)]
+pub enum Command {
+    /// Follows the chain in a window.
+    Gui(SyncOptions),
+    /// Syncs without the GUI until interrupted, writing final blocks to the
+    /// data directory. Exits with 0 once stopped by SIGINT or SIGTERM, 1 when
+    /// the sync halts and 2 when the blocks cannot be written.
+    Sync(SyncOptions),
+    /// Looks up stored blocks, transactions and addresses.
+    Query {
+        #[structopt(flatten)]
+        storage: StorageOptions,
+        #[structopt(subcommand)]
+        query: Query,
+    },
+    /// Writes stored blocks as JSON lines, in chain order, or their
+    /// flattened tables with --format.
+    Export(ExportOptions),
+    /// Checks the stored chunks for broken links and missing blocks.
+    Verify(StorageOptions),
+    /// Rewrites chunks of an older format in the current one.
+    Migrate(StorageOptions),
+    /// Checks the configuration file.
+    Config(ConfigCommand),
+    /// Syncs and serves the chain over HTTP.
+    Serve {
+        #[structopt(flatten)]
+        sync: SyncOptions,
+        /// Address the API listens on.
+        #[structopt(long, default_value = "127.0.0.1:8080")]
+        listen: SocketAddr,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+pub enum Query {
+    /// A block by hash, height or slot.
+    Block {
+        #[structopt(long, required_unless_one = &["height", "slot"])]
+        hash: Option<String>,
+        #[structopt(long, conflicts_with = "hash")]
+        height: Option<u64>,
+        #[structopt(long, conflicts_with_all = &["hash", "height"])]
+        slot: Option<u64>,
+    },
+    /// A transaction by id.
+    Tx { id: String },
+    /// The outputs paid to an address.
+    Address { address: String },
+}
+
+#[derive(Debug, StructOpt)]
+pub enum ConfigCommand {
+    /// Prints the selected profile, merged with the built-in one, and the
+    /// settings which are invalid.
+    Check,
+}
+
+/// Where the chain is followed from.
+#[derive(Debug, StructOpt)]
+pub struct SourceOptions {
+    #[structopt(
+        short,
+        long,
+        env = "MINI_EXPLORER_WS",
+        required_unless_one = &["node-socket", "replay", "replay-recording"]
+    )]
+    pub ws: Option<Uri>,
+    /// Path to a cardano-node socket, used instead of Ogmios.
+    #[structopt(long, env = "MINI_EXPLORER_NODE_SOCKET", conflicts_with = "ws")]
+    pub node_socket: Option<PathBuf>,
+    /// File of chain-sync results, one JSON object per line, replayed instead
+    /// of following a live chain.
+    #[structopt(long, conflicts_with_all = &["ws", "node-socket"])]
+    pub replay: Option<PathBuf>,
+    /// Records the Ogmios session to a gzipped JSON-lines file.
+    #[structopt(long, requires = "ws")]
+    pub record: Option<PathBuf>,
+    /// Session recorded with --record, replayed instead of following a live
+    /// chain.
+    #[structopt(long, conflicts_with_all = &["ws", "node-socket", "replay"])]
+    pub replay_recording: Option<PathBuf>,
+    /// Speed of --replay-recording relative to the original session, 0 for
+    /// as fast as possible.
+    #[structopt(long, default_value = "1")]
+    pub replay_speed: f64,
+    /// Network magic sent in the node-to-client handshake.
+    #[structopt(long, env = "MINI_EXPLORER_NETWORK_MAGIC", default_value = "764824073")]
+    pub network_magic: u64,
+    /// Ogmios protocol version: auto, v5 (jsonwsp) or v6 (JSON-RPC 2.0).
+    #[structopt(long, env = "MINI_EXPLORER_OGMIOS_VERSION", default_value = "auto")]
+    pub ogmios_version: OgmiosVersion,
+    /// Reaction to Ogmios faults and undecodable messages: skip or stop.
+    #[structopt(long, env = "MINI_EXPLORER_ON_ERROR", default_value = "skip")]
+    pub on_error: ErrorPolicy,
+    /// Minimum number of chain-sync requests kept in flight.
+    #[structopt(long, default_value = "10", parse(try_from_str = positive))]
+    pub pipeline_min: usize,
+    /// Maximum number of chain-sync requests kept in flight, at least
+    /// --pipeline-min.
+    #[structopt(long, default_value = "1000", parse(try_from_str = positive))]
+    pub pipeline_max: usize,
+    /// Number of chain-sync results buffered before requests are held back.
+    #[structopt(long, default_value = "2000", parse(try_from_str = positive))]
+    pub buffer: usize,
+}
+
+/// Where the chain is stored.
+#[derive(Debug, StructOpt)]
+pub struct StorageOptions {
+    /// Directory the chunks are written to, created if missing.
+    #[structopt(long, env = "MINI_EXPLORER_DATA_DIR", default_value = "data")]
+    pub data_dir: PathBuf,
+    #[structopt(short, long, env = "MINI_EXPLORER_MONGODB")]
+    pub mongodb: Option<String>,
+    #[structopt(short, long, env = "MINI_EXPLORER_DB_NAME")]
+    pub db_name: Option<String>,
+    /// Number of slots of a Byron epoch.
+    #[structopt(
+        long,
+        env = "MINI_EXPLORER_BYRON_EPOCH_LENGTH",
+        default_value = "21600",
+        parse(try_from_str = positive)
+    )]
+    pub byron_epoch_length: u64,
+    /// First epoch after Byron: 208 on mainnet, 4 on preprod.
+    #[structopt(long, env = "MINI_EXPLORER_SHELLEY_EPOCH", default_value = "208")]
+    pub shelley_epoch: u64,
+    /// Number of slots of the epochs after Byron.
+    #[structopt(
+        long,
+        env = "MINI_EXPLORER_EPOCH_LENGTH",
+        default_value = "432000",
+        parse(try_from_str = positive)
+    )]
+    pub epoch_length: u64,
+}
+
+impl StorageOptions {
+    pub fn layout(&self) -> EpochLayout {
+        EpochLayout {
+            byron_length: self.byron_epoch_length,
+            shelley_epoch: self.shelley_epoch,
+            length: self.epoch_length,
+        }
+    }
+
+    /// The stored blocks, to look them up without syncing.
+    pub fn open(&self) -> Result<Chain> {
+        Ok(Chain::open(&self.data_dir)?.epoch_layout(self.layout()))
+    }
+}
+
+/// Options of the export command. Blocks are selected by height, slot or
+/// epoch.
+#[derive(Debug, StructOpt)]
+pub struct ExportOptions {
+    #[structopt(flatten)]
+    pub storage: StorageOptions,
+    /// Height of the first block.
+    #[structopt(long, conflicts_with_all = &["from-slot", "from-epoch", "to-slot", "to-epoch"])]
+    pub from: Option<u64>,
+    /// Height of the last block.
+    #[structopt(long, conflicts_with_all = &["from-slot", "from-epoch", "to-slot", "to-epoch"])]
+    pub to: Option<u64>,
+    /// Slot of the first block.
+    #[structopt(long, conflicts_with_all = &["from-epoch", "to-epoch"])]
+    pub from_slot: Option<u64>,
+    /// Slot of the last block.
+    #[structopt(long, conflicts_with_all = &["from-epoch", "to-epoch"])]
+    pub to_slot: Option<u64>,
+    /// Epoch of the first block.
+    #[structopt(long)]
+    pub from_epoch: Option<u64>,
+    /// Epoch of the last block.
+    #[structopt(long)]
+    pub to_epoch: Option<u64>,
+    /// Writes the blocks, transactions, outputs, certificates and mints
+    /// tables as jsonl, csv or parquet, see `export`.
+    #[structopt(long, requires = "output")]
+    pub format: Option<Format>,
+    /// File written instead of the standard output, or directory of the
+    /// tables.
     #[structopt(short, long)]
-    pub ws: Uri,
-    #[structopt(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+impl ExportOptions {
+    /// The stored blocks selected, in chain order.
+    pub fn blocks<'a>(&self, chain: &'a Chain) -> Blocks<'a> {
+        let bounds = |from: Option<u64>, to: Option<u64>| {
+            (
+                from.map_or(Bound::Unbounded, Bound::Included),
+                to.map_or(Bound::Unbounded, Bound::Included),
+            )
+        };
+        if self.from_epoch.is_some() || self.to_epoch.is_some() {
+            chain.blocks_by_epoch(bounds(self.from_epoch, self.to_epoch))
+        } else if self.from_slot.is_some() || self.to_slot.is_some() {
+            chain.blocks_by_slot(bounds(self.from_slot, self.to_slot))
+        } else {
+            chain.blocks_by_height(bounds(self.from, self.to))
+        }
+    }
+}
+
+/// Options of the commands following the chain.
+#[derive(Debug, StructOpt)]
+pub struct SyncOptions {
+    #[structopt(flatten)]
+    pub source: SourceOptions,
+    #[structopt(flatten)]
+    pub storage: StorageOptions,
+    /// Reaction to a block which does not follow the previous one: halt,
+    /// reintersect or log.
+    #[structopt(long, env = "MINI_EXPLORER_ON_BREAK", default_value = "halt")]
+    pub on_break: LinkPolicy,
+    /// Number of blocks after which a block is final and written to disk.
+    #[structopt(long, env = "MINI_EXPLORER_SECURITY_PARAM", default_value = "2160")]
+    pub security_param: usize,
+    /// Address whose payments are logged once final.
+    #[structopt(
+        long,
+        env = "MINI_EXPLORER_WATCH",
+        number_of_values = 1,
+        use_delimiter = true
+    )]
+    pub watch: Vec<String>,
+    /// Serves Prometheus metrics on this address.
+    #[structopt(long, env = "MINI_EXPLORER_METRICS")]
+    pub metrics: Option<SocketAddr>,
+    /// File of the webhooks to notify, see `notify`.
+    #[structopt(long, env = "MINI_EXPLORER_WEBHOOKS")]
+    pub webhooks: Option<PathBuf>,
+    /// Hash of the block to start from, at --slot.
+    #[structopt(short, long, requires = "slot")]
     pub block: Option<String>,
-    #[structopt(short, long)]
+    /// Slot of --block.
+    #[structopt(short, long, requires = "block")]
     pub slot: Option<u64>,
-    #[structopt(short, long)]
-    pub mongodb: Option<String>,
-    #[structopt(short, long)]
-    pub db_name: Option<String>,
+    /// Point to fall back on, as <slot>.<hash> or origin. Points are tried in
+    /// order, after --block.
+    #[structopt(long = "point", number_of_values = 1)]
+    pub points: Vec<PointOrOrigin>,
+    /// Starts from the tip of the chain, when no block is stored yet.
+    #[structopt(long, conflicts_with_all = &["block", "points", "from-epoch"])]
+    pub from_tip: bool,
+    /// Starts from the first block of an epoch: the blocks before its first
+    /// slot are followed from the start point, without being stored.
+    #[structopt(long)]
+    pub from_epoch: Option<u64>,
+}
+
+impl SourceOptions {
+    pub fn source(&self) -> Arc<dyn ChainSource> {
+        if let Some(path) = &self.replay_recording {
+            return Arc::new(Recording::new(path.clone(), self.replay_speed));
+        }
+        match (&self.replay, &self.node_socket, &self.ws) {
+            (Some(path), _, _) => Arc::new(Replay::new(path.clone())),
+            (None, Some(socket), _) => Arc::new(NodeClient::new(
+                socket.clone(),
+                self.network_magic,
+                self.on_error,
+            )),
+            (None, None, Some(uri)) => Arc::new(
+                Ogmios::new(
+                    uri.clone(),
+                    self.ogmios_version,
+                    self.on_error,
+                    self.pipelining(),
+                )
+                .record(self.record.clone()),
+            ),
+            (None, None, None) => unreachable!("structopt requires a source"),
+        }
+    }
+
+    /// Checks the options which depend on each other.
+    pub fn validate(&self) -> Result<()> {
+        if self.pipeline_max < self.pipeline_min {
+            return Err(eyre!(
+                "--pipeline-max ({}) is lower than --pipeline-min ({})",
+                self.pipeline_max,
+                self.pipeline_min
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn pipelining(&self) -> Pipelining {
+        Pipelining {
+            min_depth: self.pipeline_min,
+            max_depth: self.pipeline_max,
+            buffer: self.buffer,
+        }
+    }
+}
+
+impl SyncOptions {
+    /// The chain followed, going on from the blocks of the data directory.
+    pub fn chain(&self) -> Result<Chain> {
+        let dir = &self.storage.data_dir;
+        let layout = self.storage.layout();
+        let start = self.from_epoch.map(|e| layout.first_slot(e)).unwrap_or(0);
+        Chain::new(self.security_param, self.on_break)
+            .data_dir(dir.clone())
+            .epoch_layout(layout)
+            .start_at(start)
+            .resume()
+            .map_err(|e| eyre!("Cannot read the blocks of {}: {}", dir.display(), e))
+    }
+
+    /// Points to start `chain` from, in the order they are tried: its last
+    /// stored block, or origin, when none is given. Points before the last
+    /// stored epoch are refused, as its chunk would be written again.
+    pub fn points(&self, chain: &Chain) -> Result<Vec<PointOrOrigin>> {
+        if self.from_tip && chain.height().is_some() {
+            return Err(eyre!(
+                "--from-tip could write a stored epoch again, start from the last stored block instead."
+            ));
+        }
+        let mut points = Vec::new();
+        if let (Some(hash), Some(slot)) = (&self.block, self.slot) {
+            points.push(PointOrOrigin::point(slot, hash.clone()));
+        }
+        points.extend(self.points.iter().cloned());
+        if points.is_empty() {
+            points.push(chain.resume_point()?);
+        }
+        for point in &points {
+            chain.check_start(point)?;
+        }
+        Ok(points)
+    }
 }
@@ -0,0 +1,328 @@
+//! Configuration file with named profiles, e.g.
+//!
+//! ```toml
+//! profile = "preprod"
+//!
+//! [profiles.preprod]
+//! ws = "ws://localhost:1337"
+//! log = "debug"
+//!
+//! [profiles.preprod.storage]
+//! data_dir = "data/preprod"
+//!
+//! [profiles.preprod.filters]
+//! watch = ["addr_test1..."]
+//!
+//! [profiles.devnet]
+//! network_magic = 42
+//!
+//! [profiles.devnet.epochs]
+//! shelley_epoch = 0
+//! length = 500
+//! ```
+//!
+//! The settings of the selected profile become the environment variables
+//! read by the CLI, unless already set, so that flags take precedence over
+//! the environment, which takes precedence over the file.
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::{eyre, Result};
+use serde::{Deserialize, Serialize};
+use tokio_tungstenite::tungstenite::http::Uri;
+
+use crate::chain::LinkPolicy;
+use crate::data::protocol::OgmiosVersion;
+use crate::data::EpochLayout;
+use crate::ws::ErrorPolicy;
+
+/// Read when `--config` is not given, if present.
+pub const DEFAULT_PATH: &str = "mini-explorer.toml";
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Profile used when `--profile` is not given.
+    pub profile: Option<String>,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    pub ws: Option<String>,
+    pub node_socket: Option<PathBuf>,
+    pub network_magic: Option<u64>,
+    pub ogmios_version: Option<String>,
+    pub on_error: Option<String>,
+    pub on_break: Option<String>,
+    pub security_param: Option<usize>,
+    pub log: Option<String>,
+    /// File of the webhooks to notify.
+    pub webhooks: Option<PathBuf>,
+    #[serde(default)]
+    pub storage: Storage,
+    #[serde(default)]
+    pub filters: Filters,
+    /// How the network splits its slots into epochs, set by the built-in
+    /// profiles.
+    #[serde(default)]
+    pub epochs: Epochs,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Storage {
+    pub data_dir: Option<PathBuf>,
+    pub mongodb: Option<String>,
+    pub db_name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Filters {
+    /// Addresses whose payments are reported while syncing.
+    #[serde(default)]
+    pub watch: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Epochs {
+    /// Number of slots of a Byron epoch.
+    pub byron_length: Option<u64>,
+    /// First epoch after Byron.
+    pub shelley_epoch: Option<u64>,
+    /// Number of slots of the epochs after Byron.
+    pub length: Option<u64>,
+}
+
+impl From<EpochLayout> for Epochs {
+    fn from(layout: EpochLayout) -> Self {
+        Self {
+            byron_length: Some(layout.byron_length),
+            shelley_epoch: Some(layout.shelley_epoch),
+            length: Some(layout.length),
+        }
+    }
+}
+
+impl Profile {
+    /// Profiles known without a file, which a file can extend.
+    pub fn builtin(name: &str) -> Option<Self> {
+        let (magic, layout) = match name {
+            "mainnet" => (764824073, EpochLayout::MAINNET),
+            "preprod" => (1, EpochLayout::PREPROD),
+            _ => return None,
+        };
+        Some(Self {
+            network_magic: Some(magic),
+            epochs: layout.into(),
+            ..Self::default()
+        })
+    }
+
+    /// Settings of `self`, completed with those of `other`.
+    pub fn or(self, other: Self) -> Self {
+        Self {
+            ws: self.ws.or(other.ws),
+            node_socket: self.node_socket.or(other.node_socket),
+            network_magic: self.network_magic.or(other.network_magic),
+            ogmios_version: self.ogmios_version.or(other.ogmios_version),
+            on_error: self.on_error.or(other.on_error),
+            on_break: self.on_break.or(other.on_break),
+            security_param: self.security_param.or(other.security_param),
+            log: self.log.or(other.log),
+            webhooks: self.webhooks.or(other.webhooks),
+            storage: Storage {
+                data_dir: self.storage.data_dir.or(other.storage.data_dir),
+                mongodb: self.storage.mongodb.or(other.storage.mongodb),
+                db_name: self.storage.db_name.or(other.storage.db_name),
+            },
+            filters: Filters {
+                watch: if self.filters.watch.is_empty() {
+                    other.filters.watch
+                } else {
+                    self.filters.watch
+                },
+            },
+            epochs: Epochs {
+                byron_length: self.epochs.byron_length.or(other.epochs.byron_length),
+                shelley_epoch: self.epochs.shelley_epoch.or(other.epochs.shelley_epoch),
+                length: self.epochs.length.or(other.epochs.length),
+            },
+        }
+    }
+
+    /// The environment variables of the CLI options set by the profile.
+    pub fn vars(&self) -> Vec<(&'static str, String)> {
+        let mut vars = Vec::new();
+        let mut set = |name, value: Option<String>| {
+            if let Some(value) = value {
+                vars.push((name, value));
+            }
+        };
+        set("MINI_EXPLORER_WS", self.ws.clone());
+        set(
+            "MINI_EXPLORER_NODE_SOCKET",
+            self.node_socket.as_ref().map(|p| p.display().to_string()),
+        );
+        set(
+            "MINI_EXPLORER_NETWORK_MAGIC",
+            self.network_magic.map(|m| m.to_string()),
+        );
+        set("MINI_EXPLORER_OGMIOS_VERSION", self.ogmios_version.clone());
+        set("MINI_EXPLORER_ON_ERROR", self.on_error.clone());
+        set("MINI_EXPLORER_ON_BREAK", self.on_break.clone());
+        set(
+            "MINI_EXPLORER_SECURITY_PARAM",
+            self.security_param.map(|k| k.to_string()),
+        );
+        set("MINI_EXPLORER_LOG", self.log.clone());
+        set(
+            "MINI_EXPLORER_WEBHOOKS",
+            self.webhooks.as_ref().map(|p| p.display().to_string()),
+        );
+        set(
+            "MINI_EXPLORER_DATA_DIR",
+            self.storage
+                .data_dir
+                .as_ref()
+                .map(|p| p.display().to_string()),
+        );
+        set("MINI_EXPLORER_MONGODB", self.storage.mongodb.clone());
+        set("MINI_EXPLORER_DB_NAME", self.storage.db_name.clone());
+        set(
+            "MINI_EXPLORER_BYRON_EPOCH_LENGTH",
+            self.epochs.byron_length.map(|n| n.to_string()),
+        );
+        set(
+            "MINI_EXPLORER_SHELLEY_EPOCH",
+            self.epochs.shelley_epoch.map(|n| n.to_string()),
+        );
+        set(
+            "MINI_EXPLORER_EPOCH_LENGTH",
+            self.epochs.length.map(|n| n.to_string()),
+        );
+        if !self.filters.watch.is_empty() {
+            set("MINI_EXPLORER_WATCH", Some(self.filters.watch.join(",")));
+        }
+        vars
+    }
+
+    /// Settings which the CLI would reject.
+    pub fn issues(&self) -> Vec<String> {
+        let mut issues = Vec::new();
+        let mut check = |name, result: Result<()>| {
+            if let Err(e) = result {
+                issues.push(format!("{}: {}", name, e));
+            }
+        };
+        if let Some(ws) = &self.ws {
+            check("ws", ws.parse::<Uri>().map(|_| ()).map_err(|e| eyre!(e)));
+        }
+        if let Some(version) = &self.ogmios_version {
+            check(
+                "ogmios_version",
+                version.parse::<OgmiosVersion>().map(|_| ()),
+            );
+        }
+        if let Some(policy) = &self.on_error {
+            check("on_error", policy.parse::<ErrorPolicy>().map(|_| ()));
+        }
+        if let Some(policy) = &self.on_break {
+            check("on_break", policy.parse::<LinkPolicy>().map(|_| ()));
+        }
+        for (name, length) in [
+            ("epochs.byron_length", self.epochs.byron_length),
+            ("epochs.length", self.epochs.length),
+        ] {
+            if length == Some(0) {
+                check(name, Err(eyre!("must be at least 1")));
+            }
+        }
+        if let Some(log) = &self.log {
+            check(
+                "log",
+                log.parse::<tracing::Level>()
+                    .map(|_| ())
+                    .map_err(|e| eyre!(e)),
+            );
+        }
+        issues
+    }
+}
+
+impl Config {
+    pub fn read(path: &Path) -> Result<Self> {
+        let text =
+            fs::read_to_string(path).map_err(|e| eyre!("Cannot read {}: {}", path.display(), e))?;
+        toml::from_str(&text).map_err(|e| eyre!("Invalid {}: {}", path.display(), e))
+    }
+
+    /// The profile `name` of the file, over the built-in one of that name.
+    pub fn profile(&self, name: &str) -> Result<Profile> {
+        match (self.profiles.get(name), Profile::builtin(name)) {
+            (Some(profile), Some(builtin)) => Ok(profile.clone().or(builtin)),
+            (Some(profile), None) => Ok(profile.clone()),
+            (None, Some(builtin)) => Ok(builtin),
+            (None, None) => Err(eyre!("Unknown profile '{}'.", name)),
+        }
+    }
+}
+
+/// Value of `--{name}` in `args`. The file and profile are needed before
+/// the CLI is parsed, as they provide the defaults of the other options.
+fn arg(args: &[String], name: &str) -> Option<String> {
+    let flag = format!("--{}", name);
+    let prefix = format!("--{}=", name);
+    args.iter().enumerate().find_map(|(i, arg)| {
+        if *arg == flag {
+            args.get(i + 1).cloned()
+        } else {
+            arg.strip_prefix(&prefix).map(str::to_string)
+        }
+    })
+}
+
+/// The file and profile selected by `args` and the environment. The
+/// default file is only read when present.
+pub fn locate(args: &[String]) -> Result<(PathBuf, Config, Option<String>)> {
+    let path = arg(args, "config")
+        .or_else(|| env::var("MINI_EXPLORER_CONFIG").ok())
+        .map(PathBuf::from);
+    let (path, config) = match path {
+        Some(path) => {
+            let config = Config::read(&path)?;
+            (path, config)
+        }
+        None if Path::new(DEFAULT_PATH).exists() => (
+            PathBuf::from(DEFAULT_PATH),
+            Config::read(Path::new(DEFAULT_PATH))?,
+        ),
+        None => (PathBuf::from(DEFAULT_PATH), Config::default()),
+    };
+    let name = arg(args, "profile")
+        .or_else(|| env::var("MINI_EXPLORER_PROFILE").ok())
+        .or_else(|| config.profile.clone());
+    Ok((path, config, name))
+}
+
+/// Sets the environment variables of the selected profile which are not
+/// set yet, returning the profile.
+pub fn load(args: &[String]) -> Result<Option<Profile>> {
+    let (config, name) = match locate(args)? {
+        (_, config, Some(name)) => (config, name),
+        (_, _, None) => return Ok(None),
+    };
+    let profile = config.profile(&name)?;
+    for (var, value) in profile.vars() {
+        if env::var_os(var).is_none() {
+            env::set_var(var, value);
+        }
+    }
+    Ok(Some(profile))
+}
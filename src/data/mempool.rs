@@ -0,0 +1,23 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum AcquireResult {
+    AwaitAcquired { slot: u64 },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct MempoolSize {
+    pub capacity: u64,
+    #[serde(rename = "currentSize")]
+    pub current_size: u64,
+    #[serde(rename = "numberOfTxs")]
+    pub number_of_txs: u64,
+}
+
+/// Content of one acquired mempool snapshot, as reported by the tx monitor.
+#[derive(Debug, Clone)]
+pub struct MempoolSnapshot {
+    pub slot: u64,
+    pub txs: Vec<String>,
+    pub size: MempoolSize,
+}
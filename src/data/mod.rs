@@ -1,16 +1,28 @@
+use bech32::{ToBase32, Variant};
+use blake2::digest::consts::U28;
+use blake2::{Blake2b, Digest};
 use chrono::prelude::*;
+use color_eyre::eyre::{eyre, Report, Result};
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
 
 pub mod alonzo;
 pub mod byron;
+pub mod mempool;
+pub mod protocol;
 pub mod shelley;
+pub mod v6;
 
 use alonzo::TxBodyAlonzo;
 use byron::{ByronBlockEra, ByronHeader, TxBodyByron};
 use shelley::{
-    ShelleyBlockEra, ShelleyHeader, TxBodyAllegra, TxBodyMary, TxBodyShelley, TxMetadata,
+    Certificate, Mint, ShelleyBlockEra, ShelleyHeader, TxBodyAllegra, TxBodyMary, TxBodyShelley,
+    TxMetadata,
 };
 
+type Blake2b224 = Blake2b<U28>;
+
 #[derive(Debug)]
 pub enum Era {
     Byron,
@@ -18,6 +30,8 @@ pub enum Era {
     Allegra,
     Mary,
     Alonzo,
+    Babbage,
+    Conway,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -32,9 +46,15 @@ pub enum Block {
     Mary(ShelleyBlockEra<ShelleyHeader, TxBodyMary>),
     #[serde(rename = "alonzo")]
     Alonzo(ShelleyBlockEra<ShelleyHeader, TxBodyAlonzo>),
+    #[serde(rename = "babbage")]
+    Babbage(ShelleyBlockEra<ShelleyHeader, TxBodyAlonzo>),
+    #[serde(rename = "conway")]
+    Conway(ShelleyBlockEra<ShelleyHeader, TxBodyAlonzo>),
 }
 
 impl Block {
+    /// Slot of the block, or its height for Byron blocks as their slot is
+    /// not known.
     pub fn slot(&self) -> u64 {
         match self {
             Self::Byron(block) => block.header.block_height,
@@ -42,14 +62,19 @@ impl Block {
             Self::Allegra(block) => block.header.slot,
             Self::Mary(block) => block.header.slot,
             Self::Alonzo(block) => block.header.slot,
-            _ => 0,
+            Self::Babbage(block) => block.header.slot,
+            Self::Conway(block) => block.header.slot,
         }
     }
 
-    pub fn epoch(&self) -> u64 {
+    /// Epoch of the block, as its header gives it for Byron blocks.
+    pub fn epoch(&self, layout: &EpochLayout) -> u64 {
         match self {
-            Self::Byron(_) => self.slot() / 21600,
-            _ => (self.slot() - 4492799) / 432000 + 4492799 / 21600 + 1,
+            Self::Byron(block) => block
+                .header
+                .epoch
+                .unwrap_or(block.header.block_height / layout.byron_length),
+            _ => layout.epoch(self.slot()),
         }
     }
 
@@ -60,7 +85,32 @@ impl Block {
             Self::Allegra(block) => block.header_hash.clone(),
             Self::Mary(block) => block.header_hash.clone(),
             Self::Alonzo(block) => block.header_hash.clone(),
-            _ => "".to_string(),
+            Self::Babbage(block) => block.header_hash.clone(),
+            Self::Conway(block) => block.header_hash.clone(),
+        }
+    }
+
+    pub fn prev_hash(&self) -> String {
+        match self {
+            Self::Byron(block) => block.header.prev_hash.clone(),
+            Self::Shelley(block) => block.header.prev_hash.clone(),
+            Self::Allegra(block) => block.header.prev_hash.clone(),
+            Self::Mary(block) => block.header.prev_hash.clone(),
+            Self::Alonzo(block) => block.header.prev_hash.clone(),
+            Self::Babbage(block) => block.header.prev_hash.clone(),
+            Self::Conway(block) => block.header.prev_hash.clone(),
+        }
+    }
+
+    pub fn height(&self) -> u64 {
+        match self {
+            Self::Byron(block) => block.header.block_height,
+            Self::Shelley(block) => block.header.block_height,
+            Self::Allegra(block) => block.header.block_height,
+            Self::Mary(block) => block.header.block_height,
+            Self::Alonzo(block) => block.header.block_height,
+            Self::Babbage(block) => block.header.block_height,
+            Self::Conway(block) => block.header.block_height,
         }
     }
 
@@ -72,10 +122,109 @@ impl Block {
                 Self::Allegra(block) => 4492799 * 20 + (block.header.slot - 4492799),
                 Self::Mary(block) => 4492799 * 20 + (block.header.slot - 4492799),
                 Self::Alonzo(block) => 4492799 * 20 + (block.header.slot - 4492799),
+                Self::Babbage(block) => 4492799 * 20 + (block.header.slot - 4492799),
+                Self::Conway(block) => 4492799 * 20 + (block.header.slot - 4492799),
             } as i64;
         Local.from_utc_datetime(&NaiveDateTime::from_timestamp(ts, 0))
     }
 
+    pub fn tx_ids(&self) -> Vec<String> {
+        match self {
+            Self::Byron(block) => block
+                .body
+                .as_ref()
+                .and_then(|b| b.tx_payload.as_ref())
+                .map(|txs| txs.iter().map(|tx| tx.id.clone()).collect())
+                .unwrap_or_default(),
+            Self::Shelley(block) => block.body.iter().map(|tx| tx.id.clone()).collect(),
+            Self::Allegra(block) => block.body.iter().map(|tx| tx.id.clone()).collect(),
+            Self::Mary(block) => block.body.iter().map(|tx| tx.id.clone()).collect(),
+            Self::Alonzo(block) => block.body.iter().map(|tx| tx.id.clone()).collect(),
+            Self::Babbage(block) => block.body.iter().map(|tx| tx.id.clone()).collect(),
+            Self::Conway(block) => block.body.iter().map(|tx| tx.id.clone()).collect(),
+        }
+    }
+
+    /// A transaction of the block as JSON.
+    pub fn tx(&self, id: &str) -> Option<serde_json::Value> {
+        match self {
+            Self::Byron(block) => find_tx(byron_txs(block), id),
+            Self::Shelley(block) => find_tx(&block.body, id),
+            Self::Allegra(block) => find_tx(&block.body, id),
+            Self::Mary(block) => find_tx(&block.body, id),
+            Self::Alonzo(block) => find_tx(&block.body, id),
+            Self::Babbage(block) => find_tx(&block.body, id),
+            Self::Conway(block) => find_tx(&block.body, id),
+        }
+    }
+
+    /// Outputs of the transactions of the block, in order.
+    pub fn outputs(&self) -> Vec<Output> {
+        match self {
+            Self::Byron(block) => outputs(byron_txs(block)),
+            Self::Shelley(block) => outputs(&block.body),
+            Self::Allegra(block) => outputs(&block.body),
+            Self::Mary(block) => outputs(&block.body),
+            Self::Alonzo(block) => outputs(&block.body),
+            Self::Babbage(block) => outputs(&block.body),
+            Self::Conway(block) => outputs(&block.body),
+        }
+    }
+
+    /// Summaries of the transactions of the block, in order.
+    pub fn transactions(&self) -> Vec<Transaction> {
+        match self {
+            Self::Byron(block) => transactions(byron_txs(block)),
+            Self::Shelley(block) => transactions(&block.body),
+            Self::Allegra(block) => transactions(&block.body),
+            Self::Mary(block) => transactions(&block.body),
+            Self::Alonzo(block) => transactions(&block.body),
+            Self::Babbage(block) => transactions(&block.body),
+            Self::Conway(block) => transactions(&block.body),
+        }
+    }
+
+    /// Certificates of the transactions of the block, with the id of their
+    /// transaction.
+    pub fn certificates(&self) -> Vec<(String, Certificate)> {
+        match self {
+            Self::Byron(_) => Vec::new(),
+            Self::Shelley(block) => certificates(&block.body),
+            Self::Allegra(block) => certificates(&block.body),
+            Self::Mary(block) => certificates(&block.body),
+            Self::Alonzo(block) => certificates(&block.body),
+            Self::Babbage(block) => certificates(&block.body),
+            Self::Conway(block) => certificates(&block.body),
+        }
+    }
+
+    /// Assets minted, or burnt when negative, by the transactions of the
+    /// block.
+    pub fn mints(&self) -> Vec<Minted> {
+        match self {
+            Self::Byron(_) | Self::Shelley(_) | Self::Allegra(_) => Vec::new(),
+            Self::Mary(block) => mints(&block.body, |body| &body.mint),
+            Self::Alonzo(block) => mints(&block.body, |body| &body.mint),
+            Self::Babbage(block) => mints(&block.body, |body| &body.mint),
+            Self::Conway(block) => mints(&block.body, |body| &body.mint),
+        }
+    }
+
+    /// Id of the pool which minted the block, none before Shelley.
+    pub fn issuer(&self) -> Option<String> {
+        let vk = match self {
+            Self::Byron(_) => return None,
+            Self::Shelley(block) => &block.header.issuer_vk,
+            Self::Allegra(block) => &block.header.issuer_vk,
+            Self::Mary(block) => &block.header.issuer_vk,
+            Self::Alonzo(block) => &block.header.issuer_vk,
+            Self::Babbage(block) => &block.header.issuer_vk,
+            Self::Conway(block) => &block.header.issuer_vk,
+        };
+        let hash = Blake2b224::digest(hex::decode(vk).ok()?);
+        bech32::encode("pool", hash.to_base32(), Variant::Bech32).ok()
+    }
+
     pub fn era(&self) -> Era {
         use Era::*;
         match self {
@@ -84,10 +233,256 @@ impl Block {
             Self::Allegra(_) => Allegra,
             Self::Mary(_) => Mary,
             Self::Alonzo(_) => Alonzo,
+            Self::Babbage(_) => Babbage,
+            Self::Conway(_) => Conway,
         }
     }
 }
 
+fn byron_txs(block: &ByronBlockEra<ByronHeader, TxBodyByron>) -> &[Tx<TxBodyByron>] {
+    block
+        .body
+        .as_ref()
+        .and_then(|b| b.tx_payload.as_deref())
+        .unwrap_or_default()
+}
+
+fn find_tx<Body: Clone + Serialize>(txs: &[Tx<Body>], id: &str) -> Option<serde_json::Value> {
+    let tx = txs.iter().find(|tx| tx.id == id)?;
+    serde_json::to_value(tx).ok()
+}
+
+fn outputs<Body: Clone + Outputs>(txs: &[Tx<Body>]) -> Vec<Output> {
+    txs.iter()
+        .flat_map(|tx| {
+            tx.body
+                .outputs()
+                .into_iter()
+                .enumerate()
+                .map(move |(index, (address, coins))| Output {
+                    tx_id: tx.id.clone(),
+                    index: index as u64,
+                    address: address.to_string(),
+                    coins,
+                })
+        })
+        .collect()
+}
+
+fn transactions<Body: Clone + Inputs + Outputs>(txs: &[Tx<Body>]) -> Vec<Transaction> {
+    txs.iter()
+        .enumerate()
+        .map(|(index, tx)| {
+            let outputs = tx.body.outputs();
+            Transaction {
+                id: tx.id.clone(),
+                index: index as u64,
+                inputs: tx.body.inputs() as u64,
+                outputs: outputs.len() as u64,
+                coins: outputs.iter().map(|(_, coins)| coins).sum(),
+                fee: tx.body.fee(),
+            }
+        })
+        .collect()
+}
+
+fn certificates<Body: Clone + Certificates>(txs: &[Tx<Body>]) -> Vec<(String, Certificate)> {
+    txs.iter()
+        .flat_map(|tx| {
+            tx.body
+                .certificates()
+                .iter()
+                .map(move |certificate| (tx.id.clone(), certificate.clone()))
+        })
+        .collect()
+}
+
+fn mints<Body: Clone>(txs: &[Tx<Body>], mint: fn(&Body) -> &Mint) -> Vec<Minted> {
+    txs.iter()
+        .flat_map(|tx| {
+            mint(&tx.body)
+                .assets
+                .iter()
+                .map(move |(asset, quantity)| Minted {
+                    tx_id: tx.id.clone(),
+                    asset: asset.clone(),
+                    quantity: *quantity,
+                })
+        })
+        .collect()
+}
+
+/// A transaction, with the number of its inputs and outputs and the coins
+/// paid to the outputs.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Transaction {
+    pub id: String,
+    pub index: u64,
+    pub inputs: u64,
+    pub outputs: u64,
+    pub coins: u64,
+    pub fee: Option<u64>,
+}
+
+/// A transaction output, with the transaction it belongs to.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Output {
+    pub tx_id: String,
+    pub index: u64,
+    pub address: String,
+    pub coins: u64,
+}
+
+/// An asset minted or burnt by a transaction, as `policy.name`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Minted {
+    pub tx_id: String,
+    pub asset: String,
+    pub quantity: i64,
+}
+
+impl Minted {
+    pub fn policy(&self) -> &str {
+        self.asset.split('.').next().unwrap_or_default()
+    }
+}
+
+/// Certificates of a transaction body.
+pub trait Certificates {
+    fn certificates(&self) -> &[Certificate];
+}
+
+impl Certificates for TxBodyShelley {
+    fn certificates(&self) -> &[Certificate] {
+        &self.certificates
+    }
+}
+
+impl Certificates for TxBodyAllegra {
+    fn certificates(&self) -> &[Certificate] {
+        &self.certificates
+    }
+}
+
+impl Certificates for TxBodyMary {
+    fn certificates(&self) -> &[Certificate] {
+        &self.certificates
+    }
+}
+
+impl Certificates for TxBodyAlonzo {
+    fn certificates(&self) -> &[Certificate] {
+        &self.certificates
+    }
+}
+
+/// Inputs of a transaction body, and the fee paid by them.
+pub trait Inputs {
+    fn inputs(&self) -> usize;
+    fn fee(&self) -> Option<u64>;
+}
+
+impl Inputs for TxBodyByron {
+    fn inputs(&self) -> usize {
+        self.inputs.as_ref().map_or(0, Vec::len)
+    }
+
+    fn fee(&self) -> Option<u64> {
+        self.fee
+    }
+}
+
+impl Inputs for TxBodyShelley {
+    fn inputs(&self) -> usize {
+        self.inputs.len()
+    }
+
+    fn fee(&self) -> Option<u64> {
+        Some(self.fee)
+    }
+}
+
+impl Inputs for TxBodyAllegra {
+    fn inputs(&self) -> usize {
+        self.inputs.len()
+    }
+
+    fn fee(&self) -> Option<u64> {
+        Some(self.fee)
+    }
+}
+
+impl Inputs for TxBodyMary {
+    fn inputs(&self) -> usize {
+        self.inputs.len()
+    }
+
+    fn fee(&self) -> Option<u64> {
+        Some(self.fee)
+    }
+}
+
+impl Inputs for TxBodyAlonzo {
+    fn inputs(&self) -> usize {
+        self.inputs.len()
+    }
+
+    fn fee(&self) -> Option<u64> {
+        Some(self.fee)
+    }
+}
+
+/// Address and lovelace of the outputs of a transaction body.
+pub trait Outputs {
+    fn outputs(&self) -> Vec<(&str, u64)>;
+}
+
+impl Outputs for TxBodyByron {
+    fn outputs(&self) -> Vec<(&str, u64)> {
+        self.outputs
+            .iter()
+            .flatten()
+            .map(|o| (o.address.as_str(), o.value.coins))
+            .collect()
+    }
+}
+
+impl Outputs for TxBodyShelley {
+    fn outputs(&self) -> Vec<(&str, u64)> {
+        self.outputs
+            .iter()
+            .map(|o| (o.address.as_str(), o.value.coins))
+            .collect()
+    }
+}
+
+impl Outputs for TxBodyAllegra {
+    fn outputs(&self) -> Vec<(&str, u64)> {
+        self.outputs
+            .iter()
+            .map(|o| (o.address.as_str(), o.value.coins))
+            .collect()
+    }
+}
+
+impl Outputs for TxBodyMary {
+    fn outputs(&self) -> Vec<(&str, u64)> {
+        self.outputs
+            .iter()
+            .map(|o| (o.address.as_str(), o.value.coins))
+            .collect()
+    }
+}
+
+impl Outputs for TxBodyAlonzo {
+    fn outputs(&self) -> Vec<(&str, u64)> {
+        self.outputs
+            .iter()
+            .map(|o| (o.address.as_str(), o.value.coins))
+            .collect()
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Tx<Body>
 where
@@ -125,8 +520,8 @@ pub struct Tip {
 }
 
 impl Tip {
-    pub fn epoch(&self) -> u64 {
-        (self.slot - 4492799) / 432000 + 4492799 / 21600 + 1
+    pub fn epoch(&self, layout: &EpochLayout) -> u64 {
+        layout.epoch(self.slot)
     }
 
     pub fn timestamp(&self) -> DateTime<Local> {
@@ -135,6 +530,52 @@ impl Tip {
     }
 }
 
+/// How a network splits its slots into epochs: Byron epochs last
+/// `byron_length` slots and those from `shelley_epoch` on `length`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EpochLayout {
+    pub byron_length: u64,
+    pub shelley_epoch: u64,
+    pub length: u64,
+}
+
+impl EpochLayout {
+    pub const MAINNET: Self = Self {
+        byron_length: 21600,
+        shelley_epoch: 208,
+        length: 432000,
+    };
+
+    pub const PREPROD: Self = Self {
+        byron_length: 21600,
+        shelley_epoch: 4,
+        length: 432000,
+    };
+
+    pub fn first_slot(&self, epoch: u64) -> u64 {
+        if epoch < self.shelley_epoch {
+            epoch * self.byron_length
+        } else {
+            self.shelley_epoch * self.byron_length + (epoch - self.shelley_epoch) * self.length
+        }
+    }
+
+    pub fn epoch(&self, slot: u64) -> u64 {
+        let shelley = self.first_slot(self.shelley_epoch);
+        if slot < shelley {
+            slot / self.byron_length
+        } else {
+            self.shelley_epoch + (slot - shelley) / self.length
+        }
+    }
+}
+
+impl Default for EpochLayout {
+    fn default() -> Self {
+        Self::MAINNET
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ProtocolVersion {
     pub minor: u64,
@@ -152,31 +593,38 @@ pub struct Request {
     #[serde(rename = "methodname")]
     pub method: String,
     pub args: Option<ArgsInner>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mirror: Option<serde_json::Value>,
 }
 
 impl Request {
-    pub fn new(args: Args) -> Self {
-        match args {
-            Args::FindIntersect(v) => Request {
-                ttype: "jsonwsp/request".into(),
-                version: "1.0".into(),
-                service_name: "ogmios".into(),
-                method: "FindIntersect".into(),
-                args: Some(ArgsInner::FindIntersect(v)),
-            },
-            Args::RequestNext => Request {
-                ttype: "jsonwsp/request".into(),
-                version: "1.0".into(),
-                service_name: "ogmios".into(),
-                method: "RequestNext".into(),
-                args: None,
-            },
+    /// Builds a request whose `mirror` carries `id`, reflected back by the
+    /// server so the response can be matched with it.
+    pub fn new(args: Args, id: u64) -> Self {
+        let (method, args) = match args {
+            Args::FindIntersect(v) => ("FindIntersect", Some(ArgsInner::FindIntersect(v))),
+            Args::RequestNext => ("RequestNext", None),
+            Args::AwaitAcquire => ("AwaitAcquire", None),
+            Args::NextTx => ("NextTx", None),
+            Args::HasTx(id) => ("HasTx", Some(ArgsInner::HasTx(id))),
+            Args::SizeAndCapacity => ("SizeAndCapacity", None),
+            Args::Query(q) => ("Query", Some(ArgsInner::Query(q.name().into()))),
+            Args::SubmitTx(tx) => ("SubmitTx", Some(ArgsInner::SubmitTx(tx))),
+        };
+
+        Request {
+            ttype: "jsonwsp/request".into(),
+            version: "1.0".into(),
+            service_name: "ogmios".into(),
+            method: method.into(),
+            args,
+            mirror: Some(id.into()),
         }
     }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
-pub struct Response {
+pub struct Response<T = RResult> {
     #[serde(rename = "type")]
     pub ttype: String,
     pub version: String,
@@ -184,13 +632,14 @@ pub struct Response {
     pub service_name: String,
     #[serde(rename = "methodname")]
     pub method: String,
-    pub result: RResult,
-    pub reflection: Option<String>,
+    pub result: T,
+    pub reflection: Option<serde_json::Value>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum RResult {
     IntersectionFound { point: PointOrOrigin, tip: Tip },
+    IntersectionNotFound { tip: Option<Tip> },
     RollBackward { point: PointOrOrigin, tip: Tip },
     RollForward { block: Block, tip: Tip },
 }
@@ -208,6 +657,35 @@ pub enum PointOrOrigin {
     Origin(String),
 }
 
+impl fmt::Display for PointOrOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Point(Point { slot, hash }) => write!(f, "{} at slot {}", hash, slot),
+            Self::Origin(_) => write!(f, "origin"),
+        }
+    }
+}
+
+/// Parses `origin` or `<slot>.<hash>`.
+impl FromStr for PointOrOrigin {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s == "origin" {
+            return Ok(Self::origin());
+        }
+        match s.split_once('.') {
+            Some((slot, hash)) if !hash.is_empty() => {
+                Ok(Self::point(slot.parse()?, hash.to_string()))
+            }
+            _ => Err(eyre!(
+                "Invalid point '{}', expected origin or <slot>.<hash>.",
+                s
+            )),
+        }
+    }
+}
+
 impl PointOrOrigin {
     pub fn point(slot: u64, hash: String) -> Self {
         Self::Point(Point { slot, hash })
@@ -217,10 +695,10 @@ impl PointOrOrigin {
         Self::Origin("origin".to_string())
     }
 
-    pub fn epoch(&self) -> u64 {
+    pub fn epoch(&self, layout: &EpochLayout) -> u64 {
         match self {
-            Self::Point(Point { slot, .. }) => (slot - 4492799) / 432000 + 4492799 / 21600 + 1,
-            _ => 1,
+            Self::Point(Point { slot, .. }) => layout.epoch(*slot),
+            _ => 0,
         }
     }
 
@@ -233,14 +711,59 @@ impl PointOrOrigin {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Args {
     FindIntersect(Vec<PointOrOrigin>),
     RequestNext,
+    AwaitAcquire,
+    NextTx,
+    HasTx(String),
+    SizeAndCapacity,
+    Query(StateQuery),
+    SubmitTx(String),
+}
+
+/// Local state queries answered by the ledger at the node's tip.
+#[derive(Debug, Clone, Copy)]
+pub enum StateQuery {
+    ChainTip,
+    BlockHeight,
+    CurrentEpoch,
+    CurrentProtocolParameters,
+    EraSummaries,
+    SystemStart,
+}
+
+impl StateQuery {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::ChainTip => "chainTip",
+            Self::BlockHeight => "blockHeight",
+            Self::CurrentEpoch => "currentEpoch",
+            Self::CurrentProtocolParameters => "currentProtocolParameters",
+            Self::EraSummaries => "eraSummaries",
+            Self::SystemStart => "systemStart",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum SubmitResult {
+    SubmitSuccess {
+        #[serde(rename = "txId")]
+        tx_id: String,
+    },
+    SubmitFail(serde_json::Value),
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub enum ArgsInner {
     #[serde(rename = "points")]
     FindIntersect(Vec<PointOrOrigin>),
+    #[serde(rename = "id")]
+    HasTx(String),
+    #[serde(rename = "query")]
+    Query(String),
+    #[serde(rename = "submit")]
+    SubmitTx(String),
 }
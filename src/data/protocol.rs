@@ -0,0 +1,287 @@
+use std::convert::TryInto;
+use std::fmt;
+use std::str::FromStr;
+
+use color_eyre::eyre::{eyre, Report, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::data::mempool::{AcquireResult, MempoolSize};
+use crate::data::v6::{self, PointV6};
+use crate::data::{Args, RResult, Request, Response, SubmitResult};
+
+/// Ogmios wire protocol spoken with the server: the legacy `jsonwsp` envelope
+/// of v5, or JSON-RPC 2.0 as of v6. `Auto` settles on the first reply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OgmiosVersion {
+    Auto,
+    V5,
+    V6,
+}
+
+impl Default for OgmiosVersion {
+    fn default() -> Self {
+        Self::Auto
+    }
+}
+
+impl FromStr for OgmiosVersion {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            "v5" => Ok(Self::V5),
+            "v6" => Ok(Self::V6),
+            _ => Err(eyre!(
+                "Unknown Ogmios version '{}', expected auto, v5 or v6.",
+                s
+            )),
+        }
+    }
+}
+
+/// Results which are decoded from either protocol version, the v6 schema
+/// being converted into the v5 one.
+pub trait Versioned: DeserializeOwned {
+    type V6: DeserializeOwned;
+
+    /// Converts the v6 result, failing on what has no v5 counterpart.
+    fn from_v6(v6: Self::V6) -> Result<Self, String>;
+}
+
+impl Versioned for RResult {
+    type V6 = v6::ChainSyncResult;
+
+    fn from_v6(v6: v6::ChainSyncResult) -> Result<Self, String> {
+        v6.try_into()
+    }
+}
+
+impl Versioned for AcquireResult {
+    type V6 = v6::Acquired;
+
+    fn from_v6(v6: v6::Acquired) -> Result<Self, String> {
+        Ok(v6.into())
+    }
+}
+
+impl Versioned for Option<String> {
+    type V6 = v6::NextTransaction;
+
+    fn from_v6(v6: v6::NextTransaction) -> Result<Self, String> {
+        Ok(v6.into())
+    }
+}
+
+impl Versioned for bool {
+    type V6 = bool;
+
+    fn from_v6(v6: bool) -> Result<Self, String> {
+        Ok(v6)
+    }
+}
+
+impl Versioned for MempoolSize {
+    type V6 = v6::SizeOfMempool;
+
+    fn from_v6(v6: v6::SizeOfMempool) -> Result<Self, String> {
+        Ok(v6.into())
+    }
+}
+
+impl Versioned for u64 {
+    type V6 = u64;
+
+    fn from_v6(v6: u64) -> Result<Self, String> {
+        Ok(v6)
+    }
+}
+
+impl Versioned for serde_json::Value {
+    type V6 = serde_json::Value;
+
+    fn from_v6(v6: serde_json::Value) -> Result<Self, String> {
+        Ok(v6)
+    }
+}
+
+impl Versioned for SubmitResult {
+    type V6 = v6::Submitted;
+
+    fn from_v6(v6: v6::Submitted) -> Result<Self, String> {
+        Ok(v6.into())
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct JsonRpcRequest {
+    jsonrpc: &'static str,
+    method: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    params: Option<Params>,
+    id: u64,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(untagged)]
+enum Params {
+    Points { points: Vec<PointV6> },
+    Id { id: String },
+    Transaction { transaction: Cbor },
+}
+
+#[derive(Serialize, Debug)]
+struct Cbor {
+    cbor: String,
+}
+
+impl JsonRpcRequest {
+    fn new(args: Args, id: u64) -> Self {
+        let (method, params) = match args {
+            Args::FindIntersect(v) => (
+                "findIntersection",
+                Some(Params::Points {
+                    points: v.into_iter().map(PointV6::from).collect(),
+                }),
+            ),
+            Args::RequestNext => ("nextBlock", None),
+            Args::AwaitAcquire => ("acquireMempool", None),
+            Args::NextTx => ("nextTransaction", None),
+            Args::HasTx(id) => ("hasTransaction", Some(Params::Id { id })),
+            Args::SizeAndCapacity => ("sizeOfMempool", None),
+            Args::Query(q) => (q.method(), None),
+            Args::SubmitTx(cbor) => (
+                "submitTransaction",
+                Some(Params::Transaction {
+                    transaction: Cbor { cbor },
+                }),
+            ),
+        };
+
+        JsonRpcRequest {
+            jsonrpc: "2.0",
+            method,
+            params,
+            id,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct JsonRpcResponse<T> {
+    result: Option<T>,
+    error: Option<RpcError>,
+}
+
+#[derive(Deserialize, Debug)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct FaultResponse {
+    fault: FaultInner,
+}
+
+#[derive(Deserialize, Debug)]
+struct FaultInner {
+    code: String,
+    string: String,
+}
+
+/// Error reported by the server, either a v5 `jsonwsp/fault` or a v6
+/// JSON-RPC error object.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Fault {
+    pub code: String,
+    pub message: String,
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Ogmios fault [{}]: {}", self.code, self.message)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum DecodeError {
+    Fault(Fault),
+    Malformed(String),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fault(fault) => fault.fmt(f),
+            Self::Malformed(e) => write!(f, "Malformed Ogmios message: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl OgmiosVersion {
+    pub fn subprotocol(&self) -> Option<&'static str> {
+        match self {
+            Self::V6 => None,
+            _ => Some("ogmios.v1:compact"),
+        }
+    }
+
+    pub fn encode(&self, args: Args, id: u64) -> String {
+        match self {
+            Self::V6 => serde_json::to_string(&JsonRpcRequest::new(args, id)).unwrap(),
+            _ => serde_json::to_string(&Request::new(args, id)).unwrap(),
+        }
+    }
+
+    /// Extracts the request id echoed back in a reply: the `reflection` of
+    /// v5, the `id` of v6.
+    pub fn correlation(&self, text: &str) -> Option<u64> {
+        let value = serde_json::from_str::<serde_json::Value>(text).ok()?;
+        let id = match self {
+            Self::V6 => value.get("id"),
+            _ => value.get("reflection"),
+        };
+        id.and_then(serde_json::Value::as_u64)
+    }
+
+    pub fn decode<T: Versioned>(&self, text: &str) -> Result<T, DecodeError> {
+        match self {
+            Self::V6 => {
+                let resp: JsonRpcResponse<T::V6> = serde_json::from_str(text)
+                    .map_err(|e| DecodeError::Malformed(e.to_string()))?;
+                match (resp.result, resp.error) {
+                    (_, Some(e)) => Err(DecodeError::Fault(Fault {
+                        code: e.code.to_string(),
+                        message: e.message,
+                    })),
+                    (Some(r), None) => T::from_v6(r).map_err(DecodeError::Malformed),
+                    (None, None) => Err(DecodeError::Malformed(
+                        "response without result".to_string(),
+                    )),
+                }
+            }
+            _ => match serde_json::from_str::<Response<T>>(text) {
+                Ok(resp) => Ok(resp.result),
+                Err(e) => match serde_json::from_str::<FaultResponse>(text) {
+                    Ok(FaultResponse { fault }) => Err(DecodeError::Fault(Fault {
+                        code: fault.code,
+                        message: fault.string,
+                    })),
+                    Err(_) => Err(DecodeError::Malformed(e.to_string())),
+                },
+            },
+        }
+    }
+
+    /// Whether a reply uses the JSON-RPC 2.0 envelope, i.e. comes from a v6
+    /// server.
+    pub fn is_json_rpc(text: &str) -> bool {
+        serde_json::from_str::<serde_json::Value>(text)
+            .map(|v| v.get("jsonrpc").is_some())
+            .unwrap_or(false)
+    }
+}
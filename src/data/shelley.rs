@@ -53,6 +53,28 @@ pub enum Certificate {
     StakeKeyDeregistration(String),
 }
 
+impl Certificate {
+    /// As named by the node, e.g. `poolRegistration`.
+    pub fn kind(&self) -> String {
+        let kind = match serde_json::to_value(self) {
+            Ok(serde_json::Value::Object(tagged)) => tagged.keys().next().cloned(),
+            Ok(serde_json::Value::String(tag)) => Some(tag),
+            _ => None,
+        };
+        kind.unwrap_or_default()
+    }
+
+    /// The pool registered, retired or delegated to.
+    pub fn pool(&self) -> Option<&str> {
+        match self {
+            Self::PoolRegistration { id, .. } => Some(id),
+            Self::PoolRetirement { pool_id, .. } => Some(pool_id),
+            Self::StakeDelegation { delegatee, .. } => Some(delegatee),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TxBodyAllegra {
     pub inputs: Vec<TxIn>,
@@ -0,0 +1,581 @@
+//! Ogmios v6 (JSON-RPC 2.0) schemas, converted into the v5 compact types
+//! used everywhere else in the crate.
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::convert::{TryFrom, TryInto};
+
+use crate::data::alonzo::{TxBodyAlonzo, TxOutAlonzo};
+use crate::data::byron::{ByronBlockEra, ByronBody, ByronHeader, TxBodyByron};
+use crate::data::mempool::{AcquireResult, MempoolSize};
+use crate::data::shelley::{
+    BlobMetadata, Certificate, Mint, PoolMetaData, Relay, ShelleyBlockEra, ShelleyHeader,
+    TxBodyAllegra, TxBodyMary, TxBodyShelley, TxMetadata, ValidityInterval,
+};
+use crate::data::{
+    Block, EpochLayout, Point, PointOrOrigin, RResult, StateQuery, SubmitResult, Tip, Tx, TxIn,
+    TxOut, Value,
+};
+
+impl StateQuery {
+    pub fn method(&self) -> &'static str {
+        match self {
+            Self::ChainTip => "queryNetwork/tip",
+            Self::BlockHeight => "queryNetwork/blockHeight",
+            Self::CurrentEpoch => "queryLedgerState/epoch",
+            Self::CurrentProtocolParameters => "queryLedgerState/protocolParameters",
+            Self::EraSummaries => "queryLedgerState/eraSummaries",
+            Self::SystemStart => "queryNetwork/startTime",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum PointV6 {
+    Point { slot: u64, id: String },
+    Origin(String),
+}
+
+impl From<PointOrOrigin> for PointV6 {
+    fn from(p: PointOrOrigin) -> Self {
+        match p {
+            PointOrOrigin::Point(Point { slot, hash }) => Self::Point { slot, id: hash },
+            PointOrOrigin::Origin(o) => Self::Origin(o),
+        }
+    }
+}
+
+impl From<PointV6> for PointOrOrigin {
+    fn from(p: PointV6) -> Self {
+        match p {
+            PointV6::Point { slot, id } => PointOrOrigin::point(slot, id),
+            PointV6::Origin(_) => PointOrOrigin::origin(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum TipV6 {
+    Tip { slot: u64, id: String, height: u64 },
+    Origin(String),
+}
+
+impl From<TipV6> for Tip {
+    fn from(t: TipV6) -> Self {
+        match t {
+            TipV6::Tip { slot, id, height } => Tip {
+                slot,
+                hash: id,
+                block_no: height,
+            },
+            TipV6::Origin(_) => Tip {
+                slot: 0,
+                hash: "".to_string(),
+                block_no: 0,
+            },
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum ChainSyncResult {
+    Intersection { intersection: PointV6, tip: TipV6 },
+    Next(NextBlock),
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "direction", rename_all = "lowercase")]
+pub enum NextBlock {
+    Forward { block: BlockV6, tip: TipV6 },
+    Backward { point: PointV6, tip: TipV6 },
+}
+
+impl TryFrom<ChainSyncResult> for RResult {
+    type Error = String;
+
+    fn try_from(r: ChainSyncResult) -> Result<Self, String> {
+        Ok(match r {
+            ChainSyncResult::Intersection { intersection, tip } => RResult::IntersectionFound {
+                point: intersection.into(),
+                tip: tip.into(),
+            },
+            ChainSyncResult::Next(NextBlock::Forward { block, tip }) => RResult::RollForward {
+                block: block.try_into()?,
+                tip: tip.into(),
+            },
+            ChainSyncResult::Next(NextBlock::Backward { point, tip }) => RResult::RollBackward {
+                point: point.into(),
+                tip: tip.into(),
+            },
+        })
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Acquired {
+    pub slot: u64,
+}
+
+impl From<Acquired> for AcquireResult {
+    fn from(a: Acquired) -> Self {
+        AcquireResult::AwaitAcquired { slot: a.slot }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct NextTransaction {
+    pub transaction: Option<TxId>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct TxId {
+    pub id: String,
+}
+
+impl From<NextTransaction> for Option<String> {
+    fn from(n: NextTransaction) -> Self {
+        n.transaction.map(|t| t.id)
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Submitted {
+    pub transaction: TxId,
+}
+
+impl From<Submitted> for SubmitResult {
+    fn from(s: Submitted) -> Self {
+        SubmitResult::SubmitSuccess {
+            tx_id: s.transaction.id,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Bytes {
+    pub bytes: u64,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Count {
+    pub count: u64,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SizeOfMempool {
+    #[serde(rename = "maxCapacity")]
+    pub max_capacity: Bytes,
+    #[serde(rename = "currentSize")]
+    pub current_size: Bytes,
+    pub transactions: Count,
+}
+
+impl From<SizeOfMempool> for MempoolSize {
+    fn from(s: SizeOfMempool) -> Self {
+        MempoolSize {
+            capacity: s.max_capacity.bytes,
+            current_size: s.current_size.bytes,
+            number_of_txs: s.transactions.count,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct ValueV6 {
+    #[serde(default)]
+    pub ada: Lovelace,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct Lovelace {
+    pub lovelace: u64,
+}
+
+impl From<ValueV6> for Value {
+    fn from(v: ValueV6) -> Self {
+        Value {
+            coins: v.ada.lovelace,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct BlockV6 {
+    pub era: String,
+    pub id: String,
+    pub ancestor: String,
+    pub height: u64,
+    pub slot: u64,
+    pub size: Option<Bytes>,
+    pub issuer: Option<Issuer>,
+    #[serde(default)]
+    pub transactions: Vec<TxV6>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Issuer {
+    #[serde(rename = "verificationKey")]
+    pub verification_key: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct InputV6 {
+    pub transaction: TxId,
+    pub index: u64,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct OutputV6 {
+    pub address: String,
+    pub value: ValueV6,
+    pub datum: Option<String>,
+    #[serde(rename = "datumHash")]
+    pub datum_hash: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct ValidityIntervalV6 {
+    #[serde(rename = "invalidBefore")]
+    pub invalid_before: Option<u64>,
+    #[serde(rename = "invalidAfter")]
+    pub invalid_after: Option<u64>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct MetadataV6 {
+    pub hash: String,
+    #[serde(default)]
+    pub labels: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct StakePoolId {
+    pub id: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct StakePool {
+    pub id: String,
+    #[serde(rename = "vrfVerificationKeyHash")]
+    pub vrf: String,
+    pub pledge: ValueV6,
+    pub cost: ValueV6,
+    pub margin: String,
+    #[serde(rename = "rewardAccount")]
+    pub reward_account: String,
+    #[serde(default)]
+    pub owners: Vec<String>,
+    #[serde(default)]
+    pub relays: Vec<serde_json::Value>,
+    pub metadata: Option<PoolMetaData>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "type")]
+pub enum CertificateV6 {
+    #[serde(rename = "stakeCredentialRegistration")]
+    Registration { credential: String },
+    #[serde(rename = "stakeCredentialDeregistration")]
+    Deregistration { credential: String },
+    #[serde(rename = "stakeDelegation")]
+    Delegation {
+        credential: String,
+        #[serde(rename = "stakePool")]
+        stake_pool: Option<StakePoolId>,
+    },
+    #[serde(rename = "stakePoolRegistration")]
+    PoolRegistration {
+        #[serde(rename = "stakePool")]
+        stake_pool: StakePool,
+    },
+    #[serde(rename = "stakePoolRetirement")]
+    PoolRetirement {
+        #[serde(rename = "stakePool")]
+        stake_pool: StakePoolId,
+        epoch: u64,
+    },
+    #[serde(other)]
+    Other,
+}
+
+impl CertificateV6 {
+    fn into_certificate(self) -> Option<Certificate> {
+        match self {
+            Self::Registration { credential } => {
+                Some(Certificate::StakeKeyRegistration(credential))
+            }
+            Self::Deregistration { credential } => {
+                Some(Certificate::StakeKeyDeregistration(credential))
+            }
+            Self::Delegation {
+                credential,
+                stake_pool,
+            } => stake_pool.map(|pool| Certificate::StakeDelegation {
+                delegator: credential,
+                delegatee: pool.id,
+            }),
+            Self::PoolRegistration { stake_pool } => Some(Certificate::PoolRegistration {
+                id: stake_pool.id,
+                vrf: stake_pool.vrf,
+                pledge: stake_pool.pledge.ada.lovelace,
+                cost: stake_pool.cost.ada.lovelace,
+                margin: stake_pool.margin,
+                reward_account: stake_pool.reward_account,
+                owners: stake_pool.owners,
+                relays: stake_pool
+                    .relays
+                    .into_iter()
+                    .filter_map(|r| serde_json::from_value::<Relay>(r).ok())
+                    .collect(),
+                metadata: stake_pool.metadata,
+            }),
+            Self::PoolRetirement { stake_pool, epoch } => Some(Certificate::PoolRetirement {
+                pool_id: stake_pool.id,
+                retirement_epoch: epoch,
+            }),
+            Self::Other => None,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct TxV6 {
+    pub id: String,
+    #[serde(default)]
+    pub inputs: Vec<InputV6>,
+    #[serde(default)]
+    pub outputs: Vec<OutputV6>,
+    pub fee: Option<ValueV6>,
+    #[serde(rename = "validityInterval", default)]
+    pub validity_interval: ValidityIntervalV6,
+    #[serde(default)]
+    pub certificates: Vec<CertificateV6>,
+    #[serde(default)]
+    pub withdrawals: HashMap<String, ValueV6>,
+    #[serde(default)]
+    pub mint: HashMap<String, HashMap<String, i64>>,
+    pub network: Option<String>,
+    #[serde(rename = "scriptIntegrityHash")]
+    pub script_integrity_hash: Option<String>,
+    #[serde(rename = "requiredExtraSignatories", default)]
+    pub required_extra_signatories: Vec<String>,
+    pub metadata: Option<MetadataV6>,
+}
+
+impl TxV6 {
+    fn inputs(&self) -> Vec<TxIn> {
+        self.inputs
+            .iter()
+            .map(|i| TxIn {
+                tx_id: i.transaction.id.clone(),
+                index: i.index,
+            })
+            .collect()
+    }
+
+    fn outputs(&self) -> Vec<TxOut> {
+        self.outputs
+            .iter()
+            .map(|o| TxOut {
+                address: o.address.clone(),
+                value: o.value.clone().into(),
+            })
+            .collect()
+    }
+
+    fn fee(&self) -> u64 {
+        self.fee.as_ref().map(|f| f.ada.lovelace).unwrap_or(0)
+    }
+
+    fn certificates(&self) -> Vec<Certificate> {
+        self.certificates
+            .iter()
+            .cloned()
+            .filter_map(CertificateV6::into_certificate)
+            .collect()
+    }
+
+    fn withdrawals(&self) -> HashMap<String, u64> {
+        self.withdrawals
+            .iter()
+            .map(|(k, v)| (k.clone(), v.ada.lovelace))
+            .collect()
+    }
+
+    fn validity_interval(&self) -> ValidityInterval {
+        ValidityInterval {
+            invalid_before: self.validity_interval.invalid_before,
+            invalid_hereafter: self.validity_interval.invalid_after,
+        }
+    }
+
+    fn mint(&self) -> Mint {
+        let assets = self
+            .mint
+            .iter()
+            .flat_map(|(policy, assets)| {
+                assets.iter().map(move |(name, quantity)| {
+                    let id = if name.is_empty() {
+                        policy.clone()
+                    } else {
+                        format!("{}.{}", policy, name)
+                    };
+                    (id, *quantity)
+                })
+            })
+            .collect();
+        Mint { coins: 0, assets }
+    }
+
+    fn tx<Body: Clone>(&self, body: Body) -> Tx<Body> {
+        Tx {
+            id: self.id.clone(),
+            body,
+            metadata: self.metadata.clone().map(|m| TxMetadata {
+                hash: m.hash,
+                body: BlobMetadata { blob: m.labels },
+            }),
+        }
+    }
+
+    fn byron(&self) -> Tx<TxBodyByron> {
+        self.tx(TxBodyByron {
+            inputs: Some(self.inputs()),
+            outputs: Some(self.outputs()),
+            fee: self.fee.as_ref().map(|f| f.ada.lovelace),
+        })
+    }
+
+    fn shelley(&self) -> Tx<TxBodyShelley> {
+        self.tx(TxBodyShelley {
+            inputs: self.inputs(),
+            outputs: self.outputs(),
+            certificates: self.certificates(),
+            withdrawals: self.withdrawals(),
+            fee: self.fee(),
+            ttl: self.validity_interval.invalid_after.unwrap_or(0),
+            update: None,
+        })
+    }
+
+    fn allegra(&self) -> Tx<TxBodyAllegra> {
+        self.tx(TxBodyAllegra {
+            inputs: self.inputs(),
+            outputs: self.outputs(),
+            certificates: self.certificates(),
+            withdrawals: self.withdrawals(),
+            fee: self.fee(),
+            validity_interval: self.validity_interval(),
+            update: None,
+        })
+    }
+
+    fn mary(&self) -> Tx<TxBodyMary> {
+        self.tx(TxBodyMary {
+            inputs: self.inputs(),
+            outputs: self.outputs(),
+            certificates: self.certificates(),
+            withdrawals: self.withdrawals(),
+            fee: self.fee(),
+            validity_interval: self.validity_interval(),
+            update: None,
+            mint: self.mint(),
+        })
+    }
+
+    fn alonzo(&self) -> Tx<TxBodyAlonzo> {
+        self.tx(TxBodyAlonzo {
+            inputs: self.inputs(),
+            outputs: self
+                .outputs
+                .iter()
+                .map(|o| TxOutAlonzo {
+                    address: o.address.clone(),
+                    value: o.value.clone().into(),
+                    datum: o.datum.clone().or_else(|| o.datum_hash.clone()),
+                })
+                .collect(),
+            certificates: self.certificates(),
+            withdrawals: self.withdrawals(),
+            fee: self.fee(),
+            validity_interval: self.validity_interval(),
+            update: None,
+            mint: self.mint(),
+            network: None,
+            script_integrity_hash: self.script_integrity_hash.clone(),
+            required_extra_signatures: self.required_extra_signatories.clone(),
+        })
+    }
+}
+
+impl BlockV6 {
+    fn shelley_era<Body: Clone>(
+        &self,
+        body: Vec<Tx<Body>>,
+    ) -> ShelleyBlockEra<ShelleyHeader, Body> {
+        ShelleyBlockEra {
+            body,
+            header: ShelleyHeader {
+                block_height: self.height,
+                slot: self.slot,
+                prev_hash: self.ancestor.clone(),
+                issuer_vk: self
+                    .issuer
+                    .as_ref()
+                    .and_then(|i| i.verification_key.clone())
+                    .unwrap_or_default(),
+                block_size: self.size.as_ref().map(|s| s.bytes).unwrap_or(0),
+                block_hash: "".to_string(),
+            },
+            header_hash: self.id.clone(),
+        }
+    }
+}
+
+/// Fails on the eras this version does not know of.
+impl TryFrom<BlockV6> for Block {
+    type Error = String;
+
+    fn try_from(b: BlockV6) -> Result<Self, String> {
+        let block = match b.era.as_str() {
+            "byron" => Block::Byron(ByronBlockEra {
+                body: Some(ByronBody {
+                    tx_payload: Some(b.transactions.iter().map(TxV6::byron).collect()),
+                    update_payload: HashMap::new(),
+                }),
+                header: ByronHeader {
+                    protocol_magic_id: None,
+                    protocol_version: None,
+                    block_height: b.height,
+                    prev_hash: b.ancestor.clone(),
+                    // Byron epochs last as long on every network which had
+                    // them.
+                    epoch: Some(b.slot / EpochLayout::MAINNET.byron_length),
+                    software_version: None,
+                    genesis_key: None,
+                },
+                header_hash: b.id,
+            }),
+            "shelley" => {
+                Block::Shelley(b.shelley_era(b.transactions.iter().map(TxV6::shelley).collect()))
+            }
+            "allegra" => {
+                Block::Allegra(b.shelley_era(b.transactions.iter().map(TxV6::allegra).collect()))
+            }
+            "mary" => Block::Mary(b.shelley_era(b.transactions.iter().map(TxV6::mary).collect())),
+            "alonzo" => {
+                Block::Alonzo(b.shelley_era(b.transactions.iter().map(TxV6::alonzo).collect()))
+            }
+            "babbage" => {
+                Block::Babbage(b.shelley_era(b.transactions.iter().map(TxV6::alonzo).collect()))
+            }
+            "conway" => {
+                Block::Conway(b.shelley_era(b.transactions.iter().map(TxV6::alonzo).collect()))
+            }
+            era => return Err(format!("unknown era '{}' of block {}", era, b.id)),
+        };
+        Ok(block)
+    }
+}
@@ -0,0 +1,533 @@
+//! Stored blocks flattened into tables, each written to its own file as
+//! JSON lines, CSV or Parquet. The columns of the tables, in order:
+//!
+//! ```text
+//! blocks        hash, height, slot, epoch, era, prev_hash, issuer, time, transactions
+//! transactions  block, height, slot, id, index, inputs, outputs, coins, fee
+//! outputs       block, height, slot, tx_id, index, address, coins
+//! certificates  block, height, slot, tx_id, index, kind, pool, certificate
+//! mints         block, height, slot, tx_id, asset, policy, quantity
+//! ```
+//!
+//! `issuer`, `fee` and `pool` are optional, null or empty in CSV, and
+//! `certificate` is the whole certificate as JSON.
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use chrono::{SecondsFormat, Utc};
+use color_eyre::eyre::{eyre, Report, Result};
+use parquet::data_type::{ByteArray, ByteArrayType, Int64Type};
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::parser::parse_message_type;
+use serde_json::{Map, Value};
+
+use crate::data::{Block, EpochLayout};
+
+/// Rows buffered before being written as a Parquet row group.
+const ROW_GROUP: usize = 65536;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Jsonl,
+    Csv,
+    Parquet,
+}
+
+impl FromStr for Format {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "jsonl" => Ok(Self::Jsonl),
+            "csv" => Ok(Self::Csv),
+            "parquet" => Ok(Self::Parquet),
+            _ => Err(eyre!(
+                "Unknown format '{}', expected jsonl, csv or parquet.",
+                s
+            )),
+        }
+    }
+}
+
+impl Format {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Jsonl => "jsonl",
+            Self::Csv => "csv",
+            Self::Parquet => "parquet",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Int,
+    Text,
+}
+
+#[derive(Debug)]
+struct Column {
+    name: &'static str,
+    kind: Kind,
+    optional: bool,
+}
+
+const fn int(name: &'static str) -> Column {
+    Column {
+        name,
+        kind: Kind::Int,
+        optional: false,
+    }
+}
+
+const fn text(name: &'static str) -> Column {
+    Column {
+        name,
+        kind: Kind::Text,
+        optional: false,
+    }
+}
+
+const fn optional(column: Column) -> Column {
+    Column {
+        optional: true,
+        ..column
+    }
+}
+
+const BLOCKS: [Column; 9] = [
+    text("hash"),
+    int("height"),
+    int("slot"),
+    int("epoch"),
+    text("era"),
+    text("prev_hash"),
+    optional(text("issuer")),
+    text("time"),
+    int("transactions"),
+];
+
+const TRANSACTIONS: [Column; 9] = [
+    text("block"),
+    int("height"),
+    int("slot"),
+    text("id"),
+    int("index"),
+    int("inputs"),
+    int("outputs"),
+    int("coins"),
+    optional(int("fee")),
+];
+
+const OUTPUTS: [Column; 7] = [
+    text("block"),
+    int("height"),
+    int("slot"),
+    text("tx_id"),
+    int("index"),
+    text("address"),
+    int("coins"),
+];
+
+const CERTIFICATES: [Column; 8] = [
+    text("block"),
+    int("height"),
+    int("slot"),
+    text("tx_id"),
+    int("index"),
+    text("kind"),
+    optional(text("pool")),
+    text("certificate"),
+];
+
+const MINTS: [Column; 7] = [
+    text("block"),
+    int("height"),
+    int("slot"),
+    text("tx_id"),
+    text("asset"),
+    text("policy"),
+    int("quantity"),
+];
+
+/// A value of a row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Cell {
+    Int(i64),
+    Text(String),
+    Null,
+}
+
+impl From<u64> for Cell {
+    fn from(n: u64) -> Self {
+        Self::Int(n as i64)
+    }
+}
+
+impl From<i64> for Cell {
+    fn from(n: i64) -> Self {
+        Self::Int(n)
+    }
+}
+
+impl From<String> for Cell {
+    fn from(s: String) -> Self {
+        Self::Text(s)
+    }
+}
+
+impl<T: Into<Cell>> From<Option<T>> for Cell {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Self::Null, Into::into)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Table {
+    Blocks,
+    Transactions,
+    Outputs,
+    Certificates,
+    Mints,
+}
+
+impl Table {
+    pub const ALL: [Table; 5] = [
+        Self::Blocks,
+        Self::Transactions,
+        Self::Outputs,
+        Self::Certificates,
+        Self::Mints,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Blocks => "blocks",
+            Self::Transactions => "transactions",
+            Self::Outputs => "outputs",
+            Self::Certificates => "certificates",
+            Self::Mints => "mints",
+        }
+    }
+
+    fn columns(&self) -> &'static [Column] {
+        match self {
+            Self::Blocks => &BLOCKS,
+            Self::Transactions => &TRANSACTIONS,
+            Self::Outputs => &OUTPUTS,
+            Self::Certificates => &CERTIFICATES,
+            Self::Mints => &MINTS,
+        }
+    }
+
+    /// The rows of `block` in the table, with its columns in order.
+    pub fn rows(&self, block: &Block, layout: &EpochLayout) -> Vec<Vec<Cell>> {
+        let located = || -> Vec<Cell> {
+            vec![
+                block.hash().into(),
+                block.height().into(),
+                block.slot().into(),
+            ]
+        };
+        match self {
+            Self::Blocks => vec![vec![
+                block.hash().into(),
+                block.height().into(),
+                block.slot().into(),
+                block.epoch(layout).into(),
+                format!("{:?}", block.era()).to_lowercase().into(),
+                block.prev_hash().into(),
+                block.issuer().into(),
+                block
+                    .timestamp()
+                    .with_timezone(&Utc)
+                    .to_rfc3339_opts(SecondsFormat::Secs, true)
+                    .into(),
+                (block.tx_ids().len() as u64).into(),
+            ]],
+            Self::Transactions => block
+                .transactions()
+                .into_iter()
+                .map(|tx| {
+                    let mut row = located();
+                    row.extend(vec![
+                        tx.id.into(),
+                        tx.index.into(),
+                        tx.inputs.into(),
+                        tx.outputs.into(),
+                        tx.coins.into(),
+                        tx.fee.into(),
+                    ]);
+                    row
+                })
+                .collect(),
+            Self::Outputs => block
+                .outputs()
+                .into_iter()
+                .map(|output| {
+                    let mut row = located();
+                    row.extend(vec![
+                        output.tx_id.into(),
+                        output.index.into(),
+                        output.address.into(),
+                        output.coins.into(),
+                    ]);
+                    row
+                })
+                .collect(),
+            Self::Certificates => {
+                let mut rows: Vec<Vec<Cell>> = Vec::new();
+                let mut index = 0;
+                for (tx_id, certificate) in block.certificates() {
+                    // Numbered within their transaction.
+                    match rows.last() {
+                        Some(last) if last[3] == Cell::Text(tx_id.clone()) => index += 1,
+                        _ => index = 0,
+                    }
+                    let mut row = located();
+                    row.extend(vec![
+                        tx_id.into(),
+                        (index as u64).into(),
+                        certificate.kind().into(),
+                        certificate.pool().map(str::to_string).into(),
+                        serde_json::to_string(&certificate)
+                            .unwrap_or_default()
+                            .into(),
+                    ]);
+                    rows.push(row);
+                }
+                rows
+            }
+            Self::Mints => block
+                .mints()
+                .into_iter()
+                .map(|minted| {
+                    let mut row = located();
+                    let policy = minted.policy().to_string();
+                    row.extend(vec![
+                        minted.tx_id.into(),
+                        minted.asset.into(),
+                        policy.into(),
+                        minted.quantity.into(),
+                    ]);
+                    row
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Rows of a Parquet file, written by row groups.
+struct Parquet {
+    writer: SerializedFileWriter<File>,
+    columns: &'static [Column],
+    rows: Vec<Vec<Cell>>,
+}
+
+impl Parquet {
+    fn create(path: &Path, table: Table) -> Result<Self> {
+        let fields: Vec<String> = table
+            .columns()
+            .iter()
+            .map(|column| {
+                format!(
+                    "{} {} {};",
+                    if column.optional {
+                        "OPTIONAL"
+                    } else {
+                        "REQUIRED"
+                    },
+                    match column.kind {
+                        Kind::Int => "INT64",
+                        Kind::Text => "BYTE_ARRAY",
+                    },
+                    match column.kind {
+                        Kind::Int => column.name.to_string(),
+                        Kind::Text => format!("{} (UTF8)", column.name),
+                    }
+                )
+            })
+            .collect();
+        let message = format!("message {} {{ {} }}", table.name(), fields.join(" "));
+        let schema = Arc::new(parse_message_type(&message)?);
+        let properties = Arc::new(WriterProperties::builder().build());
+        Ok(Self {
+            writer: SerializedFileWriter::new(File::create(path)?, schema, properties)?,
+            columns: table.columns(),
+            rows: Vec::new(),
+        })
+    }
+
+    fn write(&mut self, row: Vec<Cell>) -> Result<()> {
+        self.rows.push(row);
+        if self.rows.len() >= ROW_GROUP {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if self.rows.is_empty() {
+            return Ok(());
+        }
+        let mut group = self.writer.next_row_group()?;
+        let mut i = 0;
+        while let Some(mut writer) = group.next_column()? {
+            let column = &self.columns[i];
+            let cells = self.rows.iter().map(|row| &row[i]);
+            let levels: Vec<i16> = cells
+                .clone()
+                .map(|cell| (*cell != Cell::Null) as i16)
+                .collect();
+            let levels = if column.optional {
+                Some(levels.as_slice())
+            } else {
+                None
+            };
+            match column.kind {
+                Kind::Int => {
+                    let values: Vec<i64> = cells
+                        .filter_map(|cell| match cell {
+                            Cell::Int(n) => Some(*n),
+                            _ => None,
+                        })
+                        .collect();
+                    writer
+                        .typed::<Int64Type>()
+                        .write_batch(&values, levels, None)?;
+                }
+                Kind::Text => {
+                    let values: Vec<ByteArray> = cells
+                        .filter_map(|cell| match cell {
+                            Cell::Text(s) => Some(ByteArray::from(s.as_str())),
+                            _ => None,
+                        })
+                        .collect();
+                    writer
+                        .typed::<ByteArrayType>()
+                        .write_batch(&values, levels, None)?;
+                }
+            }
+            writer.close()?;
+            i += 1;
+        }
+        group.close()?;
+        self.rows.clear();
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<()> {
+        self.flush()?;
+        self.writer.close()?;
+        Ok(())
+    }
+}
+
+/// The file of a table.
+enum Writer {
+    Jsonl(BufWriter<File>, &'static [Column]),
+    Csv(Box<csv::Writer<File>>),
+    Parquet(Box<Parquet>),
+}
+
+impl Writer {
+    fn create(dir: &Path, table: Table, format: Format) -> Result<Self> {
+        let path = dir.join(format!("{}.{}", table.name(), format.extension()));
+        match format {
+            Format::Jsonl => Ok(Self::Jsonl(
+                BufWriter::new(File::create(path)?),
+                table.columns(),
+            )),
+            Format::Csv => {
+                let mut writer = csv::Writer::from_path(path)?;
+                writer.write_record(table.columns().iter().map(|column| column.name))?;
+                Ok(Self::Csv(Box::new(writer)))
+            }
+            Format::Parquet => Ok(Self::Parquet(Box::new(Parquet::create(&path, table)?))),
+        }
+    }
+
+    fn write(&mut self, row: Vec<Cell>) -> Result<()> {
+        match self {
+            Self::Jsonl(out, columns) => {
+                let object: Map<String, Value> = columns
+                    .iter()
+                    .zip(row)
+                    .map(|(column, cell)| {
+                        let value = match cell {
+                            Cell::Int(n) => Value::from(n),
+                            Cell::Text(s) => Value::from(s),
+                            Cell::Null => Value::Null,
+                        };
+                        (column.name.to_string(), value)
+                    })
+                    .collect();
+                serde_json::to_writer(&mut *out, &object)?;
+                out.write_all(b"\n")?;
+            }
+            Self::Csv(writer) => {
+                writer.write_record(row.into_iter().map(|cell| match cell {
+                    Cell::Int(n) => n.to_string(),
+                    Cell::Text(s) => s,
+                    Cell::Null => String::new(),
+                }))?;
+            }
+            Self::Parquet(parquet) => parquet.write(row)?,
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<()> {
+        match self {
+            Self::Jsonl(mut out, _) => out.flush()?,
+            Self::Csv(mut writer) => writer.flush()?,
+            Self::Parquet(parquet) => parquet.finish()?,
+        }
+        Ok(())
+    }
+}
+
+/// Writes `blocks` whole as JSON lines, returning how many were.
+pub fn blocks<I: Iterator<Item = Result<Block>>, W: Write>(blocks: I, mut out: W) -> Result<usize> {
+    let mut exported = 0;
+    for block in blocks {
+        serde_json::to_writer(&mut out, &block?)?;
+        out.write_all(b"\n")?;
+        exported += 1;
+    }
+    out.flush()?;
+    Ok(exported)
+}
+
+/// Writes every table of `blocks`, split into epochs by `layout`, to `dir`
+/// in `format`, returning the number of blocks exported.
+pub fn tables<I: Iterator<Item = Result<Block>>>(
+    blocks: I,
+    layout: &EpochLayout,
+    format: Format,
+    dir: &Path,
+) -> Result<usize> {
+    std::fs::create_dir_all(dir)?;
+    let mut writers = Table::ALL
+        .iter()
+        .map(|table| Ok((*table, Writer::create(dir, *table, format)?)))
+        .collect::<Result<Vec<_>>>()?;
+    let mut exported = 0;
+    for block in blocks {
+        let block = block?;
+        for (table, writer) in &mut writers {
+            for row in table.rows(&block, layout) {
+                writer.write(row)?;
+            }
+        }
+        exported += 1;
+    }
+    for (_, writer) in writers {
+        writer.finish()?;
+    }
+    Ok(exported)
+}
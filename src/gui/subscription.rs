@@ -1,25 +1,24 @@
 use std::hash::{Hash, Hasher};
 
 use futures::stream::BoxStream;
-use tokio_stream::wrappers::ReceiverStream;
 use tokio_stream::StreamExt;
-use tokio_tungstenite::tungstenite::http::Uri;
 
 use crate::chain::ChainEvent;
+use crate::synchronization::Events;
 
-pub fn progress(uri: Uri, s: Option<ReceiverStream<ChainEvent>>) -> iced::Subscription<ChainEvent> {
-    iced::Subscription::from_recipe(SyncProgressEngine { uri, s })
+pub fn progress(source: String, s: Option<Events>) -> iced::Subscription<ChainEvent> {
+    iced::Subscription::from_recipe(SyncProgressEngine { source, s })
 }
 
 #[derive(Debug, Default)]
 pub struct SyncProgressEngine {
-    pub s: Option<ReceiverStream<ChainEvent>>,
-    pub uri: Uri,
+    pub s: Option<Events>,
+    pub source: String,
 }
 
 impl SyncProgressEngine {
-    pub fn new(uri: Uri, s: Option<ReceiverStream<ChainEvent>>) -> Self {
-        Self { s, uri }
+    pub fn new(source: String, s: Option<Events>) -> Self {
+        Self { s, source }
     }
 }
 
@@ -33,7 +32,7 @@ where
         struct Marker;
         std::any::TypeId::of::<Marker>().hash(state);
 
-        self.uri.hash(state);
+        self.source.hash(state);
     }
 
     fn stream(self: Box<Self>, _input: BoxStream<I>) -> BoxStream<Self::Output> {
@@ -10,9 +10,11 @@ use iced::{
 use tracing::info;
 
 use crate::chain::{ChainEvent, SyncProgress};
-use crate::cli::CLI;
-use crate::data::{Block, Tip};
+use crate::cli::SyncOptions;
+use crate::data::mempool::MempoolSize;
+use crate::data::{Block, EpochLayout, Tip};
 use crate::gui::subscription::{progress, SyncProgressEngine};
+use crate::mempool::MempoolEvent;
 use crate::synchronization::Engine;
 
 #[derive(Debug)]
@@ -36,18 +38,33 @@ pub struct Explorer {
     sync_progress: f32,
     block: Option<Block>,
     tip: Option<Tip>,
+    pending_txs: usize,
+    mempool_size: MempoolSize,
+    last_inclusion: Option<i64>,
+    connection_status: String,
+    throughput: String,
     state: State,
+    layout: EpochLayout,
 }
 
 impl Application for Explorer {
     type Executor = executor::Default;
     type Message = Message;
-    type Flags = CLI;
+    type Flags = SyncOptions;
 
     fn new(flags: Self::Flags) -> (Explorer, Command<Message>) {
-        let uri = flags.ws.clone();
-        let (engine, rx) = Engine::new(flags.ws);
-        let sync_process_engine = SyncProgressEngine::new(uri, Some(rx));
+        let source = flags.source.source();
+        let id = source.to_string();
+        // The stored blocks and the points were checked before opening the
+        // window.
+        let chain = flags.chain().expect("the stored blocks are readable");
+        let points = flags.points(&chain);
+        let (mut engine, rx) = Engine::new(source, flags.source.buffer, chain);
+        engine.from_tip = flags.from_tip;
+        if let Ok(points) = points {
+            engine.points = points;
+        }
+        let sync_process_engine = SyncProgressEngine::new(id, Some(rx));
 
         // let start_engine = engine.start();
         (
@@ -57,7 +74,13 @@ impl Application for Explorer {
                 sync_progress: 0.0,
                 block: None,
                 tip: None as Option<Tip>,
+                pending_txs: 0,
+                mempool_size: MempoolSize::default(),
+                last_inclusion: None,
+                connection_status: "".to_string(),
+                throughput: "".to_string(),
                 state: State::UiInitialized,
+                layout: flags.storage.layout(),
             },
             Command::perform(async {}, |_| Message::UiInitialized), //perform(start_engine, |_| Message::Loaded),
         )
@@ -73,15 +96,7 @@ impl Application for Explorer {
             State::UiInitialized => match message {
                 Message::UiInitialized => {
                     self.state = State::Loading;
-                    let connection = self.engine.connection.clone();
-                    return Command::perform(
-                        async {
-                            tokio::spawn(async move {
-                                let _ = connection.run().await;
-                            });
-                        },
-                        |_| Message::Loaded,
-                    );
+                    return Command::perform(self.engine.start(), |_| Message::Loaded);
                 }
                 _ => panic!("oops"),
             },
@@ -92,13 +107,53 @@ impl Application for Explorer {
                         ChainEvent::Synchronizing(s) => match s {
                             SyncProgress::Synchronizing(progress, block, tip) => {
                                 self.sync_progress = progress;
-                                self.block = Some(block);
+                                self.block = Some(*block);
                                 self.tip = Some(tip);
                             }
                             SyncProgress::Synchronized(tip) => self.sync_progress = 100.0,
                             SyncProgress::Unsynchronized => self.sync_progress = 0.0,
                         },
                         ChainEvent::RevertFork(_) => (),
+                        ChainEvent::Mempool(e) => match e {
+                            MempoolEvent::Pending { .. } => self.pending_txs += 1,
+                            MempoolEvent::Left { .. } => {
+                                self.pending_txs = self.pending_txs.saturating_sub(1)
+                            }
+                            MempoolEvent::Included { waited, .. } => {
+                                self.last_inclusion = Some(waited.num_seconds())
+                            }
+                            MempoolEvent::Size(size) => self.mempool_size = size,
+                        },
+                        ChainEvent::Throughput(t) => {
+                            self.throughput = format!(
+                                "{:.0} blocks/s (pipeline depth {}, {} buffered)",
+                                t.blocks_per_sec, t.depth, t.buffered
+                            )
+                        }
+                        ChainEvent::Connection(e) => {
+                            if e.is_error() {
+                                self.connection_status = e.to_string();
+                            }
+                        }
+                        ChainEvent::LinkBroken(broken) => {
+                            self.connection_status = broken.to_string()
+                        }
+                        ChainEvent::Finalized(_) => (),
+                        ChainEvent::IntersectionNotFound(_) => {
+                            self.connection_status = "No intersection found".to_string()
+                        }
+                        ChainEvent::RollbackTooDeep(point) => {
+                            self.connection_status = format!("Cannot roll back to {}", point)
+                        }
+                        ChainEvent::Halted(reason) => self.connection_status = reason,
+                        ChainEvent::Dumped { .. } | ChainEvent::Memory { .. } => (),
+                        ChainEvent::Reintersecting(_) => {
+                            self.connection_status = "Intersecting again".to_string()
+                        }
+                        ChainEvent::DumpFailed { epoch, error } => {
+                            self.connection_status =
+                                format!("Cannot write epoch {}: {}", epoch, error)
+                        }
                     };
                 }
                 _ => panic!("Loaded message received when already loaded state"),
@@ -127,11 +182,11 @@ impl Application for Explorer {
             let s = flags.s;
 
             self.flags
-                .replace(SyncProgressEngine::new(flags.uri.clone(), None));
-            progress(flags.uri, s)
+                .replace(SyncProgressEngine::new(flags.source.clone(), None));
+            progress(flags.source, s)
         } else {
             self.flags.replace(flags);
-            progress(self.flags.borrow().uri.clone(), None)
+            progress(self.flags.borrow().source.clone(), None)
         }
         .map(Message::WS)
     }
@@ -139,14 +194,25 @@ impl Application for Explorer {
     fn view(&mut self) -> Element<Message> {
         let progress_bar = ProgressBar::new(0.0..=100.0, self.sync_progress);
         let tip_epoch = match &self.tip {
-            Some(tip) => tip.epoch(),
+            Some(tip) => tip.epoch(&self.layout),
             None => 0,
         };
         let (block_epoch, block_era) = match &self.block {
-            Some(block) => (block.epoch(), format!("Current Era: {:?}", block.era())),
+            Some(block) => (
+                block.epoch(&self.layout),
+                format!("Current Era: {:?}", block.era()),
+            ),
             None => (0, "".to_string()),
         };
         // let Some(block_epoch) = self.block.map(|t| t.epoch());
+        let mempool = format!(
+            "Mempool: {} pending txs ({} / {} bytes)",
+            self.pending_txs, self.mempool_size.current_size, self.mempool_size.capacity
+        );
+        let inclusion = match self.last_inclusion {
+            Some(secs) => format!("Last time-to-inclusion: {}s", secs),
+            None => "".to_string(),
+        };
 
         let row: Element<_> = Row::new()
             .spacing(10)
@@ -166,6 +232,10 @@ impl Application for Explorer {
             .push(row)
             .push(Text::new(format!("Epoch Progress {} / {}", block_epoch, tip_epoch)).size(50))
             .push(Text::new(block_era).size(50))
+            .push(Text::new(mempool).size(30))
+            .push(Text::new(inclusion).size(30))
+            .push(Text::new(&self.throughput).size(20))
+            .push(Text::new(&self.connection_status).size(20))
             .into();
 
         Container::new(control)
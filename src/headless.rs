@@ -0,0 +1,224 @@
+//! Syncing without the GUI, e.g. on a server.
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use color_eyre::eyre::Result;
+use tokio_stream::StreamExt;
+use tracing::{error, info};
+
+use crate::api::live;
+use crate::api::{Api, State};
+use crate::chain::{ChainEvent, SyncProgress};
+use crate::cli::SyncOptions;
+use crate::metrics::{self, Exporter, Metrics};
+use crate::notify::{self, Rules};
+use crate::synchronization::Engine;
+use crate::ws::ConnectionEvent;
+
+/// How `sync` ended, as the exit code of the process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exit {
+    /// Stopped by a signal once the blocks were written.
+    Stopped = 0,
+    /// The source failed or the engine halted.
+    Halted = 1,
+    /// The blocks could not be written.
+    Unwritten = 2,
+}
+
+/// Resolves once SIGINT or SIGTERM is received.
+async fn shutdown() -> Result<()> {
+    #[cfg(unix)]
+    {
+        let mut terminate =
+            tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?;
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => result?,
+            _ = terminate.recv() => (),
+        }
+    }
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await?;
+    Ok(())
+}
+
+/// Whether the engine stops following the chain after `event`.
+fn halts(event: &ChainEvent) -> bool {
+    match event {
+        ChainEvent::Halted(_) => true,
+        ChainEvent::Connection(e) => matches!(
+            e,
+            ConnectionEvent::Transport(_) | ConnectionEvent::Closed(_)
+        ),
+        _ => false,
+    }
+}
+
+/// Resolves once `stop` is sent or dropped.
+async fn stopped(mut stop: tokio::sync::watch::Receiver<bool>) {
+    while !*stop.borrow() {
+        if stop.changed().await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Runs the engine until a signal is received or the sync halts, then
+/// writes the final blocks still in memory.
+pub async fn run(opt: &SyncOptions) -> Exit {
+    follow(opt, None).await
+}
+
+/// Runs the engine as `run` does, serving the chain over HTTP on `listen`
+/// meanwhile.
+pub async fn serve(opt: &SyncOptions, listen: SocketAddr) -> Exit {
+    follow(opt, Some(listen)).await
+}
+
+async fn follow(opt: &SyncOptions, listen: Option<SocketAddr>) -> Exit {
+    let started = opt
+        .chain()
+        .and_then(|chain| Ok((opt.points(&chain)?, chain)));
+    let (points, chain) = match started {
+        Ok(started) => started,
+        Err(e) => {
+            error!("{}", e);
+            return Exit::Halted;
+        }
+    };
+    let rules = match opt.webhooks.as_deref().map(Rules::read).transpose() {
+        Ok(rules) => rules,
+        Err(e) => {
+            error!("{}", e);
+            return Exit::Halted;
+        }
+    };
+    let source = opt.source.source();
+    info!("Syncing from {}", source);
+    let (mut engine, mut events) = Engine::new(source, opt.source.buffer, chain);
+    engine.points = points;
+    engine.from_tip = opt.from_tip;
+
+    let (stop, stopping) = tokio::sync::watch::channel(false);
+    if let Some(listen) = listen {
+        let state = State::new(engine.chain.clone(), opt.storage.layout());
+        tokio::spawn(live::forward(engine.watch(), state.live.clone()));
+        let api = match Api::bind(&listen, state) {
+            Ok(api) => api,
+            Err(e) => {
+                error!("Cannot listen on {}: {}", listen, e);
+                return Exit::Halted;
+            }
+        };
+        info!("Serving the API on http://{}", api.local_addr());
+        let stopping = stopping.clone();
+        tokio::spawn(async move {
+            if let Err(e) = api.run(stopped(stopping)).await {
+                error!("The API stopped: {}", e);
+            }
+        });
+    }
+    if let Some(addr) = opt.metrics {
+        let recorded = Arc::new(Mutex::new(Metrics::new()));
+        tokio::spawn(metrics::record(engine.watch(), recorded.clone()));
+        let exporter = match Exporter::bind(&addr, recorded) {
+            Ok(exporter) => exporter,
+            Err(e) => {
+                error!("Cannot listen on {}: {}", addr, e);
+                return Exit::Halted;
+            }
+        };
+        info!(
+            "Serving metrics on http://{}/metrics",
+            exporter.local_addr()
+        );
+        tokio::spawn(async move {
+            if let Err(e) = exporter.run(stopped(stopping)).await {
+                error!("The metrics stopped: {}", e);
+            }
+        });
+    }
+    if let Some(rules) = rules {
+        info!("Notifying {} webhooks", rules.webhooks.len());
+        let (events, chain) = (engine.watch(), engine.chain.clone());
+        tokio::spawn(async move {
+            if let Err(e) = notify::run(rules, events, chain).await {
+                error!("The webhooks stopped: {}", e);
+            }
+        });
+    }
+    engine.start().await;
+
+    let shutdown = shutdown();
+    tokio::pin!(shutdown);
+    let mut logged = Instant::now();
+    let mut synchronized = false;
+    let exit = loop {
+        let event = tokio::select! {
+            result = &mut shutdown => {
+                if let Err(e) = result {
+                    error!("Cannot listen to signals: {}", e);
+                }
+                info!("Stopping");
+                break Exit::Stopped;
+            }
+            event = events.next() => match event {
+                Some(event) => event,
+                None => break Exit::Halted,
+            },
+        };
+
+        match &event {
+            ChainEvent::Synchronizing(SyncProgress::Synchronizing(progress, block, _)) => {
+                synchronized = false;
+                if logged.elapsed() >= Duration::from_secs(10) {
+                    info!(
+                        "Epoch {}, slot {} ({:.2}%)",
+                        block.epoch(&opt.storage.layout()),
+                        block.slot(),
+                        progress
+                    );
+                    logged = Instant::now();
+                }
+            }
+            ChainEvent::Synchronizing(SyncProgress::Synchronized(tip)) if !synchronized => {
+                synchronized = true;
+                info!("Synchronized at slot {}", tip.slot);
+            }
+            ChainEvent::RevertFork(blocks) => info!("Rolled back {} blocks", blocks.len()),
+            ChainEvent::Dumped { epoch, blocks, .. } => {
+                info!("Wrote {} blocks of epoch {}", blocks, epoch)
+            }
+            ChainEvent::Finalized(blocks) if !opt.watch.is_empty() => {
+                for block in blocks {
+                    for output in block.outputs() {
+                        if opt.watch.contains(&output.address) {
+                            info!(
+                                "{} lovelace paid to {} by {} in block {}",
+                                output.coins,
+                                output.address,
+                                output.tx_id,
+                                block.hash()
+                            );
+                        }
+                    }
+                }
+            }
+            _ => (),
+        }
+        if halts(&event) {
+            break Exit::Halted;
+        }
+    };
+
+    let _ = stop.send(true);
+    let flushed = engine.chain.lock().await.flush();
+    match flushed {
+        Ok(()) => exit,
+        Err(e) => {
+            error!("{}", e);
+            Exit::Unwritten
+        }
+    }
+}
@@ -0,0 +1,18 @@
+pub mod api;
+pub mod chain;
+pub mod cli;
+pub mod config;
+pub mod data;
+pub mod export;
+pub mod gui;
+pub mod headless;
+pub mod mempool;
+pub mod metrics;
+pub mod n2c;
+pub mod notify;
+pub mod query;
+pub mod server;
+pub mod source;
+pub mod storage;
+pub mod synchronization;
+pub mod ws;
@@ -1,30 +1,48 @@
-use std::sync::Arc;
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::net::SocketAddr;
+use std::path::Path;
 
-use color_eyre::eyre::Result;
-use gui::subscription::SyncProgressEngine;
+use color_eyre::eyre::Report;
 use iced::{Application, Settings};
-use structopt::StructOpt;
-use synchronization::Engine;
-
-mod chain;
-mod cli;
-mod data;
-mod gui;
-mod storage;
-mod synchronization;
-mod ws;
+use mini_explorer::cli::{
+    Command, ConfigCommand, ExportOptions, Query, StorageOptions, SyncOptions,
+};
+use mini_explorer::{chain, cli, config, export, gui, headless, query};
+use structopt::{clap, StructOpt};
 
 // #[tokio::main]
 pub fn main() -> iced::Result {
-    let opt = init();
-
-    // let uri = opt.ws.clone();
-    // let (engine, rx) = Engine::new(opt.ws);
-    // let sync_process_engine = SyncProgressEngine::new(uri, Some(rx));
-    // let settings = Settings::with_flags(sync_process_engine);
-    // engine.start();
-    let settings = Settings::with_flags(opt);
-    gui::ui::Explorer::run(settings)
+    let args: Vec<String> = std::env::args().collect();
+    let _ = color_eyre::install();
+    // The .env file is optional.
+    let _ = dotenv::dotenv();
+    let loaded = config::load(&args);
+    let opt = init(&args, loaded.as_ref().err());
+
+    // The configuration is checked even when it cannot be loaded.
+    let code = match (opt.command, loaded) {
+        (Command::Config(ConfigCommand::Check), _) => check(&args),
+        (_, Err(e)) => {
+            eprintln!("{}", e);
+            2
+        }
+        (Command::Gui(sync), _) => {
+            if let Err(e) = sync.chain().and_then(|chain| sync.points(&chain)) {
+                eprintln!("{}", e);
+                std::process::exit(2);
+            }
+            let settings = Settings::with_flags(sync);
+            return gui::ui::Explorer::run(settings);
+        }
+        (Command::Sync(sync), _) => self::sync(&sync),
+        (Command::Query { storage, query }, _) => self::query(&storage, &query),
+        (Command::Export(opt), _) => export(&opt),
+        (Command::Verify(storage), _) => verify(&storage.data_dir),
+        (Command::Migrate(storage), _) => migrate(&storage.data_dir),
+        (Command::Serve { sync, listen }, _) => serve(&sync, listen),
+    };
+    std::process::exit(code)
 }
 
 // #[derive(Default)]
@@ -79,30 +97,196 @@ pub fn main() -> iced::Result {
 //     }
 // }
 
-// #[tokio::main]
-// async fn main() -> Result<()> {
-//     let opt = init();
+/// Prints the issues found in the chunks of `dir`, returning the exit code.
+fn verify(dir: &Path) -> i32 {
+    match chain::verify::verify(dir) {
+        Ok(report) => {
+            for issue in &report.issues {
+                println!("{}", issue);
+            }
+            println!(
+                "{} chunks, {} blocks, {} issues",
+                report.chunks,
+                report.blocks,
+                report.issues.len()
+            );
+            if report.issues.is_empty() {
+                0
+            } else {
+                1
+            }
+        }
+        Err(e) => {
+            eprintln!("Cannot verify {}: {}", dir.display(), e);
+            2
+        }
+    }
+}
 
-//     let (engine, mut rx) = Engine::new(opt.ws);
+/// Syncs without the GUI, returning the exit code.
+fn sync(opt: &SyncOptions) -> i32 {
+    match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime.block_on(headless::run(opt)) as i32,
+        Err(e) => {
+            eprintln!("Cannot start the runtime: {}", e);
+            headless::Exit::Halted as i32
+        }
+    }
+}
 
-//     engine.start();
+/// Syncs and serves the API, returning the exit code.
+fn serve(opt: &SyncOptions, listen: SocketAddr) -> i32 {
+    match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime.block_on(headless::serve(opt, listen)) as i32,
+        Err(e) => {
+            eprintln!("Cannot start the runtime: {}", e);
+            headless::Exit::Halted as i32
+        }
+    }
+}
 
-//     while let Some(e) = rx.next().await {
-//         info!("{:?}", e);
-//         let c = engine.chain.lock().await;
-//         info!("{:?}", c.sync());
-//     }
+/// Migrates the chunks of `dir` to the current format, returning the exit
+/// code.
+fn migrate(dir: &Path) -> i32 {
+    match chain::format::migrate_all(dir) {
+        Ok(migrated) => {
+            println!("{} chunks migrated", migrated);
+            0
+        }
+        Err(e) => {
+            eprintln!("Cannot migrate {}: {}", dir.display(), e);
+            2
+        }
+    }
+}
 
-//     Ok(())
-// }
+/// Prints the result of `query` as JSON, returning 0 when found, 1 when not
+/// and 2 on errors.
+fn query(storage: &StorageOptions, query: &Query) -> i32 {
+    let dir = &storage.data_dir;
+    let found = storage.open().and_then(|chain| {
+        match query {
+        Query::Block { hash, height, slot } => {
+            let block = match (hash, height, slot) {
+                (Some(hash), _, _) => chain.get_block_by_hash(hash)?,
+                (None, Some(height), _) => chain.get_block_by_height(*height)?,
+                (None, None, Some(slot)) => chain.get_block_by_slot(*slot)?,
+                (None, None, None) => None,
+            };
+            Ok(block.map(serde_json::to_value).transpose()?)
+        }
+        Query::Tx { id } => Ok(query::tx(&chain, id)?.map(|(block, tx)| {
+            serde_json::json!({ "block": block.hash(), "height": block.height(), "tx": tx })
+        })),
+        Query::Address { address } => {
+            let payments = query::address(&chain, address)?;
+            if payments.is_empty() {
+                Ok(None)
+            } else {
+                Ok(Some(serde_json::to_value(payments)?))
+            }
+        }
+    }
+    });
+    match found {
+        Ok(Some(value)) => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&value).unwrap_or_default()
+            );
+            0
+        }
+        Ok(None) => {
+            eprintln!("Not found");
+            1
+        }
+        Err(e) => {
+            eprintln!("Cannot query {}: {}", dir.display(), e);
+            2
+        }
+    }
+}
 
-fn init() -> cli::CLI {
-    let _ = color_eyre::install();
-    dotenv::dotenv().unwrap();
+/// Writes the blocks selected by `opt` to its output or the standard
+/// output, returning the exit code.
+fn export(opt: &ExportOptions) -> i32 {
+    let dir = &opt.storage.data_dir;
+    let exported = opt.storage.open().and_then(|chain| {
+        let blocks = opt.blocks(&chain);
+        match (opt.format, &opt.output) {
+            (Some(format), Some(output)) => export::tables(blocks, &chain.layout, format, output),
+            (Some(_), None) => unreachable!("structopt requires --output"),
+            (None, Some(path)) => export::blocks(blocks, BufWriter::new(File::create(path)?)),
+            (None, None) => export::blocks(blocks, io::stdout().lock()),
+        }
+    });
+    match exported {
+        Ok(count) => {
+            eprintln!("{} blocks exported", count);
+            0
+        }
+        Err(e) => {
+            eprintln!("Cannot export {}: {}", dir.display(), e);
+            2
+        }
+    }
+}
+
+/// Prints the selected profile and its invalid settings, returning 0 when
+/// it is valid and 1 otherwise.
+fn check(args: &[String]) -> i32 {
+    let (path, config, name) = match config::locate(args) {
+        Ok(located) => located,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        }
+    };
+    let name = match name {
+        Some(name) => name,
+        None => {
+            println!("# {}: no profile selected", path.display());
+            return 0;
+        }
+    };
+    let profile = match config.profile(&name) {
+        Ok(profile) => profile,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        }
+    };
+    println!("# {}, profile {}", path.display(), name);
+    print!("{}", toml::to_string(&profile).unwrap_or_default());
+    let issues = profile.issues();
+    for issue in &issues {
+        eprintln!("{}", issue);
+    }
+    if issues.is_empty() {
+        0
+    } else {
+        1
+    }
+}
+
+/// Parses the CLI, reporting first why the configuration could not be
+/// loaded when it is rejected.
+fn init(args: &[String], unloaded: Option<&Report>) -> cli::CLI {
+    let opt = match cli::CLI::from_iter_safe(args) {
+        Ok(opt) => opt,
+        Err(e) => {
+            if let Some(unloaded) = unloaded {
+                eprintln!("{}", unloaded);
+            }
+            e.exit()
+        }
+    };
+    if let Err(e) = opt.validate() {
+        clap::Error::with_description(&e.to_string(), clap::ErrorKind::ValueValidation).exit()
+    }
     // let file_appender = tracing_appender::rolling::hourly("./", "prefix.log");
     // let (non_blocking, guard) = tracing_appender::non_blocking(file_appender);
     // tracing_subscriber::fmt().with_writer(non_blocking).init();
-    tracing_subscriber::fmt().init();
-    let opt = cli::CLI::from_args();
+    tracing_subscriber::fmt().with_max_level(opt.log).init();
     opt
 }
@@ -0,0 +1,106 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Duration, Local};
+
+use crate::data::mempool::{MempoolSize, MempoolSnapshot};
+use crate::data::Block;
+
+/// How long a transaction that left the mempool is remembered while waiting
+/// for chain-sync to deliver the block that includes it.
+const LEFT_RETENTION_HOURS: i64 = 24;
+
+#[derive(Debug, Clone)]
+pub enum MempoolEvent {
+    Pending {
+        id: String,
+        seen: DateTime<Local>,
+    },
+    Left {
+        id: String,
+    },
+    Included {
+        id: String,
+        slot: u64,
+        waited: Duration,
+    },
+    Size(MempoolSize),
+}
+
+#[derive(Debug, Default)]
+pub struct Mempool {
+    pending: HashMap<String, DateTime<Local>>,
+    /// Transactions which left the mempool, with the times they were first
+    /// seen and left.
+    left: HashMap<String, (DateTime<Local>, DateTime<Local>)>,
+    pub size: MempoolSize,
+    pub slot: u64,
+}
+
+impl Mempool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Diffs a new snapshot against the pending set, timestamping newly seen
+    /// transactions.
+    pub fn update(&mut self, snapshot: MempoolSnapshot) -> Vec<MempoolEvent> {
+        let now = Local::now();
+        let mut events = Vec::new();
+
+        let current: HashSet<String> = snapshot.txs.into_iter().collect();
+        let gone: Vec<String> = self
+            .pending
+            .keys()
+            .filter(|id| !current.contains(*id))
+            .cloned()
+            .collect();
+        for id in gone {
+            if let Some(seen) = self.pending.remove(&id) {
+                self.left.insert(id.clone(), (seen, now));
+            }
+            events.push(MempoolEvent::Left { id });
+        }
+
+        for id in current {
+            if !self.pending.contains_key(&id) {
+                let seen = self.left.remove(&id).map_or(now, |(seen, _)| seen);
+                self.pending.insert(id.clone(), seen);
+                events.push(MempoolEvent::Pending { id, seen });
+            }
+        }
+
+        let limit = now - Duration::hours(LEFT_RETENTION_HOURS);
+        self.left.retain(|_, (_, left)| *left > limit);
+
+        self.slot = snapshot.slot;
+        self.size = snapshot.size.clone();
+        events.push(MempoolEvent::Size(snapshot.size));
+        events
+    }
+
+    /// Resolves the transactions of a new block against the ones seen pending
+    /// and reports their time-to-inclusion. Those still pending leave the
+    /// mempool first.
+    pub fn include(&mut self, block: &Block) -> Vec<MempoolEvent> {
+        let minted = block.timestamp();
+        let mut events = Vec::new();
+        for id in block.tx_ids() {
+            let seen = match self.pending.remove(&id) {
+                Some(seen) => {
+                    events.push(MempoolEvent::Left { id: id.clone() });
+                    seen
+                }
+                None => match self.left.remove(&id) {
+                    Some((seen, _)) => seen,
+                    None => continue,
+                },
+            };
+            events.push(MempoolEvent::Included {
+                id,
+                slot: block.slot(),
+                waited: minted - seen,
+            });
+        }
+        events
+    }
+}
@@ -0,0 +1,308 @@
+//! Prometheus metrics of the sync, served on `/metrics` of the address
+//! given with `--metrics`.
+use std::fmt::Write;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex as StdMutex};
+
+use color_eyre::eyre::Result;
+use hyper::{header, Body, Method, Request, Response, StatusCode};
+use tokio_stream::StreamExt;
+
+use crate::chain::{ChainEvent, SyncProgress};
+use crate::server::Listener;
+use crate::synchronization::{Lagged, Watched};
+use crate::ws::ConnectionEvent;
+
+const ROLLBACK_DEPTHS: [f64; 9] = [1.0, 2.0, 5.0, 10.0, 20.0, 50.0, 100.0, 500.0, 2160.0];
+const DUMP_SECONDS: [f64; 8] = [0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 10.0, 60.0];
+
+/// Observations counted in cumulative buckets.
+#[derive(Debug)]
+struct Histogram {
+    bounds: &'static [f64],
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [f64]) -> Self {
+        Self {
+            bounds,
+            counts: vec![0; bounds.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    fn observe(&mut self, value: f64) {
+        for (bound, count) in self.bounds.iter().zip(&mut self.counts) {
+            if value <= *bound {
+                *count += 1;
+            }
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+/// What was seen of the engine events.
+#[derive(Debug)]
+pub struct Metrics {
+    blocks: u64,
+    blocks_per_sec: f64,
+    slot: Option<u64>,
+    tip_slot: Option<u64>,
+    rollbacks: u64,
+    rollback_depth: Histogram,
+    reconnects: u64,
+    connection_errors: u64,
+    decode_errors: u64,
+    dumps: u64,
+    dump_failures: u64,
+    dump_seconds: Histogram,
+    lagged: u64,
+    blocks_in_memory: usize,
+    memory_bytes: usize,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            blocks: 0,
+            blocks_per_sec: 0.0,
+            slot: None,
+            tip_slot: None,
+            rollbacks: 0,
+            rollback_depth: Histogram::new(&ROLLBACK_DEPTHS),
+            reconnects: 0,
+            connection_errors: 0,
+            decode_errors: 0,
+            dumps: 0,
+            dump_failures: 0,
+            dump_seconds: Histogram::new(&DUMP_SECONDS),
+            lagged: 0,
+            blocks_in_memory: 0,
+            memory_bytes: 0,
+        }
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, event: &ChainEvent) {
+        match event {
+            ChainEvent::Collection(blocks) => {
+                self.blocks += blocks.len() as u64;
+                if let Some(block) = blocks.last() {
+                    self.slot = Some(block.slot());
+                }
+            }
+            ChainEvent::Synchronizing(SyncProgress::Synchronizing(_, _, tip))
+            | ChainEvent::Synchronizing(SyncProgress::Synchronized(tip)) => {
+                self.tip_slot = Some(tip.slot)
+            }
+            ChainEvent::Throughput(throughput) => self.blocks_per_sec = throughput.blocks_per_sec,
+            // The slot is left as it is until the next block, as the event
+            // does not tell which block the chain went back to.
+            ChainEvent::RevertFork(reverted) => {
+                self.rollbacks += 1;
+                self.rollback_depth.observe(reverted.len() as f64);
+            }
+            ChainEvent::Connection(ConnectionEvent::Decode { .. }) => self.decode_errors += 1,
+            ChainEvent::Connection(ConnectionEvent::Closed(_))
+            | ChainEvent::Connection(ConnectionEvent::Transport(_))
+            | ChainEvent::Connection(ConnectionEvent::Fault(_)) => self.connection_errors += 1,
+            ChainEvent::Reintersecting(_) => self.reconnects += 1,
+            ChainEvent::Dumped { elapsed, .. } => {
+                self.dumps += 1;
+                self.dump_seconds.observe(elapsed.as_secs_f64());
+            }
+            ChainEvent::DumpFailed { .. } => self.dump_failures += 1,
+            ChainEvent::Memory { blocks, bytes } => {
+                self.blocks_in_memory = *blocks;
+                self.memory_bytes = *bytes;
+            }
+            _ => (),
+        }
+    }
+
+    /// The metrics in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, value: f64| {
+            let _ = writeln!(out, "# HELP mini_explorer_{} {}", name, help);
+            let _ = writeln!(out, "# TYPE mini_explorer_{} {}", name, kind);
+            let _ = writeln!(out, "mini_explorer_{} {}", name, value);
+        };
+        metric(
+            "blocks_total",
+            "counter",
+            "Blocks added to the chain.",
+            self.blocks as f64,
+        );
+        metric(
+            "blocks_per_second",
+            "gauge",
+            "Blocks received per second.",
+            self.blocks_per_sec,
+        );
+        if let Some(slot) = self.slot {
+            metric("slot", "gauge", "Slot of the last block.", slot as f64);
+        }
+        if let Some(slot) = self.tip_slot {
+            metric(
+                "tip_slot",
+                "gauge",
+                "Slot of the tip of the source.",
+                slot as f64,
+            );
+        }
+        metric(
+            "rollbacks_total",
+            "counter",
+            "Rollbacks reverting blocks.",
+            self.rollbacks as f64,
+        );
+        metric(
+            "reconnects_total",
+            "counter",
+            "Connections opened again to intersect from the last blocks.",
+            self.reconnects as f64,
+        );
+        metric(
+            "connection_errors_total",
+            "counter",
+            "Faults, transport errors and closings of the connection.",
+            self.connection_errors as f64,
+        );
+        metric(
+            "decode_errors_total",
+            "counter",
+            "Messages which could not be decoded.",
+            self.decode_errors as f64,
+        );
+        metric(
+            "chunk_dumps_total",
+            "counter",
+            "Chunks written to the data directory.",
+            self.dumps as f64,
+        );
+        metric(
+            "chunk_dump_failures_total",
+            "counter",
+            "Chunks which could not be written.",
+            self.dump_failures as f64,
+        );
+        metric(
+            "events_lagged_total",
+            "counter",
+            "Events missed by the metrics for being slower than the engine.",
+            self.lagged as f64,
+        );
+        metric(
+            "chain_blocks_in_memory",
+            "gauge",
+            "Blocks held in memory, volatile or not written yet.",
+            self.blocks_in_memory as f64,
+        );
+        metric(
+            "chain_memory_bytes",
+            "gauge",
+            "Approximate size of the blocks held in memory.",
+            self.memory_bytes as f64,
+        );
+        histogram(
+            &mut out,
+            "rollback_depth",
+            "Blocks reverted by a rollback.",
+            &self.rollback_depth,
+        );
+        histogram(
+            &mut out,
+            "chunk_dump_seconds",
+            "Time taken to write a chunk.",
+            &self.dump_seconds,
+        );
+        out
+    }
+}
+
+fn histogram(out: &mut String, name: &str, help: &str, histogram: &Histogram) {
+    let _ = writeln!(out, "# HELP mini_explorer_{} {}", name, help);
+    let _ = writeln!(out, "# TYPE mini_explorer_{} histogram", name);
+    for (bound, count) in histogram.bounds.iter().zip(&histogram.counts) {
+        let _ = writeln!(
+            out,
+            "mini_explorer_{}_bucket{{le=\"{}\"}} {}",
+            name, bound, count
+        );
+    }
+    let _ = writeln!(
+        out,
+        "mini_explorer_{}_bucket{{le=\"+Inf\"}} {}",
+        name, histogram.count
+    );
+    let _ = writeln!(out, "mini_explorer_{}_sum {}", name, histogram.sum);
+    let _ = writeln!(out, "mini_explorer_{}_count {}", name, histogram.count);
+}
+
+/// Records `events` until the engine stops.
+pub async fn record(mut events: Watched, metrics: Arc<StdMutex<Metrics>>) {
+    while let Some(event) = events.next().await {
+        if let Ok(mut metrics) = metrics.lock() {
+            match event {
+                Ok(event) => metrics.record(&event),
+                Err(Lagged(missed)) => metrics.lagged += missed,
+            }
+        }
+    }
+}
+
+async fn handle(req: Request<Body>, metrics: Arc<StdMutex<Metrics>>) -> Response<Body> {
+    if req.method() != Method::GET || req.uri().path() != "/metrics" {
+        return Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::empty())
+            .unwrap();
+    }
+    let text = match metrics.lock() {
+        Ok(metrics) => metrics.render(),
+        Err(_) => String::new(),
+    };
+    Response::builder()
+        .header(header::CONTENT_TYPE, "text/plain; version=0.0.4")
+        .body(Body::from(text))
+        .unwrap()
+}
+
+/// The metrics bound to their address, served by `run`.
+pub struct Exporter {
+    listener: Listener,
+    metrics: Arc<StdMutex<Metrics>>,
+}
+
+impl Exporter {
+    pub fn bind(addr: &SocketAddr, metrics: Arc<StdMutex<Metrics>>) -> Result<Self> {
+        Ok(Self {
+            listener: Listener::bind(addr)?,
+            metrics,
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.listener.local_addr()
+    }
+
+    /// Serves the metrics until `shutdown` resolves.
+    pub async fn run(self, shutdown: impl Future<Output = ()>) -> Result<()> {
+        let metrics = self.metrics;
+        self.listener
+            .serve(move |req| handle(req, metrics.clone()), shutdown)
+            .await
+    }
+}
@@ -0,0 +1,119 @@
+use color_eyre::eyre::{eyre, Result};
+use minicbor::data::Tag;
+use minicbor::{Decoder, Encoder};
+
+use crate::data::{Point, PointOrOrigin, RResult, Tip};
+use crate::n2c::decode;
+use crate::n2c::mux::Channel;
+use crate::ws::ConnectionEvent;
+
+/// Client side of the node-to-client chain-sync mini-protocol.
+pub struct ChainSync {
+    channel: Channel,
+}
+
+pub fn encode_point(e: &mut Encoder<Vec<u8>>, point: &PointOrOrigin) -> Result<()> {
+    match point {
+        PointOrOrigin::Point(Point { slot, hash }) => {
+            e.array(2)?.u64(*slot)?.bytes(&hex::decode(hash)?)?;
+        }
+        PointOrOrigin::Origin(_) => {
+            e.array(0)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn decode_point(d: &mut Decoder) -> Result<PointOrOrigin> {
+    match d.array()? {
+        Some(0) => Ok(PointOrOrigin::origin()),
+        _ => {
+            let slot = d.u64()?;
+            Ok(PointOrOrigin::point(slot, hex::encode(d.bytes()?)))
+        }
+    }
+}
+
+fn decode_tip(d: &mut Decoder) -> Result<Tip> {
+    d.array()?;
+    let (slot, hash) = match decode_point(d)? {
+        PointOrOrigin::Point(Point { slot, hash }) => (slot, hash),
+        PointOrOrigin::Origin(_) => (0, "".to_string()),
+    };
+    Ok(Tip {
+        slot,
+        hash,
+        block_no: d.u64()?,
+    })
+}
+
+impl ChainSync {
+    pub fn new(channel: Channel) -> Self {
+        Self { channel }
+    }
+
+    pub async fn find_intersect(&mut self, points: &[PointOrOrigin]) -> Result<RResult> {
+        let mut e = Encoder::new(Vec::new());
+        e.array(2)?.u8(4)?.array(points.len() as u64)?;
+        for point in points {
+            encode_point(&mut e, point)?;
+        }
+        self.channel.send(&e.into_writer()).await?;
+
+        let msg = self.channel.recv().await?;
+        let mut d = Decoder::new(&msg);
+        d.array()?;
+        match d.u8()? {
+            5 => Ok(RResult::IntersectionFound {
+                point: decode_point(&mut d)?,
+                tip: decode_tip(&mut d)?,
+            }),
+            6 => Ok(RResult::IntersectionNotFound {
+                tip: Some(decode_tip(&mut d)?),
+            }),
+            n => Err(eyre!("Unexpected chain-sync message {}.", n)),
+        }
+    }
+
+    /// Requests the next update, waiting through `MsgAwaitReply` when the
+    /// node is at its tip. Blocks which cannot be decoded are reported as a
+    /// `ConnectionEvent` rather than failing the connection.
+    pub async fn request_next(&mut self) -> Result<Result<RResult, ConnectionEvent>> {
+        let mut e = Encoder::new(Vec::new());
+        e.array(1)?.u8(0)?;
+        self.channel.send(&e.into_writer()).await?;
+
+        loop {
+            let msg = self.channel.recv().await?;
+            let mut d = Decoder::new(&msg);
+            d.array()?;
+            match d.u8()? {
+                1 => continue,
+                2 => {
+                    if d.tag()? != Tag::Cbor {
+                        return Err(eyre!("Expected a CBOR-wrapped block."));
+                    }
+                    let bytes = d.bytes()?;
+                    let block = match decode::block(bytes) {
+                        Ok(block) => block,
+                        Err(e) => {
+                            return Ok(Err(ConnectionEvent::Decode {
+                                error: e.to_string(),
+                                payload: hex::encode(bytes),
+                            }))
+                        }
+                    };
+                    let tip = decode_tip(&mut d)?;
+                    return Ok(Ok(RResult::RollForward { block, tip }));
+                }
+                3 => {
+                    return Ok(Ok(RResult::RollBackward {
+                        point: decode_point(&mut d)?,
+                        tip: decode_tip(&mut d)?,
+                    }))
+                }
+                n => return Err(eyre!("Unexpected chain-sync message {}.", n)),
+            }
+        }
+    }
+}
@@ -0,0 +1,700 @@
+//! Decoding of CBOR blocks, as served by the node, into the `data` types.
+//! Fields are rendered as Ogmios does: hashes in hex, addresses in bech32
+//! (base58 for Byron) and metadata in its detailed JSON schema.
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+use bech32::{ToBase32, Variant};
+use blake2::digest::consts::U32;
+use blake2::{Blake2b, Digest};
+use color_eyre::eyre::{eyre, Result};
+use minicbor::data::{Tag, Type};
+use minicbor::Decoder;
+
+use crate::data::alonzo::{TxBodyAlonzo, TxOutAlonzo};
+use crate::data::byron::{ByronBlockEra, ByronBody, ByronHeader, SoftwareVersion, TxBodyByron};
+use crate::data::shelley::{
+    BlobMetadata, Certificate, Mint, PoolMetaData, Relay, ShelleyBlockEra, ShelleyHeader,
+    TxBodyAllegra, TxBodyMary, TxBodyShelley, TxMetadata, ValidityInterval,
+};
+use crate::data::{Block, ProtocolVersion, Tx, TxIn, TxOut, Value};
+
+type Blake2b256 = Blake2b<U32>;
+
+fn hash(bytes: &[u8]) -> String {
+    hex::encode(Blake2b256::digest(bytes))
+}
+
+/// Skips over the next item, returning its encoded bytes.
+fn span<'b>(d: &mut Decoder<'b>) -> Result<&'b [u8]> {
+    let start = d.position();
+    d.skip()?;
+    Ok(&d.input()[start..d.position()])
+}
+
+/// Decodes a definite or indefinite array, or a tagged set, item by item.
+fn array<'b, T>(
+    d: &mut Decoder<'b>,
+    mut item: impl FnMut(&mut Decoder<'b>) -> Result<T>,
+) -> Result<Vec<T>> {
+    if d.datatype()? == Type::Tag {
+        d.tag()?;
+    }
+    let mut items = Vec::new();
+    match d.array()? {
+        Some(n) => {
+            for _ in 0..n {
+                items.push(item(d)?);
+            }
+        }
+        None => {
+            while d.datatype()? != Type::Break {
+                items.push(item(d)?);
+            }
+            d.set_position(d.position() + 1);
+        }
+    }
+    Ok(items)
+}
+
+/// Decodes a definite or indefinite map, entry by entry.
+fn map<'b, T>(
+    d: &mut Decoder<'b>,
+    mut entry: impl FnMut(&mut Decoder<'b>) -> Result<T>,
+) -> Result<Vec<T>> {
+    let mut entries = Vec::new();
+    match d.map()? {
+        Some(n) => {
+            for _ in 0..n {
+                entries.push(entry(d)?);
+            }
+        }
+        None => {
+            while d.datatype()? != Type::Break {
+                entries.push(entry(d)?);
+            }
+            d.set_position(d.position() + 1);
+        }
+    }
+    Ok(entries)
+}
+
+fn nullable<'b, T>(
+    d: &mut Decoder<'b>,
+    item: impl FnOnce(&mut Decoder<'b>) -> Result<T>,
+) -> Result<Option<T>> {
+    if d.datatype()? == Type::Null {
+        d.null()?;
+        Ok(None)
+    } else {
+        item(d).map(Some)
+    }
+}
+
+pub fn address(bytes: &[u8]) -> String {
+    let mainnet = bytes.first().map(|h| h & 0x0f == 1).unwrap_or(false);
+    let hrp = match bytes.first().map(|h| h >> 4) {
+        Some(0..=7) if mainnet => "addr",
+        Some(0..=7) => "addr_test",
+        Some(14..=15) if mainnet => "stake",
+        Some(14..=15) => "stake_test",
+        Some(8) => return bs58::encode(bytes).into_string(),
+        _ => return hex::encode(bytes),
+    };
+    bech32::encode(hrp, bytes.to_base32(), Variant::Bech32).unwrap_or_else(|_| hex::encode(bytes))
+}
+
+fn pool_id(bytes: &[u8]) -> String {
+    bech32::encode("pool", bytes.to_base32(), Variant::Bech32)
+        .unwrap_or_else(|_| hex::encode(bytes))
+}
+
+fn credential(d: &mut Decoder) -> Result<String> {
+    d.array()?;
+    d.u8()?;
+    Ok(hex::encode(d.bytes()?))
+}
+
+/// Decodes a block served by chain-sync, wrapped as `[era, block]`.
+pub fn block(bytes: &[u8]) -> Result<Block> {
+    let mut d = Decoder::new(bytes);
+    d.array()?;
+    match d.u8()? {
+        0 => byron_ebb(&mut d),
+        1 => byron_main(&mut d),
+        era @ 2..=7 => shelley(&mut d, era),
+        era => Err(eyre!("Unknown era {}.", era)),
+    }
+}
+
+fn byron_hash(prefix: u8, header: &[u8]) -> String {
+    let mut bytes = vec![0x82, prefix];
+    bytes.extend_from_slice(header);
+    hash(&bytes)
+}
+
+fn byron_ebb(d: &mut Decoder) -> Result<Block> {
+    d.array()?;
+    let header_bytes = span(d)?;
+
+    let mut h = Decoder::new(header_bytes);
+    h.array()?;
+    h.skip()?;
+    let prev_hash = hex::encode(h.bytes()?);
+    h.skip()?;
+    h.array()?;
+    let epoch = h.u64()?;
+    h.array()?;
+    let difficulty = h.u64()?;
+
+    Ok(Block::Byron(ByronBlockEra {
+        body: None,
+        header: ByronHeader {
+            protocol_magic_id: None,
+            protocol_version: None,
+            block_height: difficulty,
+            prev_hash,
+            epoch: Some(epoch),
+            software_version: None,
+            genesis_key: None,
+        },
+        header_hash: byron_hash(0, header_bytes),
+    }))
+}
+
+fn byron_main(d: &mut Decoder) -> Result<Block> {
+    d.array()?;
+    let header_bytes = span(d)?;
+
+    let mut h = Decoder::new(header_bytes);
+    h.array()?;
+    let protocol_magic = h.u64()?;
+    let prev_hash = hex::encode(h.bytes()?);
+    h.skip()?;
+    h.array()?;
+    h.array()?;
+    let epoch = h.u64()?;
+    h.skip()?;
+    let genesis_key = hex::encode(h.bytes()?);
+    h.array()?;
+    let difficulty = h.u64()?;
+    h.skip()?;
+    h.array()?;
+    h.array()?;
+    let major = h.u64()?;
+    let minor = h.u64()?;
+    let patch = h.u64()?;
+    h.array()?;
+    let app_name = h.str()?.to_string();
+    let number = h.u64()?;
+
+    d.array()?;
+    let txs = array(d, |d| {
+        d.array()?;
+        let tx_bytes = span(d)?;
+        d.skip()?;
+        byron_tx(tx_bytes)
+    })?;
+
+    Ok(Block::Byron(ByronBlockEra {
+        body: Some(ByronBody {
+            tx_payload: Some(txs),
+            update_payload: HashMap::new(),
+        }),
+        header: ByronHeader {
+            protocol_magic_id: Some(protocol_magic),
+            protocol_version: Some(ProtocolVersion {
+                major,
+                minor,
+                patch: Some(patch),
+            }),
+            block_height: difficulty,
+            prev_hash,
+            epoch: Some(epoch),
+            software_version: Some(SoftwareVersion { app_name, number }),
+            genesis_key: Some(genesis_key),
+        },
+        header_hash: byron_hash(1, header_bytes),
+    }))
+}
+
+fn byron_tx(bytes: &[u8]) -> Result<Tx<TxBodyByron>> {
+    let mut d = Decoder::new(bytes);
+    d.array()?;
+    let inputs = array(&mut d, |d| {
+        d.array()?;
+        d.u8()?;
+        d.tag()?;
+        let mut input = Decoder::new(d.bytes()?);
+        input.array()?;
+        Ok(TxIn {
+            tx_id: hex::encode(input.bytes()?),
+            index: input.u64()?,
+        })
+    })?;
+    let outputs = array(&mut d, |d| {
+        d.array()?;
+        let address = bs58::encode(span(d)?).into_string();
+        let coins = d.u64()?;
+        Ok(TxOut {
+            address,
+            value: Value { coins },
+        })
+    })?;
+
+    Ok(Tx {
+        id: hash(bytes),
+        body: TxBodyByron {
+            inputs: Some(inputs),
+            outputs: Some(outputs),
+            fee: None,
+        },
+        metadata: None,
+    })
+}
+
+fn shelley_header(bytes: &[u8], era: u8) -> Result<ShelleyHeader> {
+    let mut d = Decoder::new(bytes);
+    d.array()?;
+    d.array()?;
+    let block_height = d.u64()?;
+    let slot = d.u64()?;
+    let prev_hash =
+        nullable(&mut d, |d| Ok(hex::encode(d.bytes()?)))?.unwrap_or_else(|| "genesis".to_string());
+    let issuer_vk = hex::encode(d.bytes()?);
+    d.skip()?;
+    // Up to Alonzo, headers carry separate nonce and leader VRF results.
+    if era <= 5 {
+        d.skip()?;
+    }
+    d.skip()?;
+    let block_size = d.u64()?;
+    let block_hash = hex::encode(d.bytes()?);
+
+    Ok(ShelleyHeader {
+        block_height,
+        slot,
+        prev_hash,
+        issuer_vk,
+        block_size,
+        block_hash,
+    })
+}
+
+#[derive(Default)]
+struct TxFields {
+    inputs: Vec<TxIn>,
+    outputs: Vec<TxOutAlonzo>,
+    fee: u64,
+    ttl: Option<u64>,
+    certificates: Vec<Certificate>,
+    withdrawals: HashMap<String, u64>,
+    validity_start: Option<u64>,
+    mint: HashMap<String, i64>,
+    script_integrity_hash: Option<String>,
+    required_signers: Vec<String>,
+    network: Option<u64>,
+}
+
+impl TxFields {
+    fn outputs(&self) -> Vec<TxOut> {
+        self.outputs
+            .iter()
+            .map(|o| TxOut {
+                address: o.address.clone(),
+                value: o.value.clone(),
+            })
+            .collect()
+    }
+
+    fn validity_interval(&self) -> ValidityInterval {
+        ValidityInterval {
+            invalid_before: self.validity_start,
+            invalid_hereafter: self.ttl,
+        }
+    }
+
+    fn mint(&self) -> Mint {
+        Mint {
+            coins: 0,
+            assets: self.mint.clone(),
+        }
+    }
+
+    fn shelley(self) -> TxBodyShelley {
+        TxBodyShelley {
+            outputs: self.outputs(),
+            ttl: self.ttl.unwrap_or(0),
+            inputs: self.inputs,
+            certificates: self.certificates,
+            withdrawals: self.withdrawals,
+            fee: self.fee,
+            update: None,
+        }
+    }
+
+    fn allegra(self) -> TxBodyAllegra {
+        TxBodyAllegra {
+            outputs: self.outputs(),
+            validity_interval: self.validity_interval(),
+            inputs: self.inputs,
+            certificates: self.certificates,
+            withdrawals: self.withdrawals,
+            fee: self.fee,
+            update: None,
+        }
+    }
+
+    fn mary(self) -> TxBodyMary {
+        TxBodyMary {
+            outputs: self.outputs(),
+            validity_interval: self.validity_interval(),
+            mint: self.mint(),
+            inputs: self.inputs,
+            certificates: self.certificates,
+            withdrawals: self.withdrawals,
+            fee: self.fee,
+            update: None,
+        }
+    }
+
+    fn alonzo(self) -> TxBodyAlonzo {
+        TxBodyAlonzo {
+            validity_interval: self.validity_interval(),
+            mint: self.mint(),
+            inputs: self.inputs,
+            outputs: self.outputs,
+            certificates: self.certificates,
+            withdrawals: self.withdrawals,
+            fee: self.fee,
+            update: None,
+            network: self.network,
+            script_integrity_hash: self.script_integrity_hash,
+            required_extra_signatures: self.required_signers,
+        }
+    }
+}
+
+fn value(d: &mut Decoder) -> Result<Value> {
+    let coins = match d.datatype()? {
+        Type::Array => {
+            d.array()?;
+            let coins = d.u64()?;
+            d.skip()?;
+            coins
+        }
+        _ => d.u64()?,
+    };
+    Ok(Value { coins })
+}
+
+fn output(d: &mut Decoder) -> Result<TxOutAlonzo> {
+    if d.datatype()? == Type::Map {
+        let mut out = TxOutAlonzo {
+            address: "".to_string(),
+            value: Value { coins: 0 },
+            datum: None,
+        };
+        map(d, |d| {
+            match d.u8()? {
+                0 => out.address = address(d.bytes()?),
+                1 => out.value = value(d)?,
+                2 => {
+                    d.array()?;
+                    out.datum = Some(match d.u8()? {
+                        0 => hex::encode(d.bytes()?),
+                        _ => {
+                            d.tag()?;
+                            hex::encode(d.bytes()?)
+                        }
+                    });
+                }
+                _ => d.skip()?,
+            }
+            Ok(())
+        })?;
+        Ok(out)
+    } else {
+        let len = d.array()?;
+        let address = address(d.bytes()?);
+        let value = value(d)?;
+        let datum = if len == Some(3) {
+            Some(hex::encode(d.bytes()?))
+        } else {
+            None
+        };
+        Ok(TxOutAlonzo {
+            address,
+            value,
+            datum,
+        })
+    }
+}
+
+fn relay(d: &mut Decoder) -> Result<Relay> {
+    d.array()?;
+    match d.u8()? {
+        0 => {
+            let port = nullable(d, |d| Ok(d.u16()?))?;
+            let ipv4 = nullable(d, |d| {
+                let b = d.bytes()?;
+                Ok(b.iter()
+                    .map(|n| n.to_string())
+                    .collect::<Vec<_>>()
+                    .join("."))
+            })?;
+            let ipv6 = nullable(d, |d| {
+                let b = d.bytes()?;
+                Ok(b.chunks(2).map(hex::encode).collect::<Vec<_>>().join(":"))
+            })?;
+            Ok(Relay::Ip {
+                port: port.unwrap_or(0),
+                ipv4,
+                ipv6,
+            })
+        }
+        1 => {
+            let port = nullable(d, |d| Ok(d.u16()?))?;
+            Ok(Relay::Hostname {
+                port,
+                hostname: d.str()?.to_string(),
+            })
+        }
+        _ => Ok(Relay::Hostname {
+            hostname: d.str()?.to_string(),
+            port: None,
+        }),
+    }
+}
+
+fn certificate(d: &mut Decoder) -> Result<Option<Certificate>> {
+    let len = d.array()?.unwrap_or(0);
+    let cert = match d.u8()? {
+        0 | 7 => Some(Certificate::StakeKeyRegistration(credential(d)?)),
+        1 | 8 => Some(Certificate::StakeKeyDeregistration(credential(d)?)),
+        2 => Some(Certificate::StakeDelegation {
+            delegator: credential(d)?,
+            delegatee: pool_id(d.bytes()?),
+        }),
+        3 => {
+            let id = pool_id(d.bytes()?);
+            let vrf = hex::encode(d.bytes()?);
+            let pledge = d.u64()?;
+            let cost = d.u64()?;
+            d.tag()?;
+            d.array()?;
+            let margin = format!("{}/{}", d.u64()?, d.u64()?);
+            let reward_account = address(d.bytes()?);
+            let owners = array(d, |d| Ok(hex::encode(d.bytes()?)))?;
+            let relays = array(d, relay)?;
+            let metadata = nullable(d, |d| {
+                d.array()?;
+                Ok(PoolMetaData {
+                    url: d.str()?.to_string(),
+                    hash: hex::encode(d.bytes()?),
+                })
+            })?;
+            Some(Certificate::PoolRegistration {
+                id,
+                vrf,
+                pledge,
+                cost,
+                margin,
+                reward_account,
+                owners,
+                relays,
+                metadata,
+            })
+        }
+        4 => Some(Certificate::PoolRetirement {
+            pool_id: pool_id(d.bytes()?),
+            retirement_epoch: d.u64()?,
+        }),
+        6 => {
+            d.array()?;
+            let pot = match d.u8()? {
+                0 => "reserves",
+                _ => "treasury",
+            };
+            let rewards = if d.datatype()? == Type::Map {
+                map(d, |d| Ok((credential(d)?, d.i64()? as u64)))?
+                    .into_iter()
+                    .collect()
+            } else {
+                d.skip()?;
+                HashMap::new()
+            };
+            Some(Certificate::MoveInstantaneousRewards {
+                pot: pot.to_string(),
+                rewards,
+            })
+        }
+        _ => {
+            for _ in 1..len {
+                d.skip()?;
+            }
+            None
+        }
+    };
+    Ok(cert)
+}
+
+fn tx_fields(bytes: &[u8]) -> Result<TxFields> {
+    let mut d = Decoder::new(bytes);
+    let mut fields = TxFields::default();
+    map(&mut d, |d| {
+        match d.u8()? {
+            0 => {
+                fields.inputs = array(d, |d| {
+                    d.array()?;
+                    Ok(TxIn {
+                        tx_id: hex::encode(d.bytes()?),
+                        index: d.u64()?,
+                    })
+                })?
+            }
+            1 => fields.outputs = array(d, output)?,
+            2 => fields.fee = d.u64()?,
+            3 => fields.ttl = Some(d.u64()?),
+            4 => fields.certificates = array(d, certificate)?.into_iter().flatten().collect(),
+            5 => {
+                fields.withdrawals = map(d, |d| Ok((address(d.bytes()?), d.u64()?)))?
+                    .into_iter()
+                    .collect()
+            }
+            8 => fields.validity_start = Some(d.u64()?),
+            9 => {
+                fields.mint = map(d, |d| {
+                    let policy = hex::encode(d.bytes()?);
+                    map(d, |d| {
+                        let name = hex::encode(d.bytes()?);
+                        let id = if name.is_empty() {
+                            policy.clone()
+                        } else {
+                            format!("{}.{}", policy, name)
+                        };
+                        Ok((id, d.i64()?))
+                    })
+                })?
+                .into_iter()
+                .flatten()
+                .collect()
+            }
+            11 => fields.script_integrity_hash = Some(hex::encode(d.bytes()?)),
+            14 => fields.required_signers = array(d, |d| Ok(hex::encode(d.bytes()?)))?,
+            15 => fields.network = Some(d.u64()?),
+            _ => d.skip()?,
+        }
+        Ok(())
+    })?;
+    Ok(fields)
+}
+
+fn metadatum(d: &mut Decoder) -> Result<serde_json::Value> {
+    use serde_json::json;
+
+    Ok(match d.datatype()? {
+        Type::Bytes | Type::BytesIndef => json!({ "bytes": hex::encode(d.bytes()?) }),
+        Type::String | Type::StringIndef => json!({ "string": d.str()? }),
+        Type::Array | Type::ArrayIndef => json!({ "list": array(d, metadatum)? }),
+        Type::Map | Type::MapIndef => {
+            let entries = map(d, |d| Ok(json!({ "k": metadatum(d)?, "v": metadatum(d)? })))?;
+            json!({ "map": entries })
+        }
+        _ => {
+            let n = i128::from(d.int()?);
+            match i64::try_from(n) {
+                Ok(n) => json!({ "int": n }),
+                Err(_) => json!({ "int": n.to_string() }),
+            }
+        }
+    })
+}
+
+/// Decodes the metadata labels of auxiliary data, in any of its Shelley,
+/// Allegra/Mary or Alonzo shapes.
+fn metadata(bytes: &[u8]) -> Result<HashMap<String, serde_json::Value>> {
+    let labels = |d: &mut Decoder| -> Result<HashMap<String, serde_json::Value>> {
+        Ok(map(d, |d| Ok((d.u64()?.to_string(), metadatum(d)?)))?
+            .into_iter()
+            .collect())
+    };
+
+    let mut d = Decoder::new(bytes);
+    match d.datatype()? {
+        Type::Map | Type::MapIndef => labels(&mut d),
+        Type::Array | Type::ArrayIndef => {
+            d.array()?;
+            labels(&mut d)
+        }
+        _ => {
+            if d.tag()? != Tag::Unassigned(259) {
+                return Err(eyre!("Unknown auxiliary data format."));
+            }
+            let mut found = HashMap::new();
+            map(&mut d, |d| {
+                if d.u8()? == 0 {
+                    found = labels(d)?;
+                } else {
+                    d.skip()?;
+                }
+                Ok(())
+            })?;
+            Ok(found)
+        }
+    }
+}
+
+fn shelley(d: &mut Decoder, era: u8) -> Result<Block> {
+    d.array()?;
+    let header_bytes = span(d)?;
+    let header = shelley_header(header_bytes, era)?;
+    let header_hash = hash(header_bytes);
+
+    let bodies = array(d, span)?;
+    d.skip()?;
+    let aux: HashMap<u64, &[u8]> = map(d, |d| Ok((d.u64()?, span(d)?)))?.into_iter().collect();
+
+    let mut txs = Vec::new();
+    for (i, body) in bodies.into_iter().enumerate() {
+        let metadata = match aux.get(&(i as u64)) {
+            Some(bytes) => Some(TxMetadata {
+                hash: hash(bytes),
+                body: BlobMetadata {
+                    blob: metadata(bytes)?,
+                },
+            }),
+            None => None,
+        };
+        txs.push((hash(body), tx_fields(body)?, metadata));
+    }
+
+    fn era_block<Body: Clone>(
+        header: ShelleyHeader,
+        header_hash: String,
+        txs: Vec<(String, TxFields, Option<TxMetadata>)>,
+        body: impl Fn(TxFields) -> Body,
+    ) -> ShelleyBlockEra<ShelleyHeader, Body> {
+        ShelleyBlockEra {
+            body: txs
+                .into_iter()
+                .map(|(id, fields, metadata)| Tx {
+                    id,
+                    body: body(fields),
+                    metadata,
+                })
+                .collect(),
+            header,
+            header_hash,
+        }
+    }
+
+    Ok(match era {
+        2 => Block::Shelley(era_block(header, header_hash, txs, TxFields::shelley)),
+        3 => Block::Allegra(era_block(header, header_hash, txs, TxFields::allegra)),
+        4 => Block::Mary(era_block(header, header_hash, txs, TxFields::mary)),
+        5 => Block::Alonzo(era_block(header, header_hash, txs, TxFields::alonzo)),
+        6 => Block::Babbage(era_block(header, header_hash, txs, TxFields::alonzo)),
+        _ => Block::Conway(era_block(header, header_hash, txs, TxFields::alonzo)),
+    })
+}
@@ -0,0 +1,38 @@
+use color_eyre::eyre::{eyre, Result};
+use minicbor::{Decoder, Encoder};
+
+use crate::n2c::mux::Channel;
+
+/// Node-to-client versions 9 to 16, bit 15 marking the node-to-client
+/// protocol family.
+const VERSIONS: std::ops::RangeInclusive<u64> = 32777..=32784;
+/// From version 15 on, version data also carries the query flag.
+const QUERY_FLAG_SINCE: u64 = 32783;
+
+/// Proposes all supported versions for `magic` and returns the one accepted
+/// by the node.
+pub async fn handshake(channel: &mut Channel, magic: u64) -> Result<u64> {
+    let mut e = Encoder::new(Vec::new());
+    e.array(2)?.u8(0)?.map(VERSIONS.count() as u64)?;
+    for version in VERSIONS {
+        e.u64(version)?;
+        if version >= QUERY_FLAG_SINCE {
+            e.array(2)?.u64(magic)?.bool(false)?;
+        } else {
+            e.u64(magic)?;
+        }
+    }
+    channel.send(&e.into_writer()).await?;
+
+    let msg = channel.recv().await?;
+    let mut d = Decoder::new(&msg);
+    d.array()?;
+    match d.u8()? {
+        1 => Ok(d.u64()?),
+        2 => Err(eyre!(
+            "Handshake refused by the node: {}",
+            hex::encode(&msg)
+        )),
+        n => Err(eyre!("Unexpected handshake message {}.", n)),
+    }
+}
@@ -0,0 +1,102 @@
+//! Direct node-to-client connection to a local cardano-node, as an
+//! alternative to going through Ogmios.
+use std::fmt;
+use std::path::PathBuf;
+
+use color_eyre::eyre::{eyre, Result};
+use futures::future::{BoxFuture, FutureExt};
+use tokio::sync::mpsc::Sender;
+use tracing::info;
+
+use crate::data::mempool::MempoolSnapshot;
+use crate::data::PointOrOrigin;
+use crate::source::ChainSource;
+use crate::ws::{ErrorPolicy, Incoming};
+
+pub mod chainsync;
+pub mod decode;
+pub mod handshake;
+pub mod mux;
+pub mod statequery;
+
+use chainsync::ChainSync;
+use statequery::StateQuery;
+
+/// Chain-sync client talking to the node over its Unix socket. Results are
+/// reported to the `Engine` like those of a `ws::Connection`.
+#[derive(Debug, Clone)]
+pub struct NodeClient {
+    socket: PathBuf,
+    magic: u64,
+    policy: ErrorPolicy,
+}
+
+impl NodeClient {
+    pub fn new(socket: PathBuf, magic: u64, policy: ErrorPolicy) -> Self {
+        NodeClient {
+            socket,
+            magic,
+            policy,
+        }
+    }
+
+    async fn sync(&self, points: Vec<PointOrOrigin>, channel: Sender<Incoming>) -> Result<()> {
+        let mut channels = mux::connect(
+            &self.socket,
+            &[mux::HANDSHAKE, mux::CHAIN_SYNC, mux::STATE_QUERY],
+        )
+        .await?;
+        let mut take = |protocol| {
+            channels
+                .remove(&protocol)
+                .ok_or_else(|| eyre!("Missing mini-protocol {}.", protocol))
+        };
+        let mut handshake = take(mux::HANDSHAKE)?;
+        let mut chain_sync = ChainSync::new(take(mux::CHAIN_SYNC)?);
+        let mut state_query = StateQuery::new(take(mux::STATE_QUERY)?);
+
+        let version = handshake::handshake(&mut handshake, self.magic).await?;
+        info!("Connected to {:?} (version {})", self.socket, version);
+
+        state_query.acquire().await?;
+        let tip = state_query.chain_point().await?;
+        let height = state_query.chain_block_no().await?;
+        state_query.release().await?;
+        info!("Node tip at {:?}, block height {:?}", tip, height);
+
+        let intersection = chain_sync.find_intersect(&points).await?;
+        channel
+            .send(Incoming::Result(Box::new(intersection)))
+            .await?;
+
+        loop {
+            match chain_sync.request_next().await? {
+                Ok(result) => channel.send(Incoming::Result(Box::new(result))).await?,
+                Err(event) => {
+                    channel.send(Incoming::Event(event.clone())).await?;
+                    if self.policy == ErrorPolicy::Stop {
+                        return Err(eyre!("Chain-sync stopped: {}", event));
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for NodeClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.socket.display())
+    }
+}
+
+impl ChainSource for NodeClient {
+    fn run(
+        &self,
+        points: Vec<PointOrOrigin>,
+        chain: Sender<Incoming>,
+        _mempool: Sender<MempoolSnapshot>,
+    ) -> BoxFuture<'static, Result<()>> {
+        let client = self.clone();
+        async move { client.sync(points, chain).await }.boxed()
+    }
+}
@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
+
+use color_eyre::eyre::{eyre, Result};
+use minicbor::Decoder;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::unix::OwnedWriteHalf;
+use tokio::net::UnixStream;
+use tokio::sync::{mpsc, Mutex};
+
+pub const HANDSHAKE: u16 = 0;
+pub const CHAIN_SYNC: u16 = 5;
+pub const STATE_QUERY: u16 = 7;
+
+const MAX_SEGMENT: usize = 0xffff;
+const RESPONDER: u16 = 0x8000;
+
+/// One mini-protocol over the multiplexed connection. Messages may span
+/// several segments, so they are buffered until a whole CBOR item is read.
+pub struct Channel {
+    protocol: u16,
+    writer: Arc<Mutex<OwnedWriteHalf>>,
+    start: Instant,
+    rx: mpsc::UnboundedReceiver<Vec<u8>>,
+    buffer: Vec<u8>,
+}
+
+/// Connects to the node socket and demultiplexes incoming segments to one
+/// `Channel` per mini-protocol in `protocols`.
+pub async fn connect(path: &Path, protocols: &[u16]) -> Result<HashMap<u16, Channel>> {
+    let stream = UnixStream::connect(path).await?;
+    let (mut read, write) = stream.into_split();
+    let writer = Arc::new(Mutex::new(write));
+    let start = Instant::now();
+
+    let mut senders = HashMap::new();
+    let mut channels = HashMap::new();
+    for &protocol in protocols {
+        let (tx, rx) = mpsc::unbounded_channel();
+        senders.insert(protocol, tx);
+        channels.insert(
+            protocol,
+            Channel {
+                protocol,
+                writer: writer.clone(),
+                start,
+                rx,
+                buffer: Vec::new(),
+            },
+        );
+    }
+
+    tokio::spawn(async move {
+        let mut header = [0u8; 8];
+        while read.read_exact(&mut header).await.is_ok() {
+            let protocol = u16::from_be_bytes([header[4], header[5]]) & !RESPONDER;
+            let len = u16::from_be_bytes([header[6], header[7]]) as usize;
+            let mut payload = vec![0u8; len];
+            if read.read_exact(&mut payload).await.is_err() {
+                break;
+            }
+            if let Some(tx) = senders.get(&protocol) {
+                let _ = tx.send(payload);
+            }
+        }
+    });
+
+    Ok(channels)
+}
+
+impl Channel {
+    pub async fn send(&self, msg: &[u8]) -> Result<()> {
+        let mut writer = self.writer.lock().await;
+        for chunk in msg.chunks(MAX_SEGMENT) {
+            let timestamp = self.start.elapsed().as_micros() as u32;
+            let mut segment = Vec::with_capacity(8 + chunk.len());
+            segment.extend_from_slice(&timestamp.to_be_bytes());
+            segment.extend_from_slice(&self.protocol.to_be_bytes());
+            segment.extend_from_slice(&(chunk.len() as u16).to_be_bytes());
+            segment.extend_from_slice(chunk);
+            writer.write_all(&segment).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn recv(&mut self) -> Result<Vec<u8>> {
+        loop {
+            if !self.buffer.is_empty() {
+                let mut d = Decoder::new(&self.buffer);
+                match d.skip() {
+                    Ok(()) => {
+                        let end = d.position();
+                        return Ok(self.buffer.drain(..end).collect());
+                    }
+                    Err(e) if e.is_end_of_input() => (),
+                    Err(e) => return Err(e.into()),
+                }
+            }
+
+            match self.rx.recv().await {
+                Some(segment) => self.buffer.extend(segment),
+                None => return Err(eyre!("Node connection closed.")),
+            }
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use color_eyre::eyre::{eyre, Result};
+use minicbor::{Decoder, Encoder};
+
+use crate::data::PointOrOrigin;
+use crate::n2c::chainsync::decode_point;
+use crate::n2c::mux::Channel;
+
+/// Client side of the local state query mini-protocol.
+pub struct StateQuery {
+    channel: Channel,
+}
+
+impl StateQuery {
+    pub fn new(channel: Channel) -> Self {
+        Self { channel }
+    }
+
+    async fn call(&mut self, msg: Vec<u8>) -> Result<Vec<u8>> {
+        self.channel.send(&msg).await?;
+        self.channel.recv().await
+    }
+
+    /// Acquires the ledger state at the node's current tip.
+    pub async fn acquire(&mut self) -> Result<()> {
+        let mut e = Encoder::new(Vec::new());
+        e.array(1)?.u8(8)?;
+        let msg = self.call(e.into_writer()).await?;
+
+        let mut d = Decoder::new(&msg);
+        d.array()?;
+        match d.u8()? {
+            1 => Ok(()),
+            2 => Err(eyre!("Cannot acquire ledger state (failure {}).", d.u8()?)),
+            n => Err(eyre!("Unexpected state query message {}.", n)),
+        }
+    }
+
+    pub async fn release(&mut self) -> Result<()> {
+        let mut e = Encoder::new(Vec::new());
+        e.array(1)?.u8(5)?;
+        self.channel.send(&e.into_writer()).await
+    }
+
+    /// Runs a query on the acquired state and returns the raw CBOR result.
+    pub async fn query(&mut self, query: &[u8]) -> Result<Vec<u8>> {
+        let mut msg = Vec::new();
+        Encoder::new(&mut msg).array(2)?.u8(3)?;
+        msg.extend_from_slice(query);
+        let reply = self.call(msg).await?;
+
+        let mut d = Decoder::new(&reply);
+        d.array()?;
+        match d.u8()? {
+            4 => Ok(reply[d.position()..].to_vec()),
+            n => Err(eyre!("Unexpected state query message {}.", n)),
+        }
+    }
+
+    pub async fn chain_point(&mut self) -> Result<PointOrOrigin> {
+        let mut e = Encoder::new(Vec::new());
+        e.array(1)?.u8(3)?;
+        let result = self.query(&e.into_writer()).await?;
+        decode_point(&mut Decoder::new(&result))
+    }
+
+    pub async fn chain_block_no(&mut self) -> Result<Option<u64>> {
+        let mut e = Encoder::new(Vec::new());
+        e.array(1)?.u8(2)?;
+        let result = self.query(&e.into_writer()).await?;
+
+        let mut d = Decoder::new(&result);
+        d.array()?;
+        match d.u8()? {
+            1 => Ok(Some(d.u64()?)),
+            _ => Ok(None),
+        }
+    }
+}
@@ -0,0 +1,382 @@
+//! Webhooks notified of payments to watched addresses, blocks minted by
+//! tracked pools and deep rollbacks, read from a file such as
+//!
+//! ```toml
+//! [[webhook]]
+//! url = "http://127.0.0.1:8080/cardano"
+//! addresses = ["addr1..."]
+//! pools = ["pool1..."]
+//! rollback_depth = 3
+//! retries = 5
+//! ```
+//!
+//! Notifications are POSTed as JSON, in order, to each webhook. Those about
+//! a block which is then rolled back are followed by a `retraction` of the
+//! block. A rollback too deep for the chain to apply retracts every block
+//! notified and is notified to the webhooks with a `rollback_depth`.
+//!
+//! Up to `QUEUE` notifications wait for each webhook, the next ones being
+//! dropped with a warning rather than holding the others back. When events
+//! are missed, the blocks notified and since rolled back are retracted
+//! once caught up with the chain, but those added meanwhile are not
+//! notified.
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use color_eyre::eyre::{eyre, Result};
+use hyper::client::HttpConnector;
+use hyper::{header, Body, Client, Request, Uri};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{mpsc, Mutex};
+use tokio_stream::StreamExt;
+use tracing::{debug, error, warn};
+
+use crate::chain::index::Location;
+use crate::chain::{Chain, ChainEvent};
+use crate::data::{Block, Output, Point, PointOrOrigin};
+use crate::query::Found;
+use crate::synchronization::{Lagged, Watched};
+
+/// Notifications waiting for each webhook, before the next ones are
+/// dropped.
+pub const QUEUE: usize = 1024;
+
+fn default_retries() -> u32 {
+    3
+}
+
+fn default_backoff_ms() -> u64 {
+    1000
+}
+
+/// An endpoint and what it is notified of.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Webhook {
+    pub url: String,
+    /// Addresses whose payments are notified.
+    #[serde(default)]
+    pub addresses: Vec<String>,
+    /// Pools whose blocks are notified.
+    #[serde(default)]
+    pub pools: Vec<String>,
+    /// Notifies the rollbacks reverting more blocks than this.
+    pub rollback_depth: Option<usize>,
+    /// Attempts after the first one before a notification is dropped.
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+    /// Delay before the first retry, doubled after each one.
+    #[serde(default = "default_backoff_ms")]
+    pub backoff_ms: u64,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Rules {
+    #[serde(default, rename = "webhook")]
+    pub webhooks: Vec<Webhook>,
+}
+
+impl Rules {
+    pub fn read(path: &Path) -> Result<Self> {
+        let text =
+            fs::read_to_string(path).map_err(|e| eyre!("Cannot read {}: {}", path.display(), e))?;
+        let rules: Self =
+            toml::from_str(&text).map_err(|e| eyre!("Invalid {}: {}", path.display(), e))?;
+        for webhook in &rules.webhooks {
+            webhook.uri()?;
+        }
+        Ok(rules)
+    }
+}
+
+impl Webhook {
+    /// The URL, which must be plain HTTP.
+    pub fn uri(&self) -> Result<Uri> {
+        let uri: Uri = self
+            .url
+            .parse()
+            .map_err(|e| eyre!("Invalid webhook '{}': {}", self.url, e))?;
+        match uri.scheme_str() {
+            Some("http") => Ok(uri),
+            _ => Err(eyre!("Invalid webhook '{}': expected http://", self.url)),
+        }
+    }
+
+    /// What the webhook is notified of `block`.
+    pub fn notifications(&self, block: &Block) -> Vec<Notification> {
+        let mut notifications = Vec::new();
+        if let Some(pool) = block.issuer() {
+            if self.pools.contains(&pool) {
+                notifications.push(Notification::BlockMinted {
+                    pool,
+                    hash: block.hash(),
+                    height: block.height(),
+                    slot: block.slot(),
+                });
+            }
+        }
+        notifications.extend(
+            block
+                .outputs()
+                .into_iter()
+                .filter(|output| self.addresses.contains(&output.address))
+                .map(|output| {
+                    Notification::Payment(Found {
+                        block: block.hash(),
+                        height: block.height(),
+                        item: output,
+                    })
+                }),
+        );
+        notifications
+    }
+}
+
+/// What the webhooks are sent.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Notification {
+    Payment(Found<Output>),
+    BlockMinted {
+        pool: String,
+        hash: String,
+        height: u64,
+        slot: u64,
+    },
+    /// The chain went back to the block `hash`, reverting the blocks listed.
+    Rollback {
+        hash: String,
+        height: u64,
+        depth: usize,
+        reverted: Vec<String>,
+    },
+    /// The block of previous notifications was rolled back.
+    Retraction {
+        block: String,
+        height: u64,
+    },
+}
+
+/// Applies the rules to the engine events, remembering the height of the
+/// blocks which can still be rolled back and which webhooks were notified
+/// of them.
+#[derive(Debug)]
+pub struct Notifier {
+    webhooks: Vec<Webhook>,
+    notified: HashMap<String, (u64, Vec<usize>)>,
+}
+
+impl Notifier {
+    pub fn new(rules: Rules) -> Self {
+        Self {
+            webhooks: rules.webhooks,
+            notified: HashMap::new(),
+        }
+    }
+
+    /// The notifications due after `event`, with the index of their webhook.
+    pub fn notify(&mut self, event: &ChainEvent) -> Vec<(usize, Notification)> {
+        let mut due = Vec::new();
+        match event {
+            ChainEvent::Collection(blocks) => {
+                for block in blocks {
+                    for (i, webhook) in self.webhooks.iter().enumerate() {
+                        let notifications = webhook.notifications(block);
+                        if notifications.is_empty() {
+                            continue;
+                        }
+                        self.notified
+                            .entry(block.hash())
+                            .or_insert_with(|| (block.height(), Vec::new()))
+                            .1
+                            .push(i);
+                        due.extend(notifications.into_iter().map(|n| (i, n)));
+                    }
+                }
+            }
+            ChainEvent::RevertFork(reverted) => {
+                due.extend(self.retract(reverted.iter().map(Block::hash).collect()));
+                if let Some(first) = reverted.first() {
+                    for (i, webhook) in self.webhooks.iter().enumerate() {
+                        if webhook.rollback_depth.map_or(false, |n| reverted.len() > n) {
+                            let rollback = Notification::Rollback {
+                                hash: first.prev_hash(),
+                                height: first.height().saturating_sub(1),
+                                depth: reverted.len(),
+                                reverted: reverted.iter().map(Block::hash).collect(),
+                            };
+                            due.push((i, rollback));
+                        }
+                    }
+                }
+            }
+            ChainEvent::Finalized(blocks) => {
+                for block in blocks {
+                    self.notified.remove(&block.hash());
+                }
+            }
+            _ => (),
+        }
+        due
+    }
+
+    /// Retractions of the notified blocks among `hashes`, the last one
+    /// first.
+    fn retract(&mut self, hashes: Vec<String>) -> Vec<(usize, Notification)> {
+        let mut retracted: Vec<(String, u64, Vec<usize>)> = hashes
+            .into_iter()
+            .filter_map(|hash| {
+                let (height, webhooks) = self.notified.remove(&hash)?;
+                Some((hash, height, webhooks))
+            })
+            .collect();
+        retracted.sort_by(|a, b| b.1.cmp(&a.1));
+        let mut due = Vec::new();
+        for (block, height, webhooks) in retracted {
+            for i in webhooks {
+                let retraction = Notification::Retraction {
+                    block: block.clone(),
+                    height,
+                };
+                due.push((i, retraction));
+            }
+        }
+        due
+    }
+
+    /// Catches up with `chain` after missing events: the notified blocks no
+    /// longer stored were rolled back and are retracted, and those which
+    /// became final are forgotten.
+    pub fn reconcile(&mut self, chain: &Chain) -> Vec<(usize, Notification)> {
+        let mut gone = Vec::new();
+        self.notified.retain(|hash, _| match chain.locate(hash) {
+            None => {
+                gone.push(hash.clone());
+                true
+            }
+            Some(Location::Immutable { .. }) => false,
+            Some(Location::Volatile(_)) => true,
+        });
+        self.retract(gone)
+    }
+
+    /// The notifications due after a rollback to `point`, past the blocks
+    /// `chain` can roll back: every notified block is retracted, and the
+    /// webhooks notified of rollbacks are sent the stored blocks after the
+    /// point, or after the last one before its slot when it is not stored.
+    /// Those are left out when the stored blocks cannot be read.
+    pub fn too_deep(&mut self, point: &PointOrOrigin, chain: &Chain) -> Vec<(usize, Notification)> {
+        let mut due = self.retract(self.notified.keys().cloned().collect());
+        match self.rollbacks(point, chain) {
+            Ok(rollbacks) => due.extend(rollbacks),
+            Err(e) => error!("Cannot read the blocks rolled back to {}: {}", point, e),
+        }
+        due
+    }
+
+    fn rollbacks(
+        &self,
+        point: &PointOrOrigin,
+        chain: &Chain,
+    ) -> Result<Vec<(usize, Notification)>> {
+        let (hash, kept) = match point {
+            PointOrOrigin::Point(Point { slot, hash }) => match chain.get_block_by_hash(hash)? {
+                Some(block) => (hash.clone(), Some(block)),
+                None => (hash.clone(), chain.get_block_before_slot(slot + 1)?),
+            },
+            PointOrOrigin::Origin(_) => ("origin".to_string(), None),
+        };
+        let height = kept.as_ref().map_or(0, Block::height);
+        let reverted = chain.hashes_from(kept.map_or(0, |block| block.height() + 1));
+        let mut due = Vec::new();
+        for (i, webhook) in self.webhooks.iter().enumerate() {
+            if webhook.rollback_depth.is_some() {
+                let rollback = Notification::Rollback {
+                    hash: hash.clone(),
+                    height,
+                    depth: reverted.len(),
+                    reverted: reverted.clone(),
+                };
+                due.push((i, rollback));
+            }
+        }
+        Ok(due)
+    }
+}
+
+async fn post(client: &Client<HttpConnector>, uri: &Uri, body: &str) -> Result<()> {
+    let req = Request::post(uri.clone())
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))?;
+    let status = client.request(req).await?.status();
+    if !status.is_success() {
+        return Err(eyre!("answered {}", status));
+    }
+    Ok(())
+}
+
+/// Sends the notifications of `queue` to `webhook` one after the other,
+/// retrying each with a growing delay before dropping it.
+async fn deliver(webhook: Webhook, uri: Uri, mut queue: mpsc::Receiver<Notification>) {
+    let client = Client::new();
+    while let Some(notification) = queue.recv().await {
+        let body = match serde_json::to_string(&notification) {
+            Ok(body) => body,
+            Err(e) => {
+                warn!("Cannot encode a notification: {}", e);
+                continue;
+            }
+        };
+        let mut delay = Duration::from_millis(webhook.backoff_ms);
+        for attempt in 0..=webhook.retries {
+            match post(&client, &uri, &body).await {
+                Ok(()) => break,
+                Err(e) if attempt < webhook.retries => {
+                    debug!("Retrying the notification to {}: {}", uri, e);
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                }
+                Err(e) => warn!("Dropped a notification to {}: {}", uri, e),
+            }
+        }
+    }
+}
+
+/// Notifies the webhooks of `rules` of `events` until the engine stops,
+/// looking `chain` up when the events do not tell enough.
+pub async fn run(rules: Rules, mut events: Watched, chain: Arc<Mutex<Chain>>) -> Result<()> {
+    let mut queues = Vec::with_capacity(rules.webhooks.len());
+    for webhook in &rules.webhooks {
+        let (queue, queued) = mpsc::channel(QUEUE);
+        tokio::spawn(deliver(webhook.clone(), webhook.uri()?, queued));
+        queues.push(queue);
+    }
+    let mut notifier = Notifier::new(rules);
+    while let Some(event) = events.next().await {
+        let due = match event {
+            Ok(ChainEvent::RollbackTooDeep(point)) => {
+                notifier.too_deep(&point, &*chain.lock().await)
+            }
+            Ok(event) => notifier.notify(&event),
+            Err(Lagged(missed)) => {
+                warn!("The webhooks missed {} events", missed);
+                notifier.reconcile(&*chain.lock().await)
+            }
+        };
+        for (i, notification) in due {
+            // Closed once the webhook stops, with the engine.
+            if let Err(TrySendError::Full(_)) = queues[i].try_send(notification) {
+                warn!(
+                    "Dropped a notification to {}, {} are waiting",
+                    notifier.webhooks[i].url, QUEUE
+                );
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,230 @@
+//! Lookups in the stored chunks, without syncing.
+use std::io::Write;
+use std::ops::RangeBounds;
+
+use color_eyre::eyre::Result;
+use serde::Serialize;
+
+use crate::chain::Chain;
+use crate::data::shelley::Certificate;
+use crate::data::{Block, Minted, Output};
+use crate::export;
+
+/// Most blocks read for a page or a lookup, so that looking for something
+/// rare holds the chain for a bounded time. A page can then be empty, with
+/// the cursor to carry on from.
+pub const SCAN_BLOCKS: usize = 1000;
+
+/// Something found in a block, with that block.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Found<T> {
+    pub block: String,
+    pub height: u64,
+    #[serde(flatten)]
+    pub item: T,
+}
+
+/// An output paid to an address.
+pub type Payment = Found<Output>;
+
+/// A certificate, with the transaction it belongs to.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Certified {
+    pub tx_id: String,
+    pub certificate: Certificate,
+}
+
+/// Results of a scan, with the cursor of the next page when there is one.
+#[derive(Serialize, Debug)]
+pub struct Page<T> {
+    pub data: Vec<T>,
+    pub next: Option<String>,
+}
+
+/// Where a lookup of `SCAN_BLOCKS` blocks at most ended.
+#[derive(Debug)]
+pub enum Lookup<T> {
+    Found(T),
+    /// Not found yet, to be carried on from this height.
+    Next(u64),
+    Missing,
+}
+
+/// Collects what `items` finds in the blocks from height `from`, until
+/// `limit` is reached or `SCAN_BLOCKS` were read. The items of a block are
+/// never split across pages, so the cursor of the next page is the height
+/// of the first block left out.
+pub fn scan<T>(
+    chain: &Chain,
+    from: u64,
+    limit: usize,
+    mut items: impl FnMut(&Block) -> Vec<T>,
+) -> Result<Page<Found<T>>> {
+    let mut data = Vec::new();
+    for (read, block) in chain.blocks_from(from, SCAN_BLOCKS + 1).enumerate() {
+        let block = block?;
+        if read == SCAN_BLOCKS {
+            return Ok(Page {
+                data,
+                next: Some(block.height().to_string()),
+            });
+        }
+        let found = items(&block);
+        if found.is_empty() {
+            continue;
+        }
+        if !data.is_empty() && data.len() + found.len() > limit {
+            return Ok(Page {
+                data,
+                next: Some(block.height().to_string()),
+            });
+        }
+        data.extend(found.into_iter().map(|item| Found {
+            block: block.hash(),
+            height: block.height(),
+            item,
+        }));
+    }
+    Ok(Page { data, next: None })
+}
+
+/// The first stored transaction with `id`, with its block.
+pub fn tx(chain: &Chain, id: &str) -> Result<Option<(Block, serde_json::Value)>> {
+    let mut from = 0;
+    loop {
+        match tx_from(chain, id, from)? {
+            Lookup::Found(found) => return Ok(Some(found)),
+            Lookup::Next(next) => from = next,
+            Lookup::Missing => return Ok(None),
+        }
+    }
+}
+
+/// The first transaction with `id` in the blocks from height `from`.
+pub fn tx_from(chain: &Chain, id: &str, from: u64) -> Result<Lookup<(Block, serde_json::Value)>> {
+    for (read, block) in chain.blocks_from(from, SCAN_BLOCKS + 1).enumerate() {
+        let block = block?;
+        if read == SCAN_BLOCKS {
+            return Ok(Lookup::Next(block.height()));
+        }
+        if let Some(tx) = block.tx(id) {
+            return Ok(Lookup::Found((block, tx)));
+        }
+    }
+    Ok(Lookup::Missing)
+}
+
+/// The stored outputs paid to `address`, in chain order.
+pub fn address(chain: &Chain, address: &str) -> Result<Vec<Payment>> {
+    let mut found = Vec::new();
+    let mut from = 0;
+    loop {
+        let page = payments(chain, address, from, usize::MAX)?;
+        found.extend(page.data);
+        match page.next {
+            Some(next) => from = next.parse()?,
+            None => return Ok(found),
+        }
+    }
+}
+
+pub fn payments(chain: &Chain, address: &str, from: u64, limit: usize) -> Result<Page<Payment>> {
+    scan(chain, from, limit, |block| {
+        block
+            .outputs()
+            .into_iter()
+            .filter(|output| output.address == address)
+            .collect()
+    })
+}
+
+/// Registrations and retirements of the pool `id`, and delegations to it.
+pub fn pool(chain: &Chain, id: &str, from: u64, limit: usize) -> Result<Page<Found<Certified>>> {
+    scan(chain, from, limit, |block| {
+        block
+            .certificates()
+            .into_iter()
+            .filter(|(_, certificate)| certificate.pool() == Some(id))
+            .map(|(tx_id, certificate)| Certified { tx_id, certificate })
+            .collect()
+    })
+}
+
+/// Mints and burns of `asset`, given as `policy.name` or a policy id for
+/// all of its assets.
+pub fn asset(chain: &Chain, asset: &str, from: u64, limit: usize) -> Result<Page<Found<Minted>>> {
+    scan(chain, from, limit, |block| {
+        block
+            .mints()
+            .into_iter()
+            .filter(|minted| minted.asset == asset || minted.policy() == asset)
+            .collect()
+    })
+}
+
+/// Blocks and transactions of `epoch`. A page counts the transactions of
+/// `SCAN_BLOCKS` of its blocks at most, the next one carrying on from
+/// `next`.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Epoch {
+    pub epoch: u64,
+    pub first_slot: u64,
+    pub blocks: usize,
+    pub txs: usize,
+    pub first_block: Option<String>,
+    pub last_block: Option<String>,
+    pub next: Option<String>,
+}
+
+/// The whole summary of `epoch`, adding up the transactions of its pages.
+pub fn epoch(chain: &Chain, epoch: u64) -> Result<Epoch> {
+    let mut summary = epoch_page(chain, epoch, 0)?;
+    while let Some(next) = summary.next.take() {
+        let page = epoch_page(chain, epoch, next.parse()?)?;
+        summary.txs += page.txs;
+        summary.next = page.next;
+    }
+    Ok(summary)
+}
+
+/// The summary of `epoch`, counting the transactions of its blocks from
+/// height `from`. Its blocks are counted from their heights in the index.
+pub fn epoch_page(chain: &Chain, epoch: u64, from: u64) -> Result<Epoch> {
+    let mut summary = Epoch {
+        epoch,
+        first_slot: chain.layout.first_slot(epoch),
+        blocks: 0,
+        txs: 0,
+        first_block: None,
+        last_block: None,
+        next: None,
+    };
+    let (first, last) = match chain.epoch_heights(epoch..=epoch) {
+        Some(heights) => heights,
+        None => return Ok(summary),
+    };
+    summary.blocks = (last - first + 1) as usize;
+    summary.first_block = chain.get_block_by_height(first)?.map(|b| b.hash());
+    summary.last_block = chain.get_block_by_height(last)?.map(|b| b.hash());
+    let blocks = chain.blocks_from(from.max(first), SCAN_BLOCKS + 1);
+    for (read, block) in blocks.enumerate() {
+        let block = block?;
+        if block.height() > last {
+            break;
+        }
+        if read == SCAN_BLOCKS {
+            summary.next = Some(block.height().to_string());
+            break;
+        }
+        summary.txs += block.tx_ids().len();
+    }
+    Ok(summary)
+}
+
+/// Writes the stored blocks within `heights` as JSON lines, returning how
+/// many were.
+pub fn export<R: RangeBounds<u64>, W: Write>(chain: &Chain, heights: R, out: W) -> Result<usize> {
+    export::blocks(chain.blocks_by_height(heights), out)
+}
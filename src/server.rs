@@ -0,0 +1,45 @@
+//! HTTP servers bound to an address, such as the API and the metrics.
+use std::convert::Infallible;
+use std::future::Future;
+use std::net::SocketAddr;
+
+use color_eyre::eyre::Result;
+use hyper::server::conn::AddrIncoming;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response, Server};
+
+/// An address bound, whose requests are answered by `serve`.
+#[derive(Debug)]
+pub struct Listener(AddrIncoming);
+
+impl Listener {
+    pub fn bind(addr: &SocketAddr) -> Result<Self> {
+        Ok(Self(AddrIncoming::bind(addr)?))
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.0.local_addr()
+    }
+
+    /// Answers the requests with `handle` until `shutdown` resolves.
+    pub async fn serve<H, F>(self, handle: H, shutdown: impl Future<Output = ()>) -> Result<()>
+    where
+        H: Fn(Request<Body>) -> F + Clone + Send + 'static,
+        F: Future<Output = Response<Body>> + Send + 'static,
+    {
+        let make = make_service_fn(move |_| {
+            let handle = handle.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req| {
+                    let response = handle(req);
+                    async move { Ok::<_, Infallible>(response.await) }
+                }))
+            }
+        });
+        Server::builder(self.0)
+            .serve(make)
+            .with_graceful_shutdown(shutdown)
+            .await?;
+        Ok(())
+    }
+}
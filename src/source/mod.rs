@@ -0,0 +1,32 @@
+//! Where the `Engine` gets the chain from: a live connection, a recorded
+//! file or a script.
+use std::fmt;
+
+use color_eyre::eyre::Result;
+use futures::future::BoxFuture;
+use tokio::sync::mpsc::Sender;
+
+use crate::data::mempool::MempoolSnapshot;
+use crate::data::PointOrOrigin;
+use crate::ws::Incoming;
+
+pub mod recording;
+pub mod replay;
+pub mod scripted;
+
+pub use recording::Recording;
+pub use replay::Replay;
+pub use scripted::Scripted;
+
+/// A producer of chain-sync results. Starting from the intersection with
+/// `points`, it reports roll-forwards and roll-backwards to `chain` (and
+/// mempool snapshots to `mempool` when it can see one) until it is
+/// exhausted or fails.
+pub trait ChainSource: fmt::Debug + fmt::Display + Send + Sync {
+    fn run(
+        &self,
+        points: Vec<PointOrOrigin>,
+        chain: Sender<Incoming>,
+        mempool: Sender<MempoolSnapshot>,
+    ) -> BoxFuture<'static, Result<()>>;
+}
@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, ErrorKind};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use color_eyre::eyre::Result;
+use flate2::read::MultiGzDecoder;
+use futures::future::{BoxFuture, FutureExt};
+use tokio::sync::mpsc::Sender;
+use tokio::time::{sleep_until, Instant};
+
+use crate::data::mempool::MempoolSnapshot;
+use crate::data::protocol::{DecodeError, OgmiosVersion};
+use crate::data::{PointOrOrigin, RResult};
+use crate::source::ChainSource;
+use crate::ws::record::{Direction, Frame};
+use crate::ws::{ConnectionEvent, Incoming};
+
+const CHAIN_SYNC_METHODS: [&str; 4] = [
+    "FindIntersect",
+    "RequestNext",
+    "findIntersection",
+    "nextBlock",
+];
+
+/// Replays the chain-sync replies of a session recorded with
+/// `ws::Ogmios::record`, paced as they were received. The recording is
+/// played from its own intersection, whatever points are asked for.
+#[derive(Debug, Clone)]
+pub struct Recording {
+    path: PathBuf,
+    /// Playback speed: 2.0 replays twice as fast, 0.0 as fast as possible.
+    speed: f64,
+}
+
+impl Recording {
+    pub fn new(path: PathBuf, speed: f64) -> Self {
+        Recording { path, speed }
+    }
+
+    /// Reads every frame of the recording. A recording whose writer did
+    /// not finish is read up to its last complete frame.
+    pub fn load(&self) -> Result<Vec<Frame>> {
+        let reader = BufReader::new(MultiGzDecoder::new(File::open(&self.path)?));
+        let mut frames = Vec::new();
+        for line in reader.lines() {
+            match line {
+                Ok(line) => match serde_json::from_str(&line) {
+                    Ok(frame) => frames.push(frame),
+                    Err(e) if e.is_eof() => break,
+                    Err(e) => return Err(e.into()),
+                },
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(frames)
+    }
+
+    fn delay(&self, at: u64) -> Option<Duration> {
+        if self.speed > 0.0 {
+            Some(Duration::from_secs_f64(at as f64 / 1000.0 / self.speed))
+        } else {
+            None
+        }
+    }
+}
+
+/// Turns a received frame into what `ws::Connection` would have reported
+/// for it, given the methods of the requests sent so far.
+fn incoming(text: &str, methods: &HashMap<u64, String>) -> Option<Incoming> {
+    let version = if OgmiosVersion::is_json_rpc(text) {
+        OgmiosVersion::V6
+    } else {
+        OgmiosVersion::V5
+    };
+    let method = version.correlation(text).and_then(|id| methods.get(&id));
+    match method {
+        Some(m) if !CHAIN_SYNC_METHODS.contains(&m.as_str()) => None,
+        Some(_) => Some(match version.decode::<RResult>(text) {
+            Ok(result) => Incoming::Result(Box::new(result)),
+            Err(DecodeError::Fault(fault)) => Incoming::Event(ConnectionEvent::Fault(fault)),
+            Err(DecodeError::Malformed(error)) => Incoming::Event(ConnectionEvent::Decode {
+                error,
+                payload: text.to_string(),
+            }),
+        }),
+        None => Some(Incoming::Event(
+            match version.decode::<serde_json::Value>(text) {
+                Err(DecodeError::Fault(fault)) => ConnectionEvent::Fault(fault),
+                _ => ConnectionEvent::Unexpected(format!("reply {}", text)),
+            },
+        )),
+    }
+}
+
+/// Extracts the id and method of a request sent to Ogmios.
+fn request(text: &str) -> Option<(u64, String)> {
+    let value = serde_json::from_str::<serde_json::Value>(text).ok()?;
+    let id = value.get("mirror").or_else(|| value.get("id"))?.as_u64()?;
+    let method = value.get("methodname").or_else(|| value.get("method"))?;
+    Some((id, method.as_str()?.to_string()))
+}
+
+impl fmt::Display for Recording {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}x", self.path.display(), self.speed)
+    }
+}
+
+impl ChainSource for Recording {
+    fn run(
+        &self,
+        _points: Vec<PointOrOrigin>,
+        chain: Sender<Incoming>,
+        _mempool: Sender<MempoolSnapshot>,
+    ) -> BoxFuture<'static, Result<()>> {
+        let recording = self.clone();
+        async move {
+            let loading = recording.clone();
+            let frames = tokio::task::spawn_blocking(move || loading.load()).await??;
+
+            let start = Instant::now();
+            let mut methods = HashMap::new();
+            for frame in frames {
+                match frame.direction {
+                    Direction::Out => methods.extend(request(&frame.text)),
+                    Direction::In => {
+                        if let Some(incoming) = incoming(&frame.text, &methods) {
+                            if let Some(delay) = recording.delay(frame.at) {
+                                sleep_until(start + delay).await;
+                            }
+                            chain.send(incoming).await?;
+                        }
+                    }
+                }
+            }
+            Ok(())
+        }
+        .boxed()
+    }
+}
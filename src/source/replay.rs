@@ -0,0 +1,55 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use color_eyre::eyre::Result;
+use futures::future::{BoxFuture, FutureExt};
+use tokio::sync::mpsc::Sender;
+
+use crate::data::mempool::MempoolSnapshot;
+use crate::data::{PointOrOrigin, RResult};
+use crate::source::{ChainSource, Scripted};
+use crate::ws::Incoming;
+
+/// Replays chain-sync results from a file holding one JSON `RResult` per
+/// line, in the same shape as Ogmios v5 results.
+#[derive(Debug, Clone)]
+pub struct Replay {
+    path: PathBuf,
+}
+
+impl Replay {
+    pub fn new(path: PathBuf) -> Self {
+        Replay { path }
+    }
+
+    pub async fn load(&self) -> Result<Vec<RResult>> {
+        let content = tokio::fs::read_to_string(&self.path).await?;
+        let mut results = Vec::new();
+        for line in content.lines().filter(|l| !l.trim().is_empty()) {
+            results.push(serde_json::from_str(line)?);
+        }
+        Ok(results)
+    }
+}
+
+impl fmt::Display for Replay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.path.display())
+    }
+}
+
+impl ChainSource for Replay {
+    fn run(
+        &self,
+        points: Vec<PointOrOrigin>,
+        chain: Sender<Incoming>,
+        mempool: Sender<MempoolSnapshot>,
+    ) -> BoxFuture<'static, Result<()>> {
+        let replay = self.clone();
+        async move {
+            let script = Scripted::new(replay.load().await?);
+            script.run(points, chain, mempool).await
+        }
+        .boxed()
+    }
+}
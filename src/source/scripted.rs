@@ -0,0 +1,96 @@
+use std::fmt;
+
+use color_eyre::eyre::Result;
+use futures::future::{BoxFuture, FutureExt};
+use tokio::sync::mpsc::Sender;
+
+use crate::data::mempool::MempoolSnapshot;
+use crate::data::{PointOrOrigin, RResult, Tip};
+use crate::source::ChainSource;
+use crate::ws::Incoming;
+
+/// In-memory source playing a fixed list of roll-forwards and
+/// roll-backwards, as if it were the node's chain.
+#[derive(Debug, Clone)]
+pub struct Scripted {
+    script: Vec<RResult>,
+}
+
+impl Scripted {
+    pub fn new(script: Vec<RResult>) -> Self {
+        Scripted { script }
+    }
+
+    /// Finds the first of `points` on the script, returning it with the
+    /// index of the entry following it.
+    fn intersect(&self, points: &[PointOrOrigin]) -> Option<(PointOrOrigin, usize)> {
+        points.iter().find_map(|point| match point {
+            PointOrOrigin::Origin(_) => Some((point.clone(), 0)),
+            PointOrOrigin::Point(p) => self
+                .script
+                .iter()
+                .position(|r| match r {
+                    RResult::RollForward { block, .. } => block.hash() == p.hash,
+                    _ => false,
+                })
+                .map(|i| (point.clone(), i + 1)),
+        })
+    }
+
+    fn tip(&self) -> Tip {
+        match self.script.last() {
+            Some(RResult::RollForward { tip, .. })
+            | Some(RResult::RollBackward { tip, .. })
+            | Some(RResult::IntersectionFound { tip, .. })
+            | Some(RResult::IntersectionNotFound { tip: Some(tip) }) => tip.clone(),
+            _ => Tip {
+                slot: 0,
+                hash: "".to_string(),
+                block_no: 0,
+            },
+        }
+    }
+}
+
+impl fmt::Display for Scripted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "script of {} results", self.script.len())
+    }
+}
+
+impl ChainSource for Scripted {
+    fn run(
+        &self,
+        points: Vec<PointOrOrigin>,
+        chain: Sender<Incoming>,
+        _mempool: Sender<MempoolSnapshot>,
+    ) -> BoxFuture<'static, Result<()>> {
+        let script = self.clone();
+        async move {
+            let tip = script.tip();
+            let (point, start) = match script.intersect(&points) {
+                Some(found) => found,
+                None => {
+                    let tip = Some(tip);
+                    chain
+                        .send(Incoming::Result(Box::new(RResult::IntersectionNotFound {
+                            tip,
+                        })))
+                        .await?;
+                    return Ok(());
+                }
+            };
+            chain
+                .send(Incoming::Result(Box::new(RResult::IntersectionFound {
+                    point,
+                    tip,
+                })))
+                .await?;
+            for result in script.script.into_iter().skip(start) {
+                chain.send(Incoming::Result(Box::new(result))).await?;
+            }
+            Ok(())
+        }
+        .boxed()
+    }
+}
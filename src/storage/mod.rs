@@ -1,7 +1,7 @@
 use mongodb::{options::ClientOptions, Client, Database};
 use color_eyre::eyre::{Result, eyre};
 use crate::data::{Args, PointOrOrigin, RResult, Request, Response, Block};
-use crate::cli::CLI;
+use crate::cli::StorageOptions;
 
 #[derive(Debug, Default)]
 pub struct Mongodb {
@@ -12,7 +12,7 @@ pub struct Mongodb {
 }
 
 impl Mongodb {
-    pub fn new(opt: &CLI) -> Self {
+    pub fn new(opt: &StorageOptions) -> Self {
         Self { address: opt.mongodb.clone().unwrap(), db_name: opt.db_name.clone().unwrap(), ..Self::default() }
     }
 
@@ -1,60 +1,344 @@
-use std::sync::Arc;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex as StdMutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
 
-use tokio::sync::mpsc;
+use color_eyre::eyre::{eyre, Result};
 use tokio::sync::Mutex;
-use tokio_stream::wrappers::ReceiverStream;
-use tokio_stream::StreamExt;
-use tokio_tungstenite::tungstenite::http::Uri;
+use tokio::sync::{broadcast, mpsc};
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use tokio_stream::wrappers::{BroadcastStream, ReceiverStream};
+use tokio_stream::{Stream, StreamExt};
+use tracing::{debug, error, info, warn};
 
-use crate::chain::{Chain, ChainEvent};
+use crate::chain::{Chain, ChainEvent, LinkPolicy, SyncProgress};
+use crate::data::mempool::MempoolSnapshot;
 use crate::data::{PointOrOrigin, RResult};
-use crate::ws::{self, Connection};
+use crate::mempool::Mempool;
+use crate::source::ChainSource;
+use crate::ws::{ConnectionEvent, Incoming};
+
+/// Events kept for each consumer slower than the engine. The engine waits
+/// for the consumers that `subscribe` once theirs are full, while those
+/// that `watch` miss the next events instead.
+pub const EVENTS_CAPACITY: usize = 4096;
+
+/// How often the memory held by the chain is reported while syncing at
+/// most. Once synchronized, it is after every block.
+pub const MEMORY_EVERY: Duration = Duration::from_secs(1);
+
+/// The events of an engine received by a consumer that must see them all,
+/// as the GUI and the headless sync do, the engine waiting for it when it
+/// falls behind.
+#[derive(Debug)]
+pub struct Events(ReceiverStream<ChainEvent>);
+
+impl Stream for Events {
+    type Item = ChainEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<ChainEvent>> {
+        Pin::new(&mut self.0).poll_next(cx)
+    }
+}
+
+/// Events missed by a consumer that fell behind the engine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Lagged(pub u64);
+
+/// The events of an engine received by a consumer that can miss some
+/// rather than hold the engine back, as the live API, the metrics and the
+/// webhooks do. It is told how many it missed, to resync or report them.
+#[derive(Debug)]
+pub struct Watched(BroadcastStream<ChainEvent>);
+
+impl Stream for Watched {
+    type Item = Result<ChainEvent, Lagged>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.0).poll_next(cx).map(|event| {
+            event.map(|event| {
+                event.map_err(|BroadcastStreamRecvError::Lagged(missed)| Lagged(missed))
+            })
+        })
+    }
+}
+
+/// The consumers of the events of an engine.
+#[derive(Debug, Clone)]
+struct Hub {
+    subscribers: Arc<StdMutex<Vec<mpsc::Sender<ChainEvent>>>>,
+    watchers: broadcast::Sender<ChainEvent>,
+}
+
+impl Hub {
+    fn new() -> Self {
+        Self {
+            subscribers: Arc::new(StdMutex::new(Vec::new())),
+            watchers: broadcast::channel(EVENTS_CAPACITY).0,
+        }
+    }
+
+    fn subscribe(&self) -> Events {
+        let (tx, rx) = mpsc::channel(EVENTS_CAPACITY);
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers.push(tx);
+        }
+        Events(ReceiverStream::new(rx))
+    }
+
+    fn watch(&self) -> Watched {
+        Watched(BroadcastStream::new(self.watchers.subscribe()))
+    }
+
+    /// Sends `event` to every consumer, waiting for the subscribers that
+    /// are behind.
+    async fn send(&self, event: ChainEvent) {
+        // Fails when nothing watches.
+        let _ = self.watchers.send(event.clone());
+        let subscribers = match self.subscribers.lock() {
+            Ok(subscribers) => subscribers.clone(),
+            Err(_) => return,
+        };
+        let mut closed = false;
+        for subscriber in &subscribers {
+            closed |= subscriber.send(event.clone()).await.is_err();
+        }
+        if closed {
+            if let Ok(mut subscribers) = self.subscribers.lock() {
+                subscribers.retain(|subscriber| !subscriber.is_closed());
+            }
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct Engine {
-    pub uri: Uri,
+    pub source: Arc<dyn ChainSource>,
     pub chain: Arc<Mutex<Chain>>,
-    pub connection: Arc<Connection>,
+    pub mempool: Arc<Mutex<Mempool>>,
+    pub points: Vec<PointOrOrigin>,
+    /// Starts from the tip of the source instead of `points`.
+    pub from_tip: bool,
+    incoming: mpsc::Sender<Incoming>,
+    snapshots: mpsc::Sender<MempoolSnapshot>,
+    events: Hub,
 }
 
 impl Engine {
-    pub fn new(uri: Uri) -> (Box<Self>, ReceiverStream<ChainEvent>) {
-        let chain = Arc::new(Mutex::new(Chain::new(2000)));
-        let (tx, rx) = mpsc::channel(2000);
-        let (tx_engine, rx_engine) = mpsc::channel(2000);
+    pub fn new(source: Arc<dyn ChainSource>, buffer: usize, chain: Chain) -> (Box<Self>, Events) {
+        let chain = Arc::new(Mutex::new(chain));
+        let mempool = Arc::new(Mutex::new(Mempool::new()));
+        let (tx, rx) = mpsc::channel(buffer);
+        let (tx_mempool, rx_mempool) = mpsc::channel(16);
+        let tx_engine = Hub::new();
+        let rx_engine = tx_engine.subscribe();
         let points = vec![PointOrOrigin::origin()];
-        let connection = ws::Connection::new(uri.clone(), points, tx);
-
         let cloned_chain = chain.clone();
+        let cloned_mempool = mempool.clone();
+        let cloned_tx_engine = tx_engine.clone();
+        let cloned_source = source.clone();
+        let cloned_snapshots = tx_mempool.clone();
         tokio::spawn(async move {
             let mut rs = ReceiverStream::new(rx);
-            while let Some(r) = rs.next().await {
-                match r {
-                    RResult::RollBackward { .. } | RResult::RollForward { .. } => {
-                        let mut c = cloned_chain.lock().await;
-                        let v = c.add(r);
-                        // dbg!(&v);
-                        let _ = tx_engine.send(v).await;
+            let mut reported: Option<Instant> = None;
+            while let Some(incoming) = rs.next().await {
+                let r = match incoming {
+                    Incoming::Result(r) => *r,
+                    Incoming::Throughput(t) => {
+                        debug!(
+                            "{:.1} blocks/s, pipeline depth {}",
+                            t.blocks_per_sec, t.depth
+                        );
+                        cloned_tx_engine.send(ChainEvent::Throughput(t)).await;
+                        continue;
+                    }
+                    Incoming::Event(e) => {
+                        if e.is_error() {
+                            warn!("{}", e);
+                        } else {
+                            debug!("{}", e);
+                        }
+                        cloned_tx_engine.send(ChainEvent::Connection(e)).await;
+                        continue;
+                    }
+                };
+                let included = match &r {
+                    RResult::RollForward { block, .. } => {
+                        cloned_mempool.lock().await.include(block)
+                    }
+                    _ => Vec::new(),
+                };
+                let mut c = cloned_chain.lock().await;
+                let events = c.add(r);
+                let broken = "Synchronization halted on a broken chain";
+                let reaction = events.iter().find_map(|e| match e {
+                    ChainEvent::LinkBroken(_) => Some((c.on_break, broken)),
+                    ChainEvent::RollbackTooDeep(_) => Some((LinkPolicy::Halt, broken)),
+                    ChainEvent::IntersectionNotFound(_) => Some((
+                        LinkPolicy::Halt,
+                        "No intersection found with any of the points to start from",
+                    )),
+                    _ => None,
+                });
+                let synchronized = events
+                    .iter()
+                    .any(|e| matches!(e, ChainEvent::Synchronizing(SyncProgress::Synchronized(_))));
+                let memory =
+                    if synchronized || reported.map_or(true, |at| at.elapsed() >= MEMORY_EVERY) {
+                        reported = Some(Instant::now());
+                        Some(ChainEvent::Memory {
+                            blocks: c.blocks_in_memory(),
+                            bytes: c.memory(),
+                        })
+                    } else {
+                        None
+                    };
+                let points = c.points();
+                drop(c);
+                for v in events {
+                    cloned_tx_engine.send(v).await;
+                }
+                for e in included {
+                    cloned_tx_engine.send(ChainEvent::Mempool(e)).await;
+                }
+                if let Some(memory) = memory {
+                    cloned_tx_engine.send(memory).await;
+                }
+
+                match reaction {
+                    Some((LinkPolicy::Reintersect, _)) if !points.is_empty() => {
+                        // Results still buffered from the old source are
+                        // dropped with its channel, which also stops it.
+                        warn!("Intersecting again from {} known blocks", points.len());
+                        cloned_tx_engine
+                            .send(ChainEvent::Reintersecting(points.len()))
+                            .await;
+                        let (tx, rx) = mpsc::channel(buffer);
+                        rs = ReceiverStream::new(rx);
+                        spawn_source(cloned_source.clone(), points, tx, cloned_snapshots.clone());
+                    }
+                    Some((LinkPolicy::Halt, reason)) | Some((LinkPolicy::Reintersect, reason)) => {
+                        error!("{}", reason);
+                        cloned_tx_engine
+                            .send(ChainEvent::Halted(reason.to_string()))
+                            .await;
+                        break;
                     }
                     _ => (),
                 }
             }
         });
 
+        let cloned_mempool = mempool.clone();
+        let cloned_tx_engine = tx_engine.clone();
+        tokio::spawn(async move {
+            let mut rs = ReceiverStream::new(rx_mempool);
+            while let Some(snapshot) = rs.next().await {
+                let events = cloned_mempool.lock().await.update(snapshot);
+                for e in events {
+                    cloned_tx_engine.send(ChainEvent::Mempool(e)).await;
+                }
+            }
+        });
+
         (
             Box::new(Self {
-                uri,
-                connection: Arc::new(connection),
+                source,
                 chain,
+                mempool,
+                points,
+                from_tip: false,
+                incoming: tx,
+                snapshots: tx_mempool,
+                events: tx_engine,
             }),
-            ReceiverStream::new(rx_engine),
+            rx_engine,
         )
     }
 
-    pub async fn start(&self) {
-        let connection = self.connection.clone();
-        tokio::spawn(async move {
-            let _ = connection.run().await;
-        });
+    /// Events from now on, for another consumer than the one given by
+    /// `new` that must see them all.
+    pub fn subscribe(&self) -> Events {
+        self.events.subscribe()
+    }
+
+    /// Events from now on, for a consumer that can miss some.
+    pub fn watch(&self) -> Watched {
+        self.events.watch()
+    }
+
+    /// Runs the source in the background, once its tip is known when
+    /// starting from it.
+    pub fn start(&self) -> impl Future<Output = ()> + Send + 'static {
+        let source = self.source.clone();
+        let points = self.points.clone();
+        let from_tip = self.from_tip;
+        let incoming = self.incoming.clone();
+        let snapshots = self.snapshots.clone();
+
+        async move {
+            let points = if from_tip {
+                match tip(source.clone()).await {
+                    Ok(tip) => vec![tip],
+                    Err(e) => {
+                        error!("Cannot find the tip of {}: {}", source, e);
+                        let event = ConnectionEvent::Transport(e.to_string());
+                        let _ = incoming.send(Incoming::Event(event)).await;
+                        return;
+                    }
+                }
+            } else {
+                points
+            };
+            let listed: Vec<String> = points.iter().map(PointOrOrigin::to_string).collect();
+            info!("Intersecting from {}", listed.join(", "));
+            spawn_source(source, points, incoming, snapshots)
+        }
+    }
+}
+
+/// The tip of the chain of `source`, as reported with its intersection
+/// with origin.
+async fn tip(source: Arc<dyn ChainSource>) -> Result<PointOrOrigin> {
+    let (incoming, mut results) = mpsc::channel(1);
+    // Kept open for the source not to stop on a closed mempool channel.
+    let (snapshots, _mempool) = mpsc::channel(1);
+    let run = tokio::spawn(source.run(vec![PointOrOrigin::origin()], incoming, snapshots));
+    while let Some(incoming) = results.recv().await {
+        let result = match incoming {
+            Incoming::Result(result) => *result,
+            _ => continue,
+        };
+        if let RResult::IntersectionFound { tip, .. } = result {
+            // Dropping `results` stops the source.
+            return Ok(if tip.hash.is_empty() {
+                PointOrOrigin::origin()
+            } else {
+                PointOrOrigin::point(tip.slot, tip.hash)
+            });
+        }
     }
+    match run.await? {
+        Err(e) => Err(e),
+        Ok(()) => Err(eyre!("The source stopped before reporting its tip.")),
+    }
+}
+
+/// Runs `source` from `points`, reporting why it stopped as a connection
+/// event.
+fn spawn_source(
+    source: Arc<dyn ChainSource>,
+    points: Vec<PointOrOrigin>,
+    incoming: mpsc::Sender<Incoming>,
+    snapshots: mpsc::Sender<MempoolSnapshot>,
+) {
+    let run = source.run(points, incoming.clone(), snapshots);
+    tokio::spawn(async move {
+        if let Err(e) = run.await {
+            error!("{} stopped: {}", source, e);
+            let event = ConnectionEvent::Transport(e.to_string());
+            let _ = incoming.send(Incoming::Event(event)).await;
+        }
+    });
 }
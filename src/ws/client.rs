@@ -0,0 +1,213 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use color_eyre::eyre::Result;
+use futures_util::{SinkExt, StreamExt};
+use tokio::sync::{mpsc, oneshot};
+use tokio_tungstenite::tungstenite::{http::Uri, Message};
+
+use crate::data::mempool::{AcquireResult, MempoolSize};
+use crate::data::protocol::{DecodeError, OgmiosVersion, Versioned};
+use crate::data::{Args, PointOrOrigin, RResult, StateQuery, SubmitResult};
+use crate::ws::record::{Direction, Recorder};
+use crate::ws::{connect, negotiate, ConnectionEvent};
+
+type Pending = Arc<Mutex<HashMap<u64, oneshot::Sender<Result<String, ConnectionEvent>>>>>;
+
+/// Typed Ogmios client multiplexing chain-sync, mempool, state queries and
+/// submissions over one websocket. Every request carries an id which the
+/// server reflects, and the reply is dispatched to the matching `Reply`.
+#[derive(Debug)]
+pub struct Client {
+    version: OgmiosVersion,
+    outgoing: mpsc::UnboundedSender<Message>,
+    pending: Pending,
+    next_id: AtomicU64,
+}
+
+/// Future resolving to the decoded reply of one request.
+pub struct Reply<T> {
+    rx: oneshot::Receiver<Result<String, ConnectionEvent>>,
+    version: OgmiosVersion,
+    _result: PhantomData<fn() -> T>,
+}
+
+impl<T: Versioned> Future for Reply<T> {
+    type Output = Result<T, ConnectionEvent>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let version = self.version;
+        Pin::new(&mut self.rx).poll(cx).map(|reply| match reply {
+            Ok(Ok(text)) => version.decode(&text).map_err(|e| match e {
+                DecodeError::Fault(fault) => ConnectionEvent::Fault(fault),
+                DecodeError::Malformed(error) => ConnectionEvent::Decode {
+                    error,
+                    payload: text,
+                },
+            }),
+            Ok(Err(event)) => Err(event),
+            Err(_) => Err(ConnectionEvent::Closed(None)),
+        })
+    }
+}
+
+impl Client {
+    /// Connects to the server, settling the protocol version if needed.
+    /// Messages which cannot be matched with a request are sent on the
+    /// returned channel. Text frames are also written to `recorder`.
+    pub async fn connect(
+        ws: &Uri,
+        version: OgmiosVersion,
+        recorder: Option<Recorder>,
+    ) -> Result<(Arc<Self>, mpsc::Receiver<ConnectionEvent>)> {
+        let mut version = version;
+        let mut stream = connect(ws, version).await?;
+        // The version is probed with a query, which leaves the chain-sync
+        // state of the connection untouched.
+        if version == OgmiosVersion::Auto {
+            negotiate(
+                &mut stream,
+                &mut version,
+                Args::Query(StateQuery::SystemStart),
+            )
+            .await?;
+        }
+
+        let (mut write, mut read) = stream.split();
+        let (outgoing, mut rx_outgoing) = mpsc::unbounded_channel::<Message>();
+        let (tx_events, rx_events) = mpsc::channel(100);
+        let pending: Pending = Arc::new(Mutex::new(HashMap::new()));
+
+        let cloned_recorder = recorder.clone();
+        tokio::spawn(async move {
+            while let Some(msg) = rx_outgoing.recv().await {
+                if let (Some(recorder), Message::Text(t)) = (&cloned_recorder, &msg) {
+                    recorder.record(Direction::Out, t);
+                }
+                if write.send(msg).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        let cloned_pending = pending.clone();
+        tokio::spawn(async move {
+            let closed = loop {
+                let event = match read.next().await {
+                    Some(Ok(Message::Text(t))) => {
+                        if let Some(recorder) = &recorder {
+                            recorder.record(Direction::In, &t);
+                        }
+                        let waiting = version
+                            .correlation(&t)
+                            .and_then(|id| cloned_pending.lock().unwrap().remove(&id));
+                        match waiting {
+                            Some(tx) => {
+                                let _ = tx.send(Ok(t));
+                                continue;
+                            }
+                            None => match version.decode::<serde_json::Value>(&t) {
+                                Err(DecodeError::Fault(fault)) => ConnectionEvent::Fault(fault),
+                                _ => ConnectionEvent::Unexpected(format!("reply {}", t)),
+                            },
+                        }
+                    }
+                    Some(Ok(Message::Binary(b))) => {
+                        ConnectionEvent::Unexpected(format!("binary frame of {} bytes", b.len()))
+                    }
+                    Some(Ok(Message::Ping(_))) => ConnectionEvent::Ping,
+                    Some(Ok(Message::Pong(_))) => ConnectionEvent::Pong,
+                    Some(Ok(Message::Close(frame))) => {
+                        break ConnectionEvent::Closed(
+                            frame.map(|f| format!("{} {}", f.code, f.reason)),
+                        )
+                    }
+                    Some(Err(e)) => break ConnectionEvent::Transport(e.to_string()),
+                    None => break ConnectionEvent::Closed(None),
+                };
+                let _ = tx_events.send(event).await;
+            };
+
+            for (_, tx) in cloned_pending.lock().unwrap().drain() {
+                let _ = tx.send(Err(closed.clone()));
+            }
+            let _ = tx_events.send(closed).await;
+        });
+
+        Ok((
+            Arc::new(Self {
+                version,
+                outgoing,
+                pending,
+                next_id: AtomicU64::new(1),
+            }),
+            rx_events,
+        ))
+    }
+
+    /// Sends a request without waiting for its reply, so requests can be
+    /// pipelined by holding on to several `Reply`s.
+    pub fn send<T: Versioned>(&self, args: Args) -> Reply<T> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(id, tx);
+
+        let msg = Message::Text(self.version.encode(args, id));
+        if self.outgoing.send(msg).is_err() {
+            if let Some(tx) = self.pending.lock().unwrap().remove(&id) {
+                let _ = tx.send(Err(ConnectionEvent::Closed(None)));
+            }
+        }
+
+        Reply {
+            rx,
+            version: self.version,
+            _result: PhantomData,
+        }
+    }
+
+    pub fn find_intersect(&self, points: Vec<PointOrOrigin>) -> Reply<RResult> {
+        self.send(Args::FindIntersect(points))
+    }
+
+    pub fn request_next(&self) -> Reply<RResult> {
+        self.send(Args::RequestNext)
+    }
+
+    /// Blocks until a new mempool snapshot is acquired.
+    pub fn acquire_mempool(&self) -> Reply<AcquireResult> {
+        self.send(Args::AwaitAcquire)
+    }
+
+    pub fn next_tx(&self) -> Reply<Option<String>> {
+        self.send(Args::NextTx)
+    }
+
+    pub fn has_tx(&self, id: String) -> Reply<bool> {
+        self.send(Args::HasTx(id))
+    }
+
+    pub fn mempool_size(&self) -> Reply<MempoolSize> {
+        self.send(Args::SizeAndCapacity)
+    }
+
+    /// Runs a local state query. Results are returned as is, their schema
+    /// depending on the query and the protocol version.
+    pub fn query(&self, query: StateQuery) -> Reply<serde_json::Value> {
+        self.send(Args::Query(query))
+    }
+
+    pub fn current_epoch(&self) -> Reply<u64> {
+        self.send(Args::Query(StateQuery::CurrentEpoch))
+    }
+
+    /// Submits a serialised transaction, given as hex-encoded CBOR.
+    pub fn submit_tx(&self, cbor: String) -> Reply<SubmitResult> {
+        self.send(Args::SubmitTx(cbor))
+    }
+}
@@ -0,0 +1,424 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Instant;
+
+use color_eyre::eyre::{eyre, Report, Result};
+use futures::future::{BoxFuture, FutureExt};
+use futures_util::{SinkExt, StreamExt};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{
+    connect_async,
+    tungstenite::http::{self, Uri},
+    tungstenite::Message,
+    MaybeTlsStream, WebSocketStream,
+};
+
+use crate::data::mempool::{AcquireResult, MempoolSnapshot};
+use crate::data::protocol::{Fault, OgmiosVersion};
+use crate::data::{Args, PointOrOrigin, RResult};
+use crate::source::ChainSource;
+use tokio::sync::mpsc::{self, Sender};
+use tracing::warn;
+
+pub mod client;
+pub mod record;
+
+pub use client::Client;
+
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// What the chain-sync client does when it receives something other than a
+/// result: report it and carry on, or report it and stop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    Skip,
+    Stop,
+}
+
+impl Default for ErrorPolicy {
+    fn default() -> Self {
+        Self::Skip
+    }
+}
+
+impl FromStr for ErrorPolicy {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "skip" => Ok(Self::Skip),
+            "stop" => Ok(Self::Stop),
+            _ => Err(eyre!(
+                "Unknown error policy '{}', expected skip or stop.",
+                s
+            )),
+        }
+    }
+}
+
+/// Anything received on the socket which is not a chain-sync result.
+#[derive(Debug, Clone)]
+pub enum ConnectionEvent {
+    Fault(Fault),
+    Decode { error: String, payload: String },
+    Unexpected(String),
+    Ping,
+    Pong,
+    Closed(Option<String>),
+    Transport(String),
+}
+
+impl ConnectionEvent {
+    pub fn is_error(&self) -> bool {
+        !matches!(self, Self::Ping | Self::Pong)
+    }
+}
+
+impl fmt::Display for ConnectionEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fault(fault) => fault.fmt(f),
+            Self::Decode { error, payload } => {
+                write!(f, "Cannot decode message ({}): {}", error, payload)
+            }
+            Self::Unexpected(frame) => write!(f, "Unexpected {}", frame),
+            Self::Ping => write!(f, "Ping"),
+            Self::Pong => write!(f, "Pong"),
+            Self::Closed(Some(reason)) => write!(f, "Connection closed: {}", reason),
+            Self::Closed(None) => write!(f, "Connection closed"),
+            Self::Transport(e) => write!(f, "Websocket error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ConnectionEvent {}
+
+#[derive(Debug)]
+pub enum Incoming {
+    Result(Box<RResult>),
+    Event(ConnectionEvent),
+    Throughput(Throughput),
+}
+
+/// Chain-sync flow control: bounds on the number of `RequestNext` in flight
+/// and the size of the buffer between the connection and the `Engine`.
+#[derive(Debug, Clone, Copy)]
+pub struct Pipelining {
+    pub min_depth: usize,
+    pub max_depth: usize,
+    pub buffer: usize,
+}
+
+impl Default for Pipelining {
+    fn default() -> Self {
+        Self {
+            min_depth: 10,
+            max_depth: 1000,
+            buffer: 2000,
+        }
+    }
+}
+
+impl Pipelining {
+    /// Grows the depth while the buffer stays mostly empty and halves it when
+    /// the consumer falls behind, so a stalled consumer stops requesting
+    /// blocks instead of piling them up in memory.
+    pub fn adjust(&self, depth: usize, free: usize) -> usize {
+        if free < self.buffer / 4 {
+            (depth / 2).max(self.min_depth)
+        } else if free > self.buffer / 2 {
+            (depth + 1).min(self.max_depth)
+        } else {
+            depth
+        }
+    }
+}
+
+/// Chain-sync throughput, reported about once per second.
+#[derive(Debug, Clone)]
+pub struct Throughput {
+    pub blocks_per_sec: f64,
+    pub depth: usize,
+    pub in_flight: usize,
+    pub buffered: usize,
+}
+
+async fn connect(ws: &Uri, version: OgmiosVersion) -> Result<WsStream> {
+    let mut req = http::Request::builder().uri(ws.clone());
+    if let Some(subprotocol) = version.subprotocol() {
+        req = req.header("Sec-WebSocket-Protocol", subprotocol);
+    }
+
+    let (ws_stream, _) = connect_async(req.body(()).unwrap()).await?;
+    Ok(ws_stream)
+}
+
+async fn next_text(stream: &mut WsStream) -> Result<String> {
+    loop {
+        match stream.next().await {
+            Some(Ok(Message::Text(t))) => return Ok(t),
+            Some(Ok(_)) => continue,
+            Some(Err(e)) => return Err(e.into()),
+            None => return Err(eyre!("Connection closed by the server.")),
+        }
+    }
+}
+
+/// Sends the first request of a session and returns its reply. When the
+/// version is `Auto`, the request goes out as v5 and is sent again as v6 if
+/// the server answers with a JSON-RPC envelope.
+async fn negotiate(
+    stream: &mut WsStream,
+    version: &mut OgmiosVersion,
+    args: Args,
+) -> Result<String> {
+    if *version != OgmiosVersion::Auto {
+        stream.send(Message::Text(version.encode(args, 0))).await?;
+        return next_text(stream).await;
+    }
+
+    stream
+        .send(Message::Text(OgmiosVersion::V5.encode(args.clone(), 0)))
+        .await?;
+    let reply = next_text(stream).await?;
+    if OgmiosVersion::is_json_rpc(&reply) {
+        *version = OgmiosVersion::V6;
+        stream.send(Message::Text(version.encode(args, 0))).await?;
+        next_text(stream).await
+    } else {
+        *version = OgmiosVersion::V5;
+        Ok(reply)
+    }
+}
+
+#[derive(Debug)]
+pub struct Connection {
+    policy: ErrorPolicy,
+    pipelining: Pipelining,
+    points: Vec<PointOrOrigin>,
+    channel: Sender<Incoming>,
+}
+
+impl Connection {
+    pub fn new(
+        policy: ErrorPolicy,
+        pipelining: Pipelining,
+        points: Vec<PointOrOrigin>,
+        channel: Sender<Incoming>,
+    ) -> Self {
+        // let mut points = vec![PointOrOrigin::origin()];
+        // if let Some((slot, block)) = opt.slot.zip(opt.block.as_ref()) {
+        //     points.push(PointOrOrigin::point(slot, block.clone()));
+        // };
+        // points.reverse();
+
+        Connection {
+            policy,
+            pipelining,
+            points,
+            channel,
+        }
+    }
+
+    /// Runs chain-sync on `client`, keeping requests pipelined and
+    /// forwarding results and connection events in order.
+    pub async fn run(
+        &self,
+        client: Arc<Client>,
+        mut events: mpsc::Receiver<ConnectionEvent>,
+    ) -> Result<()> {
+        let mut depth = self.pipelining.min_depth.max(1);
+        let mut inflight = VecDeque::new();
+        inflight.push_back(client.find_intersect(self.points.clone()));
+        while inflight.len() < depth {
+            inflight.push_back(client.request_next());
+            // info!("Send initial request");
+        }
+
+        let mut window = Instant::now();
+        let mut received = 0;
+        let mut intersecting = true;
+
+        loop {
+            if inflight.is_empty() {
+                inflight.push_back(client.request_next());
+            }
+            let reply = inflight
+                .front_mut()
+                .expect("chain-sync pipeline is never empty");
+            // Replies received before an event are forwarded first, so that
+            // no block is lost when the server closes the connection.
+            let event = tokio::select! {
+                biased;
+                result = reply => {
+                    inflight.pop_front();
+                    // v6 servers answer a failed intersection with an error.
+                    let result = match result {
+                        Err(ConnectionEvent::Fault(fault)) if intersecting && fault.code == "1000" => {
+                            Ok(RResult::IntersectionNotFound { tip: None })
+                        }
+                        result => result,
+                    };
+                    intersecting = false;
+                    match result {
+                        Ok(result) => {
+                            // Waits for room in the buffer when the consumer
+                            // is slow, which also holds back new requests.
+                            self.channel.send(Incoming::Result(Box::new(result))).await?;
+                            received += 1;
+
+                            let free = self.channel.capacity();
+                            depth = self.pipelining.adjust(depth, free);
+                            while inflight.len() < depth {
+                                inflight.push_back(client.request_next());
+                            }
+
+                            let elapsed = window.elapsed();
+                            if elapsed.as_secs() >= 1 {
+                                let throughput = Throughput {
+                                    blocks_per_sec: received as f64 / elapsed.as_secs_f64(),
+                                    depth,
+                                    in_flight: inflight.len(),
+                                    buffered: self.pipelining.buffer.saturating_sub(free),
+                                };
+                                self.channel.send(Incoming::Throughput(throughput)).await?;
+                                window = Instant::now();
+                                received = 0;
+                            }
+                            continue;
+                        }
+                        Err(event) => {
+                            // Faults and undecodable messages still answer
+                            // one of the pipelined requests, so the pipeline
+                            // is refilled unless we are about to stop.
+                            if self.policy == ErrorPolicy::Skip {
+                                inflight.push_back(client.request_next());
+                            }
+                            event
+                        }
+                    }
+                }
+                Some(event) = events.recv() => event,
+            };
+
+            self.channel.send(Incoming::Event(event.clone())).await?;
+            match event {
+                ConnectionEvent::Closed(_) => return Ok(()),
+                ConnectionEvent::Transport(_) => return Err(event.into()),
+                _ if event.is_error() && self.policy == ErrorPolicy::Stop => {
+                    return Err(eyre!("Chain-sync stopped: {}", event));
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+/// Local tx monitor client: repeatedly acquires a mempool snapshot and
+/// reports its content to the `Engine`.
+#[derive(Debug)]
+pub struct TxMonitor {
+    channel: Sender<MempoolSnapshot>,
+}
+
+impl TxMonitor {
+    pub fn new(channel: Sender<MempoolSnapshot>) -> Self {
+        TxMonitor { channel }
+    }
+
+    /// Runs the local tx monitor on `client`. The mini-protocol is strictly
+    /// sequential, so every call waits for its answer.
+    pub async fn run(&self, client: Arc<Client>) -> Result<()> {
+        loop {
+            let slot = match client.acquire_mempool().await? {
+                AcquireResult::AwaitAcquired { slot } => slot,
+            };
+
+            let mut txs = Vec::new();
+            while let Some(id) = client.next_tx().await? {
+                txs.push(id);
+            }
+            let size = client.mempool_size().await?;
+
+            self.channel
+                .send(MempoolSnapshot { slot, txs, size })
+                .await?;
+        }
+    }
+}
+
+/// Ogmios as a `ChainSource`: chain-sync and the tx monitor share one
+/// websocket.
+#[derive(Debug, Clone)]
+pub struct Ogmios {
+    uri: Uri,
+    version: OgmiosVersion,
+    policy: ErrorPolicy,
+    pipelining: Pipelining,
+    record: Option<PathBuf>,
+}
+
+impl Ogmios {
+    pub fn new(
+        uri: Uri,
+        version: OgmiosVersion,
+        policy: ErrorPolicy,
+        pipelining: Pipelining,
+    ) -> Self {
+        Ogmios {
+            uri,
+            version,
+            policy,
+            pipelining,
+            record: None,
+        }
+    }
+
+    /// Records the session to `path`, to be replayed later with a
+    /// `source::Recording`.
+    pub fn record(mut self, path: Option<PathBuf>) -> Self {
+        self.record = path;
+        self
+    }
+}
+
+impl fmt::Display for Ogmios {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.uri)
+    }
+}
+
+impl ChainSource for Ogmios {
+    fn run(
+        &self,
+        points: Vec<PointOrOrigin>,
+        chain: Sender<Incoming>,
+        mempool: Sender<MempoolSnapshot>,
+    ) -> BoxFuture<'static, Result<()>> {
+        let ogmios = self.clone();
+        async move {
+            let recorder = match &ogmios.record {
+                Some(path) => Some(record::Recorder::create(path)?),
+                None => None,
+            };
+            let (client, events) = Client::connect(&ogmios.uri, ogmios.version, recorder).await?;
+
+            let tx_monitor = TxMonitor::new(mempool);
+            let cloned_client = client.clone();
+            let monitor = tokio::spawn(async move {
+                if let Err(e) = tx_monitor.run(cloned_client).await {
+                    warn!("Tx monitor stopped: {}", e);
+                }
+            });
+
+            let connection = Connection::new(ogmios.policy, ogmios.pipelining, points, chain);
+            let result = connection.run(client, events).await;
+            monitor.abort();
+            result
+        }
+        .boxed()
+    }
+}
@@ -0,0 +1,74 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Instant;
+
+use color_eyre::eyre::Result;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    In,
+    Out,
+}
+
+/// One websocket text frame, as written to a recording.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Frame {
+    /// Milliseconds since the recording started.
+    pub at: u64,
+    pub direction: Direction,
+    pub text: String,
+}
+
+/// Writes the frames exchanged with Ogmios to a gzipped JSON-lines file.
+#[derive(Debug, Clone)]
+pub struct Recorder {
+    start: Instant,
+    tx: mpsc::Sender<Frame>,
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = File::create(path)?;
+        let (tx, rx) = mpsc::channel::<Frame>();
+
+        thread::spawn(move || {
+            let mut file = file;
+            for frame in rx {
+                // Every frame is a gzip member of its own, so the recording
+                // stays readable while it is written or after a crash.
+                let mut line = serde_json::to_vec(&frame).unwrap_or_default();
+                line.push(b'\n');
+                let mut member = GzEncoder::new(Vec::new(), Compression::default());
+                let written = member
+                    .write_all(&line)
+                    .and_then(|_| member.finish())
+                    .and_then(|bytes| file.write_all(&bytes));
+                if let Err(e) = written {
+                    warn!("Recording stopped: {}", e);
+                    return;
+                }
+            }
+        });
+
+        Ok(Recorder {
+            start: Instant::now(),
+            tx,
+        })
+    }
+
+    pub fn record(&self, direction: Direction, text: &str) {
+        let _ = self.tx.send(Frame {
+            at: self.start.elapsed().as_millis() as u64,
+            direction,
+            text: text.to_string(),
+        });
+    }
+}
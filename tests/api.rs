@@ -0,0 +1,344 @@
+mod support;
+
+use std::fs;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+
+use futures::{SinkExt, StreamExt};
+use mini_explorer::api::live::Update;
+use mini_explorer::api::{Api, State};
+use mini_explorer::chain::{Chain, ChainEvent};
+use mini_explorer::data::{Block, EpochLayout};
+use mini_explorer::query::SCAN_BLOCKS;
+use serde_json::Value;
+use tokio::sync::Mutex;
+use tokio_tungstenite::tungstenite::Message;
+
+use support::{http, http_get, stored, stored_chunk};
+
+/// Serves the blocks stored in `dir` on a free port.
+fn serve(dir: &Path) -> SocketAddr {
+    serve_state(State::new(
+        Arc::new(Mutex::new(Chain::open(dir).unwrap())),
+        EpochLayout::MAINNET,
+    ))
+}
+
+fn serve_state(state: State) -> SocketAddr {
+    let api = Api::bind(&"127.0.0.1:0".parse().unwrap(), state).unwrap();
+    let addr = api.local_addr();
+    tokio::spawn(api.run(futures::future::pending()));
+    addr
+}
+
+/// Status and JSON body answered to `GET path`.
+async fn get(addr: SocketAddr, path: &str) -> (u16, Value) {
+    let (status, body) = http_get(addr, path).await;
+    (status, serde_json::from_str(&body).unwrap())
+}
+
+/// JSON body answered to the GraphQL `query`.
+async fn graphql(addr: SocketAddr, query: &str) -> Value {
+    let body = serde_json::json!({ "query": query }).to_string();
+    let request = format!(
+        "POST /graphql HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        addr,
+        body.len(),
+        body
+    );
+    serde_json::from_str(&http(addr, request).await.1).unwrap()
+}
+
+#[tokio::test]
+async fn serves_the_tip_and_blocks() {
+    let (dir, written) = stored("api-blocks");
+    let addr = serve(&dir);
+
+    let (status, tip) = get(addr, "/tip").await;
+    let height = written[1].height();
+    let (_, by_height) = get(addr, &format!("/blocks/height/{}", height)).await;
+    let (_, by_hash) = get(addr, &format!("/blocks/{}", written[2].hash())).await;
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(status, 200);
+    assert_eq!(tip["height"], written[2].height());
+    let by_height: Block = serde_json::from_value(by_height).unwrap();
+    let by_hash: Block = serde_json::from_value(by_hash).unwrap();
+    assert_eq!(by_height.hash(), written[1].hash());
+    assert_eq!(by_hash.hash(), written[2].hash());
+}
+
+#[tokio::test]
+async fn pages_through_the_blocks() {
+    let (dir, written) = stored("api-pages");
+    let addr = serve(&dir);
+
+    let first = format!("/blocks?cursor={}&limit=2", written[0].height());
+    let (_, first) = get(addr, &first).await;
+    let next = first["next"].as_str().unwrap().to_string();
+    let (_, last) = get(addr, &format!("/blocks?cursor={}&limit=2", next)).await;
+    let (status, invalid) = get(addr, "/blocks?limit=0").await;
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(first["data"].as_array().unwrap().len(), 2);
+    assert_eq!(next, written[2].height().to_string());
+    assert_eq!(last["data"].as_array().unwrap().len(), 1);
+    assert!(last["next"].is_null());
+    assert_eq!(status, 400);
+    assert_eq!(invalid["error"]["status"], 400);
+}
+
+#[tokio::test]
+async fn pages_through_an_epoch() {
+    let (dir, written) = stored_chunk("api-epoch", "mary", 251, SCAN_BLOCKS + 2);
+    let addr = serve(&dir);
+
+    let (_, first) = get(addr, "/epochs/251").await;
+    let next = first["next"].as_str().unwrap().to_string();
+    let (_, last) = get(addr, &format!("/epochs/251?cursor={}", next)).await;
+    fs::remove_dir_all(&dir).unwrap();
+
+    let txs = written[0].tx_ids().len();
+    assert_eq!(first["blocks"], written.len());
+    assert_eq!(first["lastBlock"], written[SCAN_BLOCKS + 1].hash());
+    assert_eq!(next, written[SCAN_BLOCKS].height().to_string());
+    assert_eq!(first["txs"], SCAN_BLOCKS * txs);
+    assert_eq!(last["txs"], 2 * txs);
+    assert!(last["next"].is_null());
+}
+
+#[tokio::test]
+async fn answers_missing_resources_with_an_error_body() {
+    let (dir, written) = stored("api-missing");
+    let addr = serve(&dir);
+
+    let missing = format!("/blocks/height/{}", written[2].height() + 1);
+    let (status, body) = get(addr, &missing).await;
+    let (unknown, _) = get(addr, "/unknown").await;
+    let (invalid, _) = get(addr, "/blocks/slot/tomorrow").await;
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(status, 404);
+    assert_eq!(body["error"]["status"], 404);
+    assert!(body["error"]["message"].is_string());
+    assert_eq!(unknown, 404);
+    assert_eq!(invalid, 400);
+}
+
+#[tokio::test]
+async fn joins_blocks_transactions_and_addresses() {
+    let (dir, written) = stored("api-graphql");
+    let addr = serve(&dir);
+
+    let output = written[0].outputs()[0].clone();
+    let query = format!(
+        r#"{{
+            block(height: {}) {{
+                hash
+                transactions(address: "{}") {{
+                    id
+                    outputs {{ address {{ outputs(first: 2) {{ data {{ height }} next }} }} }}
+                }}
+            }}
+        }}"#,
+        written[0].height(),
+        output.address
+    );
+    let joined = graphql(addr, &query).await;
+    let first = format!(
+        "{{ blocks(after: \"{}\", first: 2) {{ data {{ hash }} next }} }}",
+        written[0].height()
+    );
+    let first = graphql(addr, &first).await;
+    let complex = graphql(
+        addr,
+        "{ blocks(first: 100) { data { transactions { outputs { address { \
+         outputs(first: 100) { data { txId } } } } } } } }",
+    )
+    .await;
+    fs::remove_dir_all(&dir).unwrap();
+
+    let block = &joined["data"]["block"];
+    assert_eq!(block["hash"], written[0].hash());
+    let txs = block["transactions"].as_array().unwrap();
+    assert!(txs.iter().any(|tx| tx["id"] == output.tx_id.as_str()));
+    let payments = &txs[0]["outputs"][0]["address"]["outputs"];
+    assert_eq!(payments["data"][0]["height"], written[0].height());
+    assert_eq!(first["data"]["blocks"]["data"].as_array().unwrap().len(), 2);
+    assert_eq!(
+        first["data"]["blocks"]["next"],
+        written[2].height().to_string()
+    );
+    assert!(complex["data"].is_null());
+    assert!(complex["errors"][0]["message"]
+        .as_str()
+        .unwrap()
+        .contains("complex"));
+}
+
+#[tokio::test]
+async fn pages_through_the_blocks_of_an_epoch() {
+    let (dir, written) = stored("api-graphql-epoch");
+    let addr = serve(&dir);
+
+    let epoch = written[0].epoch(&EpochLayout::MAINNET);
+    let query = format!(
+        "{{ blocks(epoch: {}, after: \"{}\", first: 1) {{ data {{ hash epoch }} next }} \
+         epoch(number: {}) {{ blockCount txCount next }} }}",
+        epoch,
+        written[1].height(),
+        epoch
+    );
+    let page = graphql(addr, &query).await;
+    fs::remove_dir_all(&dir).unwrap();
+
+    let blocks = &page["data"]["blocks"];
+    assert_eq!(blocks["data"][0]["hash"], written[1].hash());
+    assert_eq!(blocks["data"][0]["epoch"], epoch);
+    assert_eq!(blocks["next"], written[2].height().to_string());
+    let summary = &page["data"]["epoch"];
+    assert_eq!(summary["blockCount"], written.len());
+    assert_eq!(summary["txCount"], 3 * written[0].tx_ids().len());
+    assert!(summary["next"].is_null());
+}
+
+#[tokio::test]
+async fn costs_lookups_and_scans() {
+    let (dir, written) = stored("api-cost");
+    let addr = serve(&dir);
+
+    let lookups = (0..5)
+        .map(|i| format!("t{}: transaction(id: \"{}\") {{ id }}", i, i))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let lookups = graphql(addr, &format!("{{ {} }}", lookups)).await;
+    let epochs = (0..20)
+        .map(|i| format!("e{}: epoch(number: {}) {{ txCount }}", i, i))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let epochs = graphql(addr, &format!("{{ {} }}", epochs)).await;
+    let scans = graphql(
+        addr,
+        "{ blocks(first: 20) { data { transactions { outputs { address { \
+         outputs(first: 1) { next } } } } } } }",
+    )
+    .await;
+    // Bounded before it is multiplied, then refused when the page is read.
+    let huge = format!("{{ blocks(first: {}) {{ data {{ hash }} }} }}", i32::MAX);
+    let huge = graphql(addr, &huge).await;
+    let tx = &written[0].tx_ids()[0];
+    let found = format!("{{ transaction(id: \"{}\") {{ id }} }}", tx);
+    let found = graphql(addr, &found).await;
+    fs::remove_dir_all(&dir).unwrap();
+
+    for rejected in [&lookups, &epochs, &scans] {
+        assert!(rejected["errors"][0]["message"]
+            .as_str()
+            .unwrap()
+            .contains("complex"));
+    }
+    assert!(huge["errors"][0]["message"]
+        .as_str()
+        .unwrap()
+        .contains("first must be between"));
+    assert_eq!(found["data"]["transaction"]["id"], tx.as_str());
+}
+
+type Live =
+    tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>;
+
+/// The next JSON message pushed on `live`.
+async fn pushed(live: &mut Live) -> Value {
+    loop {
+        match live.next().await.unwrap().unwrap() {
+            Message::Text(text) => return serde_json::from_str(&text).unwrap(),
+            Message::Close(_) => panic!("closed"),
+            _ => (),
+        }
+    }
+}
+
+#[tokio::test]
+async fn pushes_filtered_events_resuming_from_a_point() {
+    let (dir, written) = stored("api-live");
+    let state = State::new(
+        Arc::new(Mutex::new(Chain::open(&dir).unwrap())),
+        EpochLayout::MAINNET,
+    );
+    let addr = serve_state(state.clone());
+
+    let output = written[1].outputs()[0].clone();
+    let url = format!(
+        "ws://{}/live?addresses={}&from={}.{}",
+        addr,
+        output.address,
+        written[0].slot(),
+        written[0].hash()
+    );
+    let (mut live, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+    // Only blocks, with no payments, once the subscription is emptied.
+    live.send(Message::Text("{}".to_string())).await.unwrap();
+    let reverted = ChainEvent::RevertFork(vec![written[2].clone()]);
+    let added = ChainEvent::Collection(vec![written[2].clone()]);
+    // Sent once the session has resumed and applied the new subscription.
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    for update in Update::of(&reverted).into_iter().chain(Update::of(&added)) {
+        state.live.send(update).unwrap();
+    }
+
+    let mut replayed = Vec::new();
+    let mut payments = 0;
+    let rollback = loop {
+        let message = pushed(&mut live).await;
+        match message["type"].as_str().unwrap() {
+            "block" => replayed.push(message["hash"].clone()),
+            "payment" => {
+                assert_eq!(message["address"], output.address.as_str());
+                payments += 1;
+            }
+            _ => break message,
+        }
+    };
+    let block = pushed(&mut live).await;
+    let after = tokio::time::timeout(std::time::Duration::from_millis(100), pushed(&mut live));
+
+    let unknown = format!("ws://{}/live?from=1.unknown", addr);
+    let (mut unknown, _) = tokio_tungstenite::connect_async(unknown).await.unwrap();
+    let error = pushed(&mut unknown).await;
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(replayed, vec![written[1].hash(), written[2].hash()]);
+    assert!(payments > 0);
+    assert!(after.await.is_err());
+    assert_eq!(rollback["type"], "rollback");
+    assert_eq!(rollback["hash"], written[1].hash());
+    assert_eq!(block["type"], "block");
+    assert_eq!(block["hash"], written[2].hash());
+    assert_eq!(error["type"], "error");
+}
+
+#[tokio::test]
+async fn resumes_from_a_rolled_back_point() {
+    let (dir, written) = stored("api-forked");
+    let state = State::new(
+        Arc::new(Mutex::new(Chain::open(&dir).unwrap())),
+        EpochLayout::MAINNET,
+    );
+    let addr = serve_state(state);
+
+    // A block of another fork, at the slot of the last one stored.
+    let url = format!("ws://{}/live?from={}.forked", addr, written[2].slot());
+    let (mut live, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+    let rollback = pushed(&mut live).await;
+    let block = pushed(&mut live).await;
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(rollback["type"], "rollback");
+    assert_eq!(rollback["hash"], written[1].hash());
+    assert_eq!(rollback["height"], written[1].height());
+    assert_eq!(rollback["reverted"][0], "forked");
+    assert_eq!(block["type"], "block");
+    assert_eq!(block["hash"], written[2].hash());
+}
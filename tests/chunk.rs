@@ -0,0 +1,154 @@
+mod support;
+
+use std::fs;
+use std::path::PathBuf;
+
+use mini_explorer::chain::format::{self, VERSION};
+use mini_explorer::chain::{Chain, Chunk};
+use mini_explorer::data::Block;
+
+use support::{blocks, fixture, ERAS};
+
+fn path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("mini-explorer-{}-{}.bin", name, std::process::id()))
+}
+
+/// A data directory holding `blocks` as a chunk of epoch 251 written
+/// before the format.
+fn legacy(name: &str, blocks: &[Block]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("mini-explorer-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let bin = bincode::serialize(&Some(blocks.to_vec())).unwrap();
+    fs::write(Chunk::path(&dir, 251), bin).unwrap();
+    dir
+}
+
+fn decode(blocks: &[serde_json::Value]) -> Vec<Block> {
+    blocks
+        .iter()
+        .map(|b| serde_json::from_value(b.clone()).unwrap())
+        .collect()
+}
+
+fn hashes(blocks: &[Block]) -> Vec<String> {
+    blocks.iter().map(Block::hash).collect()
+}
+
+#[test]
+fn reads_back_blocks_of_every_era() {
+    let path = path("eras");
+    let written = decode(&ERAS.iter().map(|era| fixture(era)).collect::<Vec<_>>());
+    format::write(&path, &written).unwrap();
+
+    let read = format::read(&path).unwrap();
+    let third = format::read_block(&path, 2).unwrap();
+    let past = format::read_block(&path, written.len()).unwrap();
+    let version = format::version(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(hashes(&read), hashes(&written));
+    assert_eq!(third.map(|b| b.hash()), Some(written[2].hash()));
+    assert!(past.is_none());
+    assert_eq!(version, VERSION);
+}
+
+#[test]
+fn detects_corrupted_blocks() {
+    let path = path("corrupted");
+    let written = decode(&blocks(&fixture("alonzo"), 0, 3));
+    format::write(&path, &written).unwrap();
+    let mut bytes = fs::read(&path).unwrap();
+    let last = bytes.len() - 1;
+    bytes[last] ^= 0xff;
+    fs::write(&path, bytes).unwrap();
+
+    let read = format::read(&path);
+    let first = format::read_block(&path, 0);
+    let corrupted = format::read_block(&path, 2);
+    fs::remove_file(&path).unwrap();
+
+    assert!(read.is_err());
+    assert_eq!(first.unwrap().map(|b| b.hash()), Some(written[0].hash()));
+    assert!(corrupted.unwrap_err().to_string().contains("block 2"));
+}
+
+#[test]
+fn migrates_chunks_written_before_the_format() {
+    let written = decode(&blocks(&fixture("byron"), 0, 3));
+    let dir = legacy("legacy", &written);
+    let path = Chunk::path(&dir, 251);
+
+    let legacy = (
+        format::version(&path).unwrap(),
+        format::read(&path).unwrap(),
+    );
+    let migrated = format::migrate(&dir, 251).unwrap();
+    let again = format::migrate(&dir, 251).unwrap();
+    let current = (
+        format::version(&path).unwrap(),
+        format::read(&path).unwrap(),
+    );
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(legacy.0, 0);
+    assert_eq!(hashes(&legacy.1), hashes(&written));
+    assert!(migrated && !again);
+    assert_eq!(current.0, VERSION);
+    assert_eq!(hashes(&current.1), hashes(&written));
+}
+
+#[test]
+fn looks_up_blocks_of_a_migrated_chunk() {
+    let written = decode(&blocks(&fixture("shelley"), 0, 3));
+    let dir = legacy("migrated", &written);
+
+    let migrated = format::migrate_all(&dir).unwrap();
+    let chain = Chain::open(&dir).unwrap();
+    let found = chain.get_block_by_hash(&written[1].hash()).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(migrated, 1);
+    assert_eq!(found.map(|b| b.hash()), Some(written[1].hash()));
+}
+
+#[test]
+fn rejects_a_block_count_past_the_end_of_the_file() {
+    let path = path("count");
+    let written = decode(&blocks(&fixture("alonzo"), 0, 1));
+    format::write(&path, &written).unwrap();
+    let mut bytes = fs::read(&path).unwrap();
+    bytes[6..10].copy_from_slice(&u32::MAX.to_le_bytes());
+    fs::write(&path, bytes).unwrap();
+
+    let read = format::read(&path);
+    fs::remove_file(&path).unwrap();
+
+    assert!(read
+        .unwrap_err()
+        .to_string()
+        .contains("longer than the file"));
+}
+
+#[test]
+fn migrates_legacy_chunks_of_every_era_without_metadata() {
+    for era in ERAS {
+        let written = decode(&blocks(&fixture(era), 0, 2));
+        let dir = legacy(&format!("legacy-{}", era), &written);
+
+        let migrated = format::migrate(&dir, 251);
+        let read = format::read(&Chunk::path(&dir, 251));
+        fs::remove_dir_all(&dir).unwrap();
+
+        // Only the Mary and Alonzo fixtures hold transaction metadata.
+        if era == "mary" || era == "alonzo" {
+            assert!(
+                migrated.unwrap_err().to_string().contains("metadata"),
+                "{}",
+                era
+            );
+        } else {
+            assert!(migrated.unwrap(), "{}", era);
+            assert_eq!(hashes(&read.unwrap()), hashes(&written), "{}", era);
+        }
+    }
+}
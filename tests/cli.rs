@@ -0,0 +1,25 @@
+use mini_explorer::cli::CLI;
+use structopt::StructOpt;
+
+fn parse(flags: &[&str]) -> Result<CLI, String> {
+    let args = ["mini-explorer", "sync", "--ws", "ws://local:1337"];
+    let cli = CLI::from_iter_safe(args.iter().chain(flags)).map_err(|e| e.message)?;
+    cli.validate().map_err(|e| e.to_string())?;
+    Ok(cli)
+}
+
+#[test]
+fn rejects_an_empty_buffer() {
+    let e = parse(&["--buffer", "0"]).unwrap_err();
+    assert!(e.contains("--buffer"), "{}", e);
+    assert!(e.contains("at least 1"), "{}", e);
+    assert!(parse(&["--buffer", "1"]).is_ok());
+}
+
+#[test]
+fn rejects_a_pipeline_max_below_the_min() {
+    let e = parse(&["--pipeline-min", "20", "--pipeline-max", "10"]).unwrap_err();
+    assert!(e.contains("--pipeline-max (10)"), "{}", e);
+    assert!(parse(&["--pipeline-min", "0"]).is_err());
+    assert!(parse(&["--pipeline-min", "10", "--pipeline-max", "10"]).is_ok());
+}
@@ -0,0 +1,134 @@
+mod support;
+
+use std::sync::Arc;
+
+use futures_util::{SinkExt, StreamExt};
+use mini_explorer::chain::{ChainEvent, LinkPolicy, SyncProgress, SECURITY_PARAM};
+use mini_explorer::data::protocol::OgmiosVersion;
+use mini_explorer::data::{Args, StateQuery};
+use mini_explorer::synchronization::Engine;
+use mini_explorer::ws::{Client, ConnectionEvent, ErrorPolicy, Ogmios, Pipelining};
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+use tokio_tungstenite::tungstenite::http::Uri;
+use tokio_tungstenite::tungstenite::Message;
+
+use support::{blocks, collect_until, fixture, hash, temp_chain, FakeOgmios, Protocol, Step};
+
+#[test]
+fn reads_the_request_id_of_either_envelope() {
+    let v5 = json!({ "type": "jsonwsp/response", "result": null, "reflection": 7 }).to_string();
+    let v6 = json!({ "jsonrpc": "2.0", "result": null, "id": 7 }).to_string();
+    assert_eq!(OgmiosVersion::V5.correlation(&v5), Some(7));
+    assert_eq!(OgmiosVersion::V6.correlation(&v6), Some(7));
+    assert_eq!(OgmiosVersion::V5.correlation(&v6), None);
+    assert_eq!(OgmiosVersion::V6.correlation("not json"), None);
+}
+
+/// Server reading `count` requests before answering them in reverse order,
+/// each with its method name, after a reply to no request.
+async fn reversing(version: OgmiosVersion, count: usize) -> Uri {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let uri = format!("ws://{}", listener.local_addr().unwrap())
+        .parse()
+        .unwrap();
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+        let mut requests = Vec::new();
+        while requests.len() < count {
+            if let Some(Ok(Message::Text(text))) = ws.next().await {
+                requests.push(serde_json::from_str::<Value>(&text).unwrap());
+            }
+        }
+        let reply = |answer: &Value, id: &Value| match version {
+            OgmiosVersion::V6 => json!({ "jsonrpc": "2.0", "result": answer, "id": id }),
+            _ => json!({
+                "type": "jsonwsp/response",
+                "version": "1.0",
+                "servicename": "ogmios",
+                "methodname": "Query",
+                "result": answer,
+                "reflection": id,
+            }),
+        };
+        let stray = reply(&json!("stray"), &json!(999)).to_string();
+        ws.send(Message::Text(stray)).await.unwrap();
+        for request in requests.iter().rev() {
+            let (answer, id) = match version {
+                OgmiosVersion::V6 => (&request["method"], &request["id"]),
+                _ => (&request["args"]["query"], &request["mirror"]),
+            };
+            let reply = reply(answer, id).to_string();
+            ws.send(Message::Text(reply)).await.unwrap();
+        }
+        while ws.next().await.is_some() {}
+    });
+    uri
+}
+
+#[tokio::test]
+async fn dispatches_replies_to_their_request() {
+    for version in [OgmiosVersion::V5, OgmiosVersion::V6] {
+        let uri = reversing(version, 3).await;
+        let (client, mut events) = Client::connect(&uri, version, None).await.unwrap();
+
+        let tip = client.query(StateQuery::ChainTip);
+        let start = client.query(StateQuery::SystemStart);
+        let epoch = client.send::<Value>(Args::Query(StateQuery::CurrentEpoch));
+        let (tip, start, epoch) = tokio::join!(tip, start, epoch);
+
+        let expected = match version {
+            OgmiosVersion::V6 => [
+                "queryNetwork/tip",
+                "queryNetwork/startTime",
+                "queryLedgerState/epoch",
+            ],
+            _ => ["chainTip", "systemStart", "currentEpoch"],
+        };
+        assert_eq!(
+            [tip.unwrap(), start.unwrap(), epoch.unwrap()],
+            expected.map(|answer| json!(answer))
+        );
+        match events.recv().await {
+            Some(ConnectionEvent::Unexpected(reply)) => assert!(reply.contains("stray")),
+            event => panic!("{:?}", event),
+        }
+    }
+}
+
+#[tokio::test]
+async fn detects_the_version_without_moving_chain_sync() {
+    for protocol in [Protocol::V5, Protocol::V6] {
+        let main = blocks(&fixture("babbage"), 0, 3);
+        let steps = main.iter().cloned().map(Step::Forward).collect();
+        let server = FakeOgmios::speaking(protocol, steps, Vec::new()).await;
+        let ogmios = Ogmios::new(
+            server.uri.clone(),
+            OgmiosVersion::Auto,
+            ErrorPolicy::Stop,
+            Pipelining::default(),
+        );
+        let (engine, mut events) = Engine::new(
+            Arc::new(ogmios),
+            100,
+            temp_chain(
+                "detects_the_version_without_moving_chain_sync",
+                SECURITY_PARAM,
+                LinkPolicy::Halt,
+            ),
+        );
+        engine.start().await;
+
+        collect_until(&mut events, |e| {
+            matches!(e, ChainEvent::Synchronizing(SyncProgress::Synchronized(_)))
+        })
+        .await;
+        let requests = server.requests();
+        assert_eq!(requests[0], "Query", "{:?}", requests);
+        let intersections = requests.iter().filter(|r| *r == "FindIntersect").count();
+        assert_eq!(intersections, 1, "{:?}", requests);
+        let chain = engine.chain.lock().await;
+        assert_eq!(chain.tip.as_ref().unwrap().hash, hash(&main[2]));
+    }
+}
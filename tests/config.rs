@@ -0,0 +1,95 @@
+use std::fs;
+use std::path::PathBuf;
+
+use mini_explorer::cli::{Command, CLI};
+use mini_explorer::config::{self, Config};
+use structopt::StructOpt;
+
+const FILE: &str = r#"
+profile = "mainnet"
+
+[profiles.mainnet]
+ws = "ws://mainnet:1337"
+ogmios_version = "v7"
+
+[profiles.local]
+ws = "ws://local:1337"
+security_param = 10
+
+[profiles.local.storage]
+data_dir = "data/local"
+
+[profiles.local.filters]
+watch = ["addr1", "addr2"]
+"#;
+
+fn file(name: &str, content: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "mini-explorer-{}-{}.toml",
+        name,
+        std::process::id()
+    ));
+    fs::write(&path, content).unwrap();
+    path
+}
+
+#[test]
+fn extends_the_built_in_profiles() {
+    let path = file("profiles", FILE);
+    let config = Config::read(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    let mainnet = config.profile("mainnet").unwrap();
+    assert_eq!(mainnet.ws.as_deref(), Some("ws://mainnet:1337"));
+    assert_eq!(mainnet.network_magic, Some(764824073));
+    assert_eq!(mainnet.issues().len(), 1);
+    let preprod = config.profile("preprod").unwrap();
+    assert_eq!(preprod.network_magic, Some(1));
+    assert_eq!(preprod.epochs.shelley_epoch, Some(4));
+    assert!(preprod
+        .vars()
+        .contains(&("MINI_EXPLORER_SHELLEY_EPOCH", "4".to_string())));
+    assert_eq!(config.profile("local").unwrap().network_magic, None);
+    assert!(config.profile("testnet").is_err());
+}
+
+#[test]
+fn rejects_unknown_settings() {
+    let path = file("unknown", "[profiles.local]\nurl = \"ws://local:1337\"\n");
+    let config = Config::read(&path);
+    fs::remove_file(&path).unwrap();
+
+    assert!(config.is_err());
+}
+
+#[test]
+fn flags_and_environment_take_precedence_over_the_file() {
+    let path = file("layers", FILE);
+    std::env::set_var("MINI_EXPLORER_DATA_DIR", "data/env");
+    let args: Vec<String> = [
+        "mini-explorer",
+        "sync",
+        "--config",
+        path.to_str().unwrap(),
+        "--profile",
+        "local",
+        "--security-param",
+        "5",
+    ]
+    .iter()
+    .map(|arg| arg.to_string())
+    .collect();
+    let profile = config::load(&args).unwrap();
+    let cli = CLI::from_iter(&args);
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(profile.unwrap().security_param, Some(10));
+    let opt = match cli.command {
+        Command::Sync(opt) => opt,
+        command => panic!("{:?}", command),
+    };
+    assert_eq!(opt.source.ws.unwrap().to_string(), "ws://local:1337/");
+    assert_eq!(opt.storage.data_dir, PathBuf::from("data/env"));
+    assert_eq!(opt.security_param, 5);
+    assert_eq!(opt.watch, vec!["addr1".to_string(), "addr2".to_string()]);
+}
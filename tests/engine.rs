@@ -0,0 +1,703 @@
+mod support;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use mini_explorer::chain::{ChainEvent, LinkPolicy, SyncProgress, SECURITY_PARAM};
+use mini_explorer::data::protocol::OgmiosVersion;
+use mini_explorer::data::{Block, PointOrOrigin};
+use mini_explorer::mempool::MempoolEvent;
+use mini_explorer::source::{Recording, Scripted};
+use mini_explorer::synchronization::{Engine, Lagged, EVENTS_CAPACITY};
+use mini_explorer::ws::{ConnectionEvent, ErrorPolicy, Ogmios, Pipelining};
+use serde_json::Value;
+use tokio_stream::StreamExt;
+
+use support::{
+    blocks, collect_until, fixture, hash, point, temp_chain, FakeOgmios, Protocol, Step, ERAS,
+};
+
+fn ogmios(server: &FakeOgmios, policy: ErrorPolicy) -> Ogmios {
+    Ogmios::new(
+        server.uri.clone(),
+        OgmiosVersion::V5,
+        policy,
+        Pipelining::default(),
+    )
+}
+
+fn json_rpc(server: &FakeOgmios, policy: ErrorPolicy) -> Ogmios {
+    Ogmios::new(
+        server.uri.clone(),
+        OgmiosVersion::V6,
+        policy,
+        Pipelining::default(),
+    )
+}
+
+fn synchronized(event: &ChainEvent) -> bool {
+    matches!(
+        event,
+        ChainEvent::Synchronizing(SyncProgress::Synchronized(_))
+    )
+}
+
+fn connection(event: &ChainEvent) -> Option<&ConnectionEvent> {
+    match event {
+        ChainEvent::Connection(e) => Some(e),
+        _ => None,
+    }
+}
+
+fn forward(blocks: &[Value]) -> Vec<Step> {
+    blocks.iter().cloned().map(Step::Forward).collect()
+}
+
+#[tokio::test]
+async fn syncs_a_block_of_every_era() {
+    let fixtures: Vec<Value> = ERAS.iter().map(|era| fixture(era)).collect();
+    let server = FakeOgmios::start(forward(&fixtures), Vec::new()).await;
+    let (engine, mut events) = Engine::new(
+        Arc::new(ogmios(&server, ErrorPolicy::Stop)),
+        100,
+        temp_chain(
+            "syncs_a_block_of_every_era",
+            SECURITY_PARAM,
+            LinkPolicy::Log,
+        ),
+    );
+    engine.start().await;
+
+    // Fixtures of different eras do not follow each other, so each is
+    // reported as a break while still being added.
+    let last = hash(&fixtures[6]);
+    collect_until(
+        &mut events,
+        |e| matches!(e, ChainEvent::LinkBroken(b) if b.hash == last),
+    )
+    .await;
+
+    let chain = engine.chain.lock().await;
+    assert!(matches!(chain.sync(), SyncProgress::Synchronized(_)));
+    assert_eq!(chain.tip.as_ref().unwrap().hash, last);
+    assert_eq!(chain.current_epoch, 507);
+}
+
+#[test]
+fn fixtures_decode_into_their_era() {
+    for era in ERAS.iter() {
+        let block: Block = serde_json::from_value(fixture(era)).unwrap();
+        assert_eq!(format!("{:?}", block.era()).to_lowercase(), *era);
+        assert_eq!(block.tx_ids().len(), 1);
+    }
+}
+
+#[tokio::test]
+async fn reverts_blocks_after_the_rollback_point() {
+    let main = blocks(&fixture("shelley"), 0, 3);
+    let fork = blocks(&main[0], 1, 2);
+    let mut steps = forward(&main);
+    steps.push(Step::Backward(point(&main[0])));
+    steps.extend(forward(&fork));
+
+    let server = FakeOgmios::start(steps, Vec::new()).await;
+    let (engine, mut events) = Engine::new(
+        Arc::new(ogmios(&server, ErrorPolicy::Stop)),
+        100,
+        temp_chain(
+            "reverts_blocks_after_the_rollback_point",
+            SECURITY_PARAM,
+            LinkPolicy::Halt,
+        ),
+    );
+    engine.start().await;
+
+    let collected = collect_until(&mut events, synchronized).await;
+    let reverted: Vec<String> = collected
+        .iter()
+        .find_map(|e| match e {
+            ChainEvent::RevertFork(blocks) => Some(blocks.iter().map(Block::hash).collect()),
+            _ => None,
+        })
+        .expect("a fork is reverted");
+    assert_eq!(reverted, vec![hash(&main[1]), hash(&main[2])]);
+
+    let chain = engine.chain.lock().await;
+    assert_eq!(chain.tip.as_ref().unwrap().hash, hash(&fork[1]));
+}
+
+#[tokio::test]
+async fn reverts_blocks_over_json_rpc() {
+    let main = blocks(&fixture("babbage"), 0, 3);
+    let fork = blocks(&main[0], 1, 2);
+    let mut steps = forward(&main);
+    steps.push(Step::Backward(point(&main[0])));
+    steps.extend(forward(&fork));
+
+    let server = FakeOgmios::speaking(Protocol::V6, steps, Vec::new()).await;
+    let (engine, mut events) = Engine::new(
+        Arc::new(json_rpc(&server, ErrorPolicy::Stop)),
+        100,
+        temp_chain(
+            "reverts_blocks_over_json_rpc",
+            SECURITY_PARAM,
+            LinkPolicy::Halt,
+        ),
+    );
+    engine.start().await;
+
+    let collected = collect_until(&mut events, synchronized).await;
+    assert!(collected.iter().all(|e| connection(e).is_none()));
+    let reverted = collected.iter().find_map(|e| match e {
+        ChainEvent::RevertFork(blocks) => Some(blocks.len()),
+        _ => None,
+    });
+    assert_eq!(reverted, Some(2));
+
+    let chain = engine.chain.lock().await;
+    assert_eq!(chain.tip.as_ref().unwrap().hash, hash(&fork[1]));
+    let block = chain.get_block_by_hash(&hash(&fork[0])).unwrap().unwrap();
+    assert_eq!(block.prev_hash(), hash(&main[0]));
+}
+
+#[tokio::test]
+async fn halts_when_json_rpc_finds_no_intersection() {
+    let main = blocks(&fixture("babbage"), 0, 2);
+    let server = FakeOgmios::speaking(Protocol::V6, forward(&main), Vec::new()).await;
+    let (mut engine, mut events) = Engine::new(
+        Arc::new(json_rpc(&server, ErrorPolicy::Stop)),
+        100,
+        temp_chain(
+            "halts_when_json_rpc_finds_no_intersection",
+            SECURITY_PARAM,
+            LinkPolicy::Halt,
+        ),
+    );
+    engine.points = vec!["1.unknown".parse().unwrap()];
+    engine.start().await;
+
+    let collected = collect_until(&mut events, |e| matches!(e, ChainEvent::Halted(_))).await;
+    assert!(collected
+        .iter()
+        .any(|e| matches!(e, ChainEvent::IntersectionNotFound(None))));
+    assert!(engine.chain.lock().await.tip.is_none());
+}
+
+#[tokio::test]
+async fn starts_from_the_requested_point() {
+    let main = blocks(&fixture("mary"), 0, 4);
+    let server = FakeOgmios::start(forward(&main), Vec::new()).await;
+    let (mut engine, mut events) = Engine::new(
+        Arc::new(ogmios(&server, ErrorPolicy::Stop)),
+        100,
+        temp_chain(
+            "starts_from_the_requested_point",
+            SECURITY_PARAM,
+            LinkPolicy::Halt,
+        ),
+    );
+    engine.points = vec![serde_json::from_value(point(&main[1])).unwrap()];
+    engine.start().await;
+
+    let collected = collect_until(&mut events, synchronized).await;
+    let progress = collected
+        .iter()
+        .filter(|e| matches!(e, ChainEvent::Synchronizing(p) if !matches!(p, SyncProgress::Unsynchronized)))
+        .count();
+    assert_eq!(progress, 2);
+}
+
+#[tokio::test]
+async fn every_consumer_receives_the_events() {
+    let main = blocks(&fixture("mary"), 0, 4);
+    let server = FakeOgmios::start(forward(&main), Vec::new()).await;
+    let (engine, mut events) = Engine::new(
+        Arc::new(ogmios(&server, ErrorPolicy::Stop)),
+        100,
+        temp_chain(
+            "every_consumer_receives_the_events",
+            SECURITY_PARAM,
+            LinkPolicy::Halt,
+        ),
+    );
+    let mut other = engine.subscribe();
+    engine.start().await;
+
+    let collected = collect_until(&mut events, synchronized).await;
+    let other = collect_until(&mut other, synchronized).await;
+    let added = |events: &[ChainEvent]| -> Vec<String> {
+        events
+            .iter()
+            .filter_map(|e| match e {
+                ChainEvent::Collection(blocks) => Some(blocks[0].hash()),
+                _ => None,
+            })
+            .collect()
+    };
+    let hashes: Vec<String> = main.iter().map(hash).collect();
+    assert_eq!(added(&collected), hashes);
+    assert_eq!(added(&other), hashes);
+}
+
+#[tokio::test]
+async fn waits_for_subscribers_while_watchers_lag() {
+    // More events than a consumer keeps, each block being two at least.
+    let main = blocks(&fixture("mary"), 0, EVENTS_CAPACITY / 2 + 100);
+    let server = FakeOgmios::start(forward(&main), Vec::new()).await;
+    let (engine, mut events) = Engine::new(
+        Arc::new(ogmios(&server, ErrorPolicy::Stop)),
+        100,
+        temp_chain(
+            "waits_for_subscribers_while_watchers_lag",
+            SECURITY_PARAM,
+            LinkPolicy::Halt,
+        ),
+    );
+    let mut slow = engine.subscribe();
+    let mut watched = engine.watch();
+    engine.start().await;
+
+    let (collected, late) = tokio::join!(collect_until(&mut events, synchronized), async {
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        collect_until(&mut slow, synchronized).await
+    });
+    let mut missed = 0;
+    let mut watching = true;
+    while watching {
+        match tokio::time::timeout(Duration::from_millis(100), watched.next()).await {
+            Ok(Some(Err(Lagged(lagged)))) => missed += lagged,
+            Ok(Some(Ok(_))) => (),
+            _ => watching = false,
+        }
+    }
+
+    let added = |events: &[ChainEvent]| {
+        events
+            .iter()
+            .filter(|e| matches!(e, ChainEvent::Collection(_)))
+            .count()
+    };
+    assert_eq!(added(&collected), main.len());
+    assert_eq!(added(&late), main.len());
+    assert!(missed > 0);
+}
+
+#[tokio::test]
+async fn falls_back_on_the_next_points() {
+    let main = blocks(&fixture("mary"), 0, 4);
+    let server = FakeOgmios::start(forward(&main), Vec::new()).await;
+    let (mut engine, mut events) = Engine::new(
+        Arc::new(ogmios(&server, ErrorPolicy::Stop)),
+        100,
+        temp_chain(
+            "falls_back_on_the_next_points",
+            SECURITY_PARAM,
+            LinkPolicy::Halt,
+        ),
+    );
+    engine.points = vec![
+        "1.unknown".parse().unwrap(),
+        serde_json::from_value(point(&main[2])).unwrap(),
+    ];
+    engine.start().await;
+
+    collect_until(&mut events, synchronized).await;
+    let chain = engine.chain.lock().await;
+    assert!(chain.get_block_by_hash(&hash(&main[3])).unwrap().is_some());
+    assert!(chain.get_block_by_hash(&hash(&main[2])).unwrap().is_none());
+}
+
+#[tokio::test]
+async fn halts_when_no_point_is_on_the_chain() {
+    let main = blocks(&fixture("mary"), 0, 2);
+    let server = FakeOgmios::start(forward(&main), Vec::new()).await;
+    let (mut engine, mut events) = Engine::new(
+        Arc::new(ogmios(&server, ErrorPolicy::Stop)),
+        100,
+        temp_chain(
+            "halts_when_no_point_is_on_the_chain",
+            SECURITY_PARAM,
+            LinkPolicy::Halt,
+        ),
+    );
+    engine.points = vec!["1.unknown".parse().unwrap()];
+    engine.start().await;
+
+    let collected = collect_until(&mut events, |e| matches!(e, ChainEvent::Halted(_))).await;
+    let tip = collected.iter().find_map(|e| match e {
+        ChainEvent::IntersectionNotFound(tip) => tip.clone(),
+        _ => None,
+    });
+    assert_eq!(tip.map(|t| t.hash), Some(hash(&main[1])));
+    assert!(engine.chain.lock().await.tip.is_none());
+}
+
+#[tokio::test]
+async fn starts_from_the_tip() {
+    let main = blocks(&fixture("mary"), 0, 3);
+    let server = FakeOgmios::start(forward(&main), Vec::new()).await;
+    let (mut engine, mut events) = Engine::new(
+        Arc::new(ogmios(&server, ErrorPolicy::Stop)),
+        100,
+        temp_chain("starts_from_the_tip", SECURITY_PARAM, LinkPolicy::Halt),
+    );
+    engine.from_tip = true;
+    engine.start().await;
+
+    collect_until(&mut events, synchronized).await;
+    let chain = engine.chain.lock().await;
+    assert_eq!(chain.tip.as_ref().unwrap().hash, hash(&main[2]));
+    assert!(chain.get_block_by_hash(&hash(&main[2])).unwrap().is_none());
+}
+
+#[tokio::test]
+async fn skips_faults_and_undecodable_replies() {
+    let main = blocks(&fixture("alonzo"), 0, 2);
+    let steps = vec![
+        Step::Forward(main[0].clone()),
+        Step::Fault("boom".to_string()),
+        Step::Garbage,
+        Step::Forward(main[1].clone()),
+    ];
+    let server = FakeOgmios::start(steps, Vec::new()).await;
+    let (engine, mut events) = Engine::new(
+        Arc::new(ogmios(&server, ErrorPolicy::Skip)),
+        100,
+        temp_chain(
+            "skips_faults_and_undecodable_replies",
+            SECURITY_PARAM,
+            LinkPolicy::Halt,
+        ),
+    );
+    engine.start().await;
+
+    let collected = collect_until(&mut events, synchronized).await;
+    let errors: Vec<&ConnectionEvent> = collected.iter().filter_map(connection).collect();
+    assert!(matches!(errors[0], ConnectionEvent::Fault(f) if f.message == "boom"));
+    assert!(matches!(errors[1], ConnectionEvent::Decode { .. }));
+}
+
+#[tokio::test]
+async fn skips_faults_and_undecodable_replies_over_json_rpc() {
+    let main = blocks(&fixture("alonzo"), 0, 2);
+    let steps = vec![
+        Step::Forward(main[0].clone()),
+        Step::Fault("boom".to_string()),
+        Step::Garbage,
+        Step::Forward(main[1].clone()),
+    ];
+    let server = FakeOgmios::speaking(Protocol::V6, steps, Vec::new()).await;
+    let (engine, mut events) = Engine::new(
+        Arc::new(json_rpc(&server, ErrorPolicy::Skip)),
+        100,
+        temp_chain(
+            "skips_faults_and_undecodable_replies_over_json_rpc",
+            SECURITY_PARAM,
+            LinkPolicy::Halt,
+        ),
+    );
+    engine.start().await;
+
+    let collected = collect_until(&mut events, synchronized).await;
+    let errors: Vec<&ConnectionEvent> = collected.iter().filter_map(connection).collect();
+    assert!(
+        matches!(errors[0], ConnectionEvent::Fault(f) if f.message == "boom" && f.code == "2000")
+    );
+    assert!(
+        matches!(errors[1], ConnectionEvent::Decode { error, .. } if error.contains("unknown era"))
+    );
+}
+
+#[tokio::test]
+async fn stops_on_fault_when_asked_to() {
+    let main = blocks(&fixture("babbage"), 0, 2);
+    let steps = vec![
+        Step::Forward(main[0].clone()),
+        Step::Fault("boom".to_string()),
+        Step::Forward(main[1].clone()),
+    ];
+    let server = FakeOgmios::start(steps, Vec::new()).await;
+    let (engine, mut events) = Engine::new(
+        Arc::new(ogmios(&server, ErrorPolicy::Stop)),
+        100,
+        temp_chain(
+            "stops_on_fault_when_asked_to",
+            SECURITY_PARAM,
+            LinkPolicy::Halt,
+        ),
+    );
+    engine.start().await;
+
+    let collected = collect_until(&mut events, |e| {
+        matches!(connection(e), Some(ConnectionEvent::Transport(_)))
+    })
+    .await;
+    assert!(collected
+        .iter()
+        .any(|e| matches!(connection(e), Some(ConnectionEvent::Fault(_)))));
+    assert!(!collected.iter().any(synchronized));
+
+    let chain = engine.chain.lock().await;
+    match chain.sync() {
+        SyncProgress::Synchronizing(_, block, _) => assert_eq!(block.hash(), hash(&main[0])),
+        progress => panic!("unexpected progress {:?}", progress),
+    }
+}
+
+#[tokio::test]
+async fn reports_disconnections() {
+    let main = blocks(&fixture("conway"), 0, 1);
+    let steps = vec![Step::Forward(main[0].clone()), Step::Disconnect];
+    let server = FakeOgmios::start(steps, Vec::new()).await;
+    let (engine, mut events) = Engine::new(
+        Arc::new(ogmios(&server, ErrorPolicy::Skip)),
+        100,
+        temp_chain("reports_disconnections", SECURITY_PARAM, LinkPolicy::Halt),
+    );
+    engine.start().await;
+
+    collect_until(&mut events, |e| {
+        matches!(connection(e), Some(ConnectionEvent::Closed(_)))
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn reports_pending_transactions() {
+    let steps = forward(&blocks(&fixture("babbage"), 0, 1));
+    let server = FakeOgmios::start(steps, vec!["ab".repeat(32)]).await;
+    let (engine, mut events) = Engine::new(
+        Arc::new(ogmios(&server, ErrorPolicy::Skip)),
+        100,
+        temp_chain(
+            "reports_pending_transactions",
+            SECURITY_PARAM,
+            LinkPolicy::Halt,
+        ),
+    );
+    engine.start().await;
+
+    let collected = collect_until(&mut events, |e| {
+        matches!(e, ChainEvent::Mempool(MempoolEvent::Size(_)))
+    })
+    .await;
+    assert!(collected.iter().any(|e| matches!(
+        e,
+        ChainEvent::Mempool(MempoolEvent::Pending { id, .. }) if *id == "ab".repeat(32)
+    )));
+}
+
+#[tokio::test]
+async fn reports_pending_transactions_over_json_rpc() {
+    let steps = forward(&blocks(&fixture("babbage"), 0, 1));
+    let server = FakeOgmios::speaking(Protocol::V6, steps, vec!["ab".repeat(32)]).await;
+    let (engine, mut events) = Engine::new(
+        Arc::new(json_rpc(&server, ErrorPolicy::Skip)),
+        100,
+        temp_chain(
+            "reports_pending_transactions_over_json_rpc",
+            SECURITY_PARAM,
+            LinkPolicy::Halt,
+        ),
+    );
+    engine.start().await;
+
+    let collected = collect_until(
+        &mut events,
+        |e| matches!(e, ChainEvent::Mempool(MempoolEvent::Size(s)) if s.number_of_txs == 1),
+    )
+    .await;
+    assert!(collected.iter().any(|e| matches!(
+        e,
+        ChainEvent::Mempool(MempoolEvent::Pending { id, .. }) if *id == "ab".repeat(32)
+    )));
+}
+
+#[tokio::test]
+async fn runs_on_a_scripted_source() {
+    let main = blocks(&fixture("shelley"), 0, 3);
+    let script = main
+        .iter()
+        .map(|block| {
+            serde_json::from_value(serde_json::json!({
+                "RollForward": { "block": block, "tip": {
+                    "slot": support::slot(&main[2]),
+                    "hash": hash(&main[2]),
+                    "blockNo": support::height(&main[2]),
+                }}
+            }))
+            .unwrap()
+        })
+        .collect();
+    let (engine, mut events) = Engine::new(
+        Arc::new(Scripted::new(script)),
+        100,
+        temp_chain(
+            "runs_on_a_scripted_source",
+            SECURITY_PARAM,
+            LinkPolicy::Halt,
+        ),
+    );
+    engine.start().await;
+
+    collect_until(&mut events, synchronized).await;
+}
+
+#[tokio::test]
+async fn replays_a_recorded_session() {
+    let path = std::env::temp_dir().join(format!("mini-explorer-{}.jsonl.gz", std::process::id()));
+    let main = blocks(&fixture("mary"), 0, 3);
+    let mut steps = forward(&main);
+    steps.insert(1, Step::Fault("boom".to_string()));
+
+    let server = FakeOgmios::start(steps, Vec::new()).await;
+    let source = ogmios(&server, ErrorPolicy::Skip).record(Some(path.clone()));
+    let (recorder, mut events) = Engine::new(
+        Arc::new(source),
+        100,
+        temp_chain(
+            "replays_a_recorded_session",
+            SECURITY_PARAM,
+            LinkPolicy::Halt,
+        ),
+    );
+    recorder.start().await;
+    let live = collect_until(&mut events, synchronized).await;
+    // Frames are written in the background.
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let (engine, mut events) = Engine::new(
+        Arc::new(Recording::new(path.clone(), 0.0)),
+        100,
+        temp_chain("replayed_session", SECURITY_PARAM, LinkPolicy::Halt),
+    );
+    engine.start().await;
+    let replayed = collect_until(&mut events, synchronized).await;
+    let _ = std::fs::remove_file(path);
+
+    let summary = |events: &[ChainEvent]| -> Vec<String> {
+        events
+            .iter()
+            .filter(|e| !matches!(e, ChainEvent::Throughput(_) | ChainEvent::Mempool(_)))
+            .map(|e| format!("{:?}", e))
+            .collect()
+    };
+    assert_eq!(summary(&live), summary(&replayed));
+}
+
+fn broken(event: &ChainEvent) -> bool {
+    matches!(event, ChainEvent::LinkBroken(_))
+}
+
+#[tokio::test]
+async fn halts_on_a_block_not_following_the_last_one() {
+    let main = blocks(&fixture("alonzo"), 0, 3);
+    let stray = blocks(&main[0], 1, 2).pop().unwrap();
+    let mut steps = forward(&main[..2]);
+    steps.push(Step::Forward(stray.clone()));
+    steps.push(Step::Forward(main[2].clone()));
+
+    let server = FakeOgmios::start(steps, Vec::new()).await;
+    let (engine, mut events) = Engine::new(
+        Arc::new(ogmios(&server, ErrorPolicy::Stop)),
+        100,
+        temp_chain(
+            "halts_on_a_block_not_following_the_last_one",
+            SECURITY_PARAM,
+            LinkPolicy::Halt,
+        ),
+    );
+    engine.start().await;
+
+    let collected = collect_until(&mut events, broken).await;
+    match collected.last() {
+        Some(ChainEvent::LinkBroken(b)) => {
+            assert_eq!(b.hash, hash(&stray));
+            assert_eq!(b.expected, hash(&main[1]));
+        }
+        event => panic!("unexpected event {:?}", event),
+    }
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let chain = engine.chain.lock().await;
+    match chain.sync() {
+        SyncProgress::Synchronizing(_, block, _) => assert_eq!(block.hash(), hash(&main[1])),
+        progress => panic!("unexpected progress {:?}", progress),
+    }
+}
+
+#[tokio::test]
+async fn intersects_again_after_a_stray_block() {
+    let main = blocks(&fixture("babbage"), 0, 4);
+    let stray = blocks(&main[0], 1, 2).pop().unwrap();
+    let mut steps = forward(&main[..2]);
+    steps.push(Step::Stray(stray));
+    steps.extend(forward(&main[2..]));
+
+    let server = FakeOgmios::start(steps, Vec::new()).await;
+    let (engine, mut events) = Engine::new(
+        Arc::new(ogmios(&server, ErrorPolicy::Stop)),
+        100,
+        temp_chain(
+            "intersects_again_after_a_stray_block",
+            SECURITY_PARAM,
+            LinkPolicy::Reintersect,
+        ),
+    );
+    engine.start().await;
+
+    let collected = collect_until(&mut events, synchronized).await;
+    assert_eq!(collected.iter().filter(|e| broken(e)).count(), 1);
+    let chain = engine.chain.lock().await;
+    let hashes: Vec<String> = chain
+        .points()
+        .into_iter()
+        .filter_map(|p| match p {
+            PointOrOrigin::Point(p) => Some(p.hash),
+            PointOrOrigin::Origin(_) => None,
+        })
+        .collect();
+    assert_eq!(hashes, main.iter().rev().map(hash).collect::<Vec<_>>());
+}
+
+#[tokio::test]
+async fn reports_blocks_once_final() {
+    let main = blocks(&fixture("shelley"), 0, 4);
+    let server = FakeOgmios::start(forward(&main), Vec::new()).await;
+    let (engine, mut events) = Engine::new(
+        Arc::new(ogmios(&server, ErrorPolicy::Stop)),
+        100,
+        temp_chain("reports_blocks_once_final", 2, LinkPolicy::Halt),
+    );
+    engine.start().await;
+
+    let collected = collect_until(&mut events, synchronized).await;
+    let finalized: Vec<String> = collected
+        .iter()
+        .filter_map(|e| match e {
+            ChainEvent::Finalized(blocks) => Some(blocks.iter().map(Block::hash)),
+            _ => None,
+        })
+        .flatten()
+        .collect();
+    assert_eq!(finalized, vec![hash(&main[0]), hash(&main[1])]);
+}
+
+#[tokio::test]
+async fn halts_on_a_rollback_past_final_blocks() {
+    let main = blocks(&fixture("mary"), 0, 4);
+    let mut steps = forward(&main);
+    steps.push(Step::Backward(point(&main[0])));
+
+    let server = FakeOgmios::start(steps, Vec::new()).await;
+    let (engine, mut events) = Engine::new(
+        Arc::new(ogmios(&server, ErrorPolicy::Stop)),
+        100,
+        temp_chain("halts_on_a_rollback_past_final_blocks", 2, LinkPolicy::Log),
+    );
+    engine.start().await;
+
+    collect_until(&mut events, |e| matches!(e, ChainEvent::RollbackTooDeep(_))).await;
+    let chain = engine.chain.lock().await;
+    assert_eq!(chain.tip.as_ref().unwrap().hash, hash(&main[3]));
+    assert!(matches!(chain.sync(), SyncProgress::Synchronized(_)));
+}
@@ -0,0 +1,150 @@
+mod support;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use mini_explorer::chain::Chain;
+use mini_explorer::cli::{Command, ExportOptions, CLI};
+use mini_explorer::data::{Block, EpochLayout};
+use mini_explorer::export::{self, Format};
+use parquet::file::reader::{FileReader, SerializedFileReader};
+use parquet::record::RowAccessor;
+use serde_json::Value;
+use structopt::StructOpt;
+
+use support::stored;
+
+fn options(dir: &Path, args: &[&str]) -> ExportOptions {
+    let mut all = vec![
+        "mini-explorer",
+        "export",
+        "--data-dir",
+        dir.to_str().unwrap(),
+    ];
+    all.extend(args);
+    match CLI::from_iter(all).command {
+        Command::Export(opt) => opt,
+        command => panic!("{:?}", command),
+    }
+}
+
+/// Number of blocks selected by `args`.
+fn selected(dir: &Path, args: &[&str]) -> usize {
+    let chain = Chain::open(dir).unwrap();
+    options(dir, args).blocks(&chain).count()
+}
+
+#[test]
+fn selects_blocks_by_height_slot_or_epoch() {
+    let (dir, written) = stored("export-range");
+    let height = written[1].height().to_string();
+    let slot = written[1].slot().to_string();
+    let epoch = written[0].epoch(&EpochLayout::MAINNET);
+
+    let by_height = selected(&dir, &["--to", &height]);
+    let by_slot = selected(&dir, &["--from-slot", &slot]);
+    let in_epoch = selected(&dir, &["--from-epoch", &epoch.to_string()]);
+    let before = selected(&dir, &["--to-epoch", &(epoch - 1).to_string()]);
+    let conflicting = CLI::from_iter_safe(vec![
+        "mini-explorer",
+        "export",
+        "--from",
+        "1",
+        "--from-slot",
+        "1",
+    ]);
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(by_height, 2);
+    assert_eq!(by_slot, 2);
+    assert_eq!(in_epoch, 3);
+    assert_eq!(before, 0);
+    assert!(conflicting.is_err());
+}
+
+#[test]
+fn writes_flattened_tables_as_jsonl_and_csv() {
+    let (dir, written) = stored("export-tables");
+    let chain = Chain::open(&dir).unwrap();
+    let jsonl = dir.join("jsonl");
+    let csv = dir.join("csv");
+    let exported = export::tables(
+        chain.blocks_by_height(..),
+        &chain.layout,
+        Format::Jsonl,
+        &jsonl,
+    )
+    .unwrap();
+    export::tables(chain.blocks_by_height(..), &chain.layout, Format::Csv, &csv).unwrap();
+
+    let lines = |path: PathBuf| -> Vec<String> {
+        fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    };
+    let blocks = lines(jsonl.join("blocks.jsonl"));
+    let outputs = lines(jsonl.join("outputs.jsonl"));
+    let transactions = lines(csv.join("transactions.csv"));
+    let certificates = lines(csv.join("certificates.csv"));
+    let mints = lines(csv.join("mints.csv"));
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(exported, 3);
+    let first: Value = serde_json::from_str(&blocks[0]).unwrap();
+    assert_eq!(first["hash"], written[0].hash());
+    assert_eq!(first["era"], "mary");
+    assert_eq!(first["issuer"], written[0].issuer().unwrap());
+    let outputs_written: usize = written.iter().map(|b| b.outputs().len()).sum();
+    assert_eq!(outputs.len(), outputs_written);
+    let output: Value = serde_json::from_str(&outputs[0]).unwrap();
+    assert_eq!(output["address"], written[0].outputs()[0].address.as_str());
+    assert_eq!(
+        transactions[0],
+        "block,height,slot,id,index,inputs,outputs,coins,fee"
+    );
+    assert_eq!(transactions.len(), 1 + 3 * written[0].tx_ids().len());
+    assert_eq!(certificates.len(), 1 + 3 * written[0].certificates().len());
+    assert_eq!(mints.len(), 1 + 3 * written[0].mints().len());
+}
+
+#[test]
+fn writes_parquet_files() {
+    let (dir, written) = stored("export-parquet");
+    let chain = Chain::open(&dir).unwrap();
+    let out = dir.join("parquet");
+    export::tables(
+        chain.blocks_by_height(..),
+        &chain.layout,
+        Format::Parquet,
+        &out,
+    )
+    .unwrap();
+
+    let reader =
+        |name: &str| SerializedFileReader::new(fs::File::open(out.join(name)).unwrap()).unwrap();
+    let blocks = reader("blocks.parquet");
+    let outputs = reader("outputs.parquet");
+    let rows = outputs.metadata().file_metadata().num_rows();
+    let hashes: Vec<String> = blocks
+        .get_row_iter(None)
+        .unwrap()
+        .map(|row| row.get_string(0).unwrap().clone())
+        .collect();
+    let columns: Vec<String> = blocks
+        .metadata()
+        .file_metadata()
+        .schema_descr()
+        .columns()
+        .iter()
+        .map(|column| column.name().to_string())
+        .collect();
+    fs::remove_dir_all(&dir).unwrap();
+
+    let outputs_written: usize = written.iter().map(|b| b.outputs().len()).sum();
+    assert_eq!(rows as usize, outputs_written);
+    assert_eq!(hashes, written.iter().map(Block::hash).collect::<Vec<_>>());
+    assert_eq!(columns[0], "hash");
+    assert_eq!(columns[6], "issuer");
+}
@@ -0,0 +1,100 @@
+mod support;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use mini_explorer::chain::Chunk;
+use mini_explorer::cli::{Command, SyncOptions, CLI};
+use mini_explorer::data::Block;
+use mini_explorer::headless::{self, Exit};
+use structopt::StructOpt;
+
+use support::{blocks, fixture, hash, next, FakeOgmios, Step};
+
+fn dir(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("mini-explorer-{}-{}", name, std::process::id()))
+}
+
+fn opt(server: &FakeOgmios, dir: &Path) -> SyncOptions {
+    let cli = CLI::from_iter(&[
+        "mini-explorer",
+        "sync",
+        "--ws",
+        &server.uri.to_string(),
+        "--ogmios-version",
+        "v5",
+        "--security-param",
+        "1",
+        "--data-dir",
+        dir.to_str().unwrap(),
+    ]);
+    match cli.command {
+        Command::Sync(opt) => opt,
+        command => panic!("{:?}", command),
+    }
+}
+
+fn hashes(path: &Path) -> Vec<String> {
+    Chunk::load(path).unwrap().iter().map(Block::hash).collect()
+}
+
+/// Two byron blocks of epoch 207, then two shelley blocks of 208.
+fn steps() -> (Vec<String>, Vec<Step>) {
+    let byron = blocks(&fixture("byron"), 0, 2);
+    let mut shelley = fixture("shelley");
+    shelley["shelley"]["header"]["prevHash"] = hash(&byron[1]).into();
+    let last = next(&shelley, 0);
+    let chain = vec![byron[0].clone(), byron[1].clone(), shelley, last];
+    (
+        chain.iter().map(hash).collect(),
+        chain.into_iter().map(Step::Forward).collect(),
+    )
+}
+
+#[tokio::test]
+async fn writes_final_blocks_when_the_source_stops() {
+    let dir = dir("headless-halted");
+    let (hashes_, mut steps) = steps();
+    steps.push(Step::Disconnect);
+    let server = FakeOgmios::start(steps, Vec::new()).await;
+
+    let exit = headless::run(&opt(&server, &dir)).await;
+    let (complete, flushed) = (hashes(&dir.join("207.bin")), hashes(&dir.join("208.bin")));
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(exit, Exit::Halted);
+    assert_eq!(complete, hashes_[..2].to_vec());
+    // Only the last block is still volatile.
+    assert_eq!(flushed, hashes_[2..3].to_vec());
+}
+
+#[test]
+fn requires_a_source() {
+    assert!(CLI::from_iter_safe(&["mini-explorer", "sync"]).is_err());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn stops_on_sigterm() {
+    let dir = dir("headless-stopped");
+    let (hashes_, steps) = steps();
+    let server = FakeOgmios::start(steps, Vec::new()).await;
+
+    let opt = opt(&server, &dir);
+    let run = tokio::spawn(async move { headless::run(&opt).await });
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    std::process::Command::new("kill")
+        .args(&["-TERM", &std::process::id().to_string()])
+        .status()
+        .unwrap();
+    let exit = tokio::time::timeout(Duration::from_secs(10), run)
+        .await
+        .unwrap()
+        .unwrap();
+    let flushed = hashes(&dir.join("208.bin"));
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(exit, Exit::Stopped);
+    assert_eq!(flushed, hashes_[2..3].to_vec());
+}
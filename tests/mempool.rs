@@ -0,0 +1,129 @@
+mod support;
+
+use mini_explorer::data::mempool::{AcquireResult, MempoolSize, MempoolSnapshot};
+use mini_explorer::data::protocol::OgmiosVersion;
+use mini_explorer::data::Block;
+use mini_explorer::mempool::{Mempool, MempoolEvent};
+use serde_json::json;
+
+use support::{fixture, v6_frame};
+
+fn v5(method: &str, result: serde_json::Value) -> String {
+    json!({
+        "type": "jsonwsp/response",
+        "version": "1.0",
+        "servicename": "ogmios",
+        "methodname": method,
+        "result": result,
+        "reflection": 4,
+    })
+    .to_string()
+}
+
+#[test]
+fn parses_the_tx_monitor_replies_of_jsonwsp() {
+    let acquired = v5(
+        "AwaitAcquire",
+        json!({ "AwaitAcquired": { "slot": 72317012 } }),
+    );
+    match OgmiosVersion::V5
+        .decode::<AcquireResult>(&acquired)
+        .unwrap()
+    {
+        AcquireResult::AwaitAcquired { slot } => assert_eq!(slot, 72317012),
+    }
+
+    let id = "ab".repeat(32);
+    let next = OgmiosVersion::V5.decode::<Option<String>>(&v5("NextTx", json!(id)));
+    assert_eq!(next.unwrap(), Some(id));
+    let last = OgmiosVersion::V5.decode::<Option<String>>(&v5("NextTx", json!(null)));
+    assert_eq!(last.unwrap(), None);
+
+    let size = json!({ "capacity": 180224, "currentSize": 2048, "numberOfTxs": 7 });
+    let size = OgmiosVersion::V5
+        .decode::<MempoolSize>(&v5("SizeAndCapacity", size))
+        .unwrap();
+    assert_eq!(
+        (size.capacity, size.current_size, size.number_of_txs),
+        (180224, 2048, 7)
+    );
+}
+
+#[test]
+fn parses_the_tx_monitor_replies_of_json_rpc() {
+    match OgmiosVersion::V6
+        .decode::<AcquireResult>(&v6_frame("acquire_mempool"))
+        .unwrap()
+    {
+        AcquireResult::AwaitAcquired { slot } => assert_eq!(slot, 72317012),
+    }
+
+    let next = OgmiosVersion::V6.decode::<Option<String>>(&v6_frame("next_transaction"));
+    assert_eq!(
+        next.unwrap().as_deref(),
+        Some("add3edbf6f795906b5307487cb98e6d8b9769ca7f207f0bb158ccecf0b9084e2")
+    );
+    let last = OgmiosVersion::V6.decode::<Option<String>>(&v6_frame("next_transaction_none"));
+    assert_eq!(last.unwrap(), None);
+
+    let size = OgmiosVersion::V6
+        .decode::<MempoolSize>(&v6_frame("size_of_mempool"))
+        .unwrap();
+    assert_eq!(
+        (size.capacity, size.current_size, size.number_of_txs),
+        (180224, 2048, 7)
+    );
+}
+
+fn snapshot(txs: &[&str]) -> MempoolSnapshot {
+    MempoolSnapshot {
+        slot: 1,
+        txs: txs.iter().map(|tx| tx.to_string()).collect(),
+        size: MempoolSize::default(),
+    }
+}
+
+#[test]
+fn reports_transactions_entering_leaving_and_included() {
+    let block: Block = serde_json::from_value(fixture("babbage")).unwrap();
+    let included = block.tx_ids().remove(0);
+    let mut mempool = Mempool::new();
+
+    let events = mempool.update(snapshot(&[&included, "other"]));
+    let pending = events
+        .iter()
+        .filter(|e| matches!(e, MempoolEvent::Pending { .. }))
+        .count();
+    assert_eq!(pending, 2);
+    assert!(matches!(events.last(), Some(MempoolEvent::Size(_))));
+
+    let events = mempool.update(snapshot(&["other"]));
+    assert!(matches!(&events[0], MempoolEvent::Left { id } if *id == included));
+
+    // Transactions which left the mempool are still matched with the block
+    // including them.
+    let events = mempool.include(&block);
+    assert!(matches!(
+        &events[..],
+        [MempoolEvent::Included { id, slot, .. }] if *id == included && *slot == block.slot()
+    ));
+    assert!(mempool.include(&block).is_empty());
+}
+
+#[test]
+fn leaves_the_mempool_when_included_while_pending() {
+    let block: Block = serde_json::from_value(fixture("babbage")).unwrap();
+    let included = block.tx_ids().remove(0);
+    let mut mempool = Mempool::new();
+    mempool.update(snapshot(&[&included]));
+
+    let events = mempool.include(&block);
+    assert!(matches!(
+        &events[..],
+        [MempoolEvent::Left { id: left }, MempoolEvent::Included { id, .. }]
+            if *left == included && *id == included
+    ));
+    // Not pending anymore, so not leaving again with the next snapshot.
+    let events = mempool.update(snapshot(&[]));
+    assert!(matches!(&events[..], [MempoolEvent::Size(_)]));
+}
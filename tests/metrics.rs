@@ -0,0 +1,136 @@
+mod support;
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use mini_explorer::chain::{ChainEvent, LinkPolicy, SyncProgress, SECURITY_PARAM};
+use mini_explorer::data::protocol::OgmiosVersion;
+use mini_explorer::data::Block;
+use mini_explorer::metrics::{self, Exporter, Metrics};
+use mini_explorer::synchronization::Engine;
+use mini_explorer::ws::{ConnectionEvent, ErrorPolicy, Ogmios, Pipelining};
+
+use support::{blocks, collect_until, fixture, http_get, temp_chain, FakeOgmios, Step};
+
+/// The value of the sample `name` in the exposition `text`.
+fn sample(text: &str, name: &str) -> Option<f64> {
+    text.lines()
+        .find_map(|line| line.strip_prefix(name)?.strip_prefix(' '))
+        .map(|value| value.parse().unwrap())
+}
+
+#[test]
+fn counts_rollbacks_decode_errors_and_dumps() {
+    let written: Vec<Block> = blocks(&fixture("mary"), 0, 3)
+        .into_iter()
+        .map(|b| serde_json::from_value(b).unwrap())
+        .collect();
+    let mut metrics = Metrics::new();
+    metrics.record(&ChainEvent::Collection(written.clone()));
+    metrics.record(&ChainEvent::RevertFork(written[1..].to_vec()));
+    metrics.record(&ChainEvent::Connection(ConnectionEvent::Decode {
+        error: "missing field".to_string(),
+        payload: "{}".to_string(),
+    }));
+    metrics.record(&ChainEvent::Reintersecting(3));
+    metrics.record(&ChainEvent::Dumped {
+        epoch: 250,
+        blocks: 21600,
+        elapsed: Duration::from_millis(300),
+    });
+
+    metrics.record(&ChainEvent::Memory {
+        blocks: 2,
+        bytes: 2048,
+    });
+
+    let text = metrics.render();
+    assert_eq!(sample(&text, "mini_explorer_blocks_total"), Some(3.0));
+    // Left as it is until the next block.
+    assert_eq!(
+        sample(&text, "mini_explorer_slot"),
+        Some(written[2].slot() as f64)
+    );
+    assert_eq!(sample(&text, "mini_explorer_rollbacks_total"), Some(1.0));
+    assert_eq!(
+        sample(&text, "mini_explorer_rollback_depth_bucket{le=\"1\"}"),
+        Some(0.0)
+    );
+    assert_eq!(
+        sample(&text, "mini_explorer_rollback_depth_bucket{le=\"2\"}"),
+        Some(1.0)
+    );
+    assert_eq!(sample(&text, "mini_explorer_rollback_depth_sum"), Some(2.0));
+    assert_eq!(
+        sample(&text, "mini_explorer_decode_errors_total"),
+        Some(1.0)
+    );
+    assert_eq!(sample(&text, "mini_explorer_reconnects_total"), Some(1.0));
+    assert_eq!(sample(&text, "mini_explorer_chunk_dumps_total"), Some(1.0));
+    assert_eq!(
+        sample(&text, "mini_explorer_chunk_dump_seconds_bucket{le=\"0.5\"}"),
+        Some(1.0)
+    );
+    assert_eq!(
+        sample(&text, "mini_explorer_chain_blocks_in_memory"),
+        Some(2.0)
+    );
+    assert_eq!(
+        sample(&text, "mini_explorer_chain_memory_bytes"),
+        Some(2048.0)
+    );
+    assert_eq!(sample(&text, "mini_explorer_tip_slot"), None);
+}
+
+#[tokio::test]
+async fn serves_the_metrics_of_the_sync() {
+    let main = blocks(&fixture("mary"), 0, 4);
+    let server = FakeOgmios::start(
+        main.iter().cloned().map(Step::Forward).collect(),
+        Vec::new(),
+    )
+    .await;
+    let source = Ogmios::new(
+        server.uri.clone(),
+        OgmiosVersion::V5,
+        ErrorPolicy::Stop,
+        Pipelining::default(),
+    );
+    let (engine, mut events) = Engine::new(
+        Arc::new(source),
+        100,
+        temp_chain(
+            "serves_the_metrics_of_the_sync",
+            SECURITY_PARAM,
+            LinkPolicy::Halt,
+        ),
+    );
+    let recorded = Arc::new(Mutex::new(Metrics::new()));
+    tokio::spawn(metrics::record(engine.watch(), recorded.clone()));
+    let exporter = Exporter::bind(&"127.0.0.1:0".parse().unwrap(), recorded).unwrap();
+    let addr = exporter.local_addr();
+    tokio::spawn(exporter.run(futures::future::pending()));
+    engine.start().await;
+
+    collect_until(&mut events, |e| {
+        matches!(e, ChainEvent::Synchronizing(SyncProgress::Synchronized(_)))
+    })
+    .await;
+    // Recorded by its own task, after this one received the events.
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let (status, text) = http_get(addr, "/metrics").await;
+    let (missing, _) = http_get(addr, "/").await;
+
+    assert_eq!(status, 200);
+    assert_eq!(sample(&text, "mini_explorer_blocks_total"), Some(4.0));
+    assert_eq!(
+        sample(&text, "mini_explorer_chain_blocks_in_memory"),
+        Some(4.0)
+    );
+    assert!(sample(&text, "mini_explorer_chain_memory_bytes").unwrap() > 0.0);
+    assert_eq!(
+        sample(&text, "mini_explorer_slot"),
+        sample(&text, "mini_explorer_tip_slot")
+    );
+    assert_eq!(missing, 404);
+}
@@ -0,0 +1,314 @@
+mod support;
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use mini_explorer::chain::{ChainEvent, LinkPolicy, SyncProgress, SECURITY_PARAM};
+use mini_explorer::data::Block;
+use mini_explorer::n2c::{decode, handshake, mux, NodeClient};
+use mini_explorer::synchronization::Engine;
+use mini_explorer::ws::ErrorPolicy;
+use minicbor::data::Tag;
+use minicbor::{Decoder, Encoder};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{UnixListener, UnixStream};
+
+use support::{collect_until, temp_chain};
+
+/// Block of `era` as the node serves it, CBOR wrapped in its era. The
+/// Babbage block follows the Byron one.
+fn cbor(era: &str) -> Vec<u8> {
+    let path = format!(
+        "{}/tests/fixtures/n2c/{}.hex",
+        env!("CARGO_MANIFEST_DIR"),
+        era
+    );
+    hex::decode(fs::read_to_string(path).unwrap().trim()).unwrap()
+}
+
+fn socket(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "mini-explorer-{}-{}.socket",
+        name,
+        std::process::id()
+    ));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+async fn read_segment(stream: &mut UnixStream) -> Option<(u16, Vec<u8>)> {
+    let mut header = [0u8; 8];
+    stream.read_exact(&mut header).await.ok()?;
+    let protocol = u16::from_be_bytes([header[4], header[5]]);
+    let len = u16::from_be_bytes([header[6], header[7]]) as usize;
+    let mut payload = vec![0u8; len];
+    stream.read_exact(&mut payload).await.ok()?;
+    Some((protocol, payload))
+}
+
+/// Sends `msg` from the node side of `protocol`, split in segments of at
+/// most `split` bytes.
+async fn write_segments(stream: &mut UnixStream, protocol: u16, msg: &[u8], split: usize) {
+    for chunk in msg.chunks(split) {
+        let mut segment = vec![0u8; 4];
+        segment.extend_from_slice(&(protocol | 0x8000).to_be_bytes());
+        segment.extend_from_slice(&(chunk.len() as u16).to_be_bytes());
+        segment.extend_from_slice(chunk);
+        stream.write_all(&segment).await.unwrap();
+    }
+}
+
+fn point(e: &mut Encoder<Vec<u8>>, block: &Block) {
+    e.array(2)
+        .unwrap()
+        .u64(block.slot())
+        .unwrap()
+        .bytes(&hex::decode(block.hash()).unwrap())
+        .unwrap();
+}
+
+fn tip(e: &mut Encoder<Vec<u8>>, block: &Block) {
+    e.array(2).unwrap();
+    point(e, block);
+    e.u64(block.height()).unwrap();
+}
+
+/// Node accepting the handshake, answering the tip queries and serving
+/// `blocks` after an intersection at the origin. Blocks are split in small
+/// segments and the node makes the client wait before each one.
+async fn fake_node(path: &PathBuf, blocks: Vec<Vec<u8>>) {
+    let listener = UnixListener::bind(path).unwrap();
+    let last = decode::block(blocks.last().unwrap()).unwrap();
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut blocks = blocks.into_iter();
+        while let Some((protocol, msg)) = read_segment(&mut stream).await {
+            let mut d = Decoder::new(&msg);
+            d.array().unwrap();
+            let tag = d.u8().unwrap();
+            let mut e = Encoder::new(Vec::new());
+            match (protocol, tag) {
+                (mux::HANDSHAKE, 0) => {
+                    e.array(3).unwrap().u8(1).unwrap().u64(32784).unwrap();
+                    e.array(2)
+                        .unwrap()
+                        .u64(764824073)
+                        .unwrap()
+                        .bool(false)
+                        .unwrap();
+                }
+                (mux::STATE_QUERY, 8) => {
+                    e.array(1).unwrap().u8(1).unwrap();
+                }
+                (mux::STATE_QUERY, 3) => {
+                    e.array(2).unwrap().u8(4).unwrap();
+                    d.array().unwrap();
+                    match d.u8().unwrap() {
+                        3 => point(&mut e, &last),
+                        _ => {
+                            e.array(2)
+                                .unwrap()
+                                .u8(1)
+                                .unwrap()
+                                .u64(last.height())
+                                .unwrap();
+                        }
+                    }
+                }
+                (mux::STATE_QUERY, 5) => continue,
+                (mux::CHAIN_SYNC, 4) => {
+                    e.array(3).unwrap().u8(5).unwrap().array(0).unwrap();
+                    tip(&mut e, &last);
+                }
+                (mux::CHAIN_SYNC, 0) => {
+                    let block = match blocks.next() {
+                        Some(block) => block,
+                        None => continue,
+                    };
+                    let mut wait = Encoder::new(Vec::new());
+                    wait.array(1).unwrap().u8(1).unwrap();
+                    write_segments(&mut stream, protocol, &wait.into_writer(), 64).await;
+                    e.array(3).unwrap().u8(2).unwrap();
+                    e.tag(Tag::Cbor).unwrap().bytes(&block).unwrap();
+                    tip(&mut e, &last);
+                }
+                other => panic!("unexpected message {:?}", other),
+            }
+            write_segments(&mut stream, protocol, &e.into_writer(), 64).await;
+        }
+    });
+}
+
+#[test]
+fn decodes_a_babbage_block() {
+    let block = decode::block(&cbor("babbage")).unwrap();
+    assert_eq!(
+        block.hash(),
+        "91d54e7566bf070fa93472846508f321c8929133b2ff240fa5723532d64205b6"
+    );
+    assert_eq!((block.height(), block.slot()), (7791700, 72316916));
+    assert_eq!(
+        block.prev_hash(),
+        "71962a8ecc85a971c1af34bcea1070467fd1f8095b30fc329f9b1c0f5ac42401"
+    );
+    assert_eq!(
+        block.issuer().unwrap(),
+        "pool1fyg39hgpz4wq0k45shm3k4ewpjh8t83v6w93cr5h24pfwz6pcfw"
+    );
+
+    let txs = block.transactions();
+    assert_eq!(
+        txs[0].id,
+        "b9684b4494ffc835794d11b99777008f0146779d0edf2caa706fb6287e940853"
+    );
+    assert_eq!(
+        (txs[0].inputs, txs[0].outputs, txs[0].coins, txs[0].fee),
+        (1, 2, 125000000, Some(174433))
+    );
+    let outputs = block.outputs();
+    assert!(outputs[0].address.starts_with("addr1qymrvd3kxcmrvd3k"));
+    assert_eq!(outputs[0].address, outputs[1].address);
+
+    let certificates = block.certificates();
+    assert_eq!(certificates.len(), 1, "unknown certificates are left out");
+    assert_eq!(
+        certificates[0].1.pool(),
+        Some("pool1pu8s7rc0pu8s7rc0pu8s7rc0pu8s7rc0pu8s7rc0pu8s7f4emcx")
+    );
+    let mints = block.mints();
+    assert_eq!(mints[0].asset, format!("{}.4d494e54", "0d".repeat(28)));
+    assert_eq!(mints[0].quantity, 5);
+
+    let tx = block.tx(&txs[0].id).unwrap();
+    assert_eq!(
+        tx["metadata"]["hash"],
+        "25310adf5d314b3a7f1014fb3ee142c26b7303a4843653427087195bd61ad355"
+    );
+    assert_eq!(
+        tx["metadata"]["body"]["blob"]["674"]["map"][0]["k"]["string"],
+        "msg"
+    );
+}
+
+#[test]
+fn decodes_a_byron_block() {
+    let block = decode::block(&cbor("byron")).unwrap();
+    assert_eq!(
+        block.hash(),
+        "71962a8ecc85a971c1af34bcea1070467fd1f8095b30fc329f9b1c0f5ac42401"
+    );
+    assert_eq!(block.height(), 97000);
+    assert_eq!(block.prev_hash(), "89".repeat(32));
+
+    let txs = block.transactions();
+    assert_eq!(
+        txs[0].id,
+        "7595c9c20ea14d12deb1fa4cf1ec8683ad648f811a53ad7cd4c6b8af67176361"
+    );
+    assert_eq!((txs[0].inputs, txs[0].coins), (1, 1500000));
+    assert_eq!(
+        block.outputs()[0].address,
+        "7W5RaS6Y3RMupPqwZ19RmCZwAGRrZFUW8PAEG35ZsGgxfS7k1SJENBC"
+    );
+}
+
+#[test]
+fn rejects_unknown_eras_and_truncated_blocks() {
+    let mut block = cbor("babbage");
+    block[1] = 9;
+    assert!(decode::block(&block).is_err());
+    let block = cbor("babbage");
+    assert!(decode::block(&block[..block.len() / 2]).is_err());
+}
+
+#[tokio::test]
+async fn reassembles_messages_spanning_segments() {
+    let path = socket("segments");
+    let listener = UnixListener::bind(&path).unwrap();
+    let mut channels = mux::connect(&path, &[mux::HANDSHAKE, mux::CHAIN_SYNC])
+        .await
+        .unwrap();
+    let (mut node, _) = listener.accept().await.unwrap();
+
+    // Two messages of chain-sync, the first split over three segments with
+    // one of the handshake in between.
+    let block = cbor("babbage");
+    let mut msgs = Encoder::new(Vec::new());
+    msgs.bytes(&block).unwrap().u8(7).unwrap();
+    let msgs = msgs.into_writer();
+    let (first, rest) = msgs.split_at(block.len() / 2);
+    write_segments(&mut node, mux::CHAIN_SYNC, first, first.len()).await;
+    write_segments(&mut node, mux::HANDSHAKE, &[0x80], 1).await;
+    write_segments(&mut node, mux::CHAIN_SYNC, rest, 1000).await;
+
+    let mut chain_sync = channels.remove(&mux::CHAIN_SYNC).unwrap();
+    let mut handshake = channels.remove(&mux::HANDSHAKE).unwrap();
+    let msg = chain_sync.recv().await.unwrap();
+    assert_eq!(Decoder::new(&msg).bytes().unwrap(), &block[..]);
+    assert_eq!(chain_sync.recv().await.unwrap(), vec![7]);
+    assert_eq!(handshake.recv().await.unwrap(), vec![0x80]);
+
+    // Messages larger than a segment are sent in several.
+    let large = vec![0u8; 70000];
+    chain_sync.send(&large).await.unwrap();
+    let (protocol, segment) = read_segment(&mut node).await.unwrap();
+    assert_eq!((protocol, segment.len()), (mux::CHAIN_SYNC, 0xffff));
+    let (_, segment) = read_segment(&mut node).await.unwrap();
+    assert_eq!(segment.len(), 70000 - 0xffff);
+    fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn reports_a_refused_handshake() {
+    let path = socket("refused");
+    let listener = UnixListener::bind(&path).unwrap();
+    let mut channels = mux::connect(&path, &[mux::HANDSHAKE]).await.unwrap();
+    let (mut node, _) = listener.accept().await.unwrap();
+    let mut channel = channels.remove(&mux::HANDSHAKE).unwrap();
+
+    let node = tokio::spawn(async move {
+        let (protocol, proposal) = read_segment(&mut node).await.unwrap();
+        let mut d = Decoder::new(&proposal);
+        d.array().unwrap();
+        assert_eq!((protocol, d.u8().unwrap()), (mux::HANDSHAKE, 0));
+        let versions = d.map().unwrap().unwrap();
+        let mut refuse = Encoder::new(Vec::new());
+        refuse.array(2).unwrap().u8(2).unwrap();
+        refuse.array(2).unwrap().u8(0).unwrap().array(0).unwrap();
+        write_segments(&mut node, mux::HANDSHAKE, &refuse.into_writer(), 64).await;
+        versions
+    });
+    let refused = handshake::handshake(&mut channel, 42).await.unwrap_err();
+    assert!(refused.to_string().contains("refused"), "{}", refused);
+    assert_eq!(node.await.unwrap(), 8);
+    fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn syncs_from_the_node_socket() {
+    let path = socket("sync");
+    fake_node(&path, vec![cbor("byron"), cbor("babbage")]).await;
+    let node = NodeClient::new(path.clone(), 764824073, ErrorPolicy::Stop);
+    let (engine, mut events) = Engine::new(
+        Arc::new(node),
+        100,
+        temp_chain(
+            "syncs_from_the_node_socket",
+            SECURITY_PARAM,
+            LinkPolicy::Halt,
+        ),
+    );
+    engine.start().await;
+
+    collect_until(&mut events, |e| {
+        matches!(e, ChainEvent::Synchronizing(SyncProgress::Synchronized(_)))
+    })
+    .await;
+    let chain = engine.chain.lock().await;
+    for era in ["byron", "babbage"] {
+        let hash = decode::block(&cbor(era)).unwrap().hash();
+        assert!(chain.get_block_by_hash(&hash).unwrap().is_some(), "{}", era);
+    }
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,265 @@
+mod support;
+
+use std::convert::Infallible;
+use std::fs;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response, Server, StatusCode};
+use mini_explorer::chain::{Chain, ChainEvent, Chunk, LinkPolicy, SECURITY_PARAM};
+use mini_explorer::data::protocol::OgmiosVersion;
+use mini_explorer::data::{Block, PointOrOrigin};
+use mini_explorer::notify::{self, Notification, Notifier, Rules, Webhook};
+use mini_explorer::synchronization::Engine;
+use mini_explorer::ws::{ErrorPolicy, Ogmios, Pipelining};
+use serde_json::Value;
+use tokio::sync::mpsc;
+
+use support::{blocks, fixture, hash, point, stored, temp_chain, FakeOgmios, Step};
+
+fn webhook(url: &str) -> Webhook {
+    toml::from_str(&format!("url = \"{}\"\nbackoff_ms = 10", url)).unwrap()
+}
+
+/// An endpoint failing its first request, then sending the JSON bodies
+/// received.
+async fn endpoint() -> (SocketAddr, mpsc::UnboundedReceiver<Value>) {
+    let (received, receiving) = mpsc::unbounded_channel();
+    let requests = Arc::new(AtomicUsize::new(0));
+    let make = make_service_fn(move |_| {
+        let (received, requests) = (received.clone(), requests.clone());
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req: Request<Body>| {
+                let (received, requests) = (received.clone(), requests.clone());
+                async move {
+                    let body = hyper::body::to_bytes(req.into_body()).await.unwrap();
+                    let status = if requests.fetch_add(1, Ordering::SeqCst) == 0 {
+                        StatusCode::INTERNAL_SERVER_ERROR
+                    } else {
+                        let _ = received.send(serde_json::from_slice(&body).unwrap());
+                        StatusCode::OK
+                    };
+                    Ok::<_, Infallible>(
+                        Response::builder()
+                            .status(status)
+                            .body(Body::empty())
+                            .unwrap(),
+                    )
+                }
+            }))
+        }
+    });
+    let server = Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(make);
+    let addr = server.local_addr();
+    tokio::spawn(server);
+    (addr, receiving)
+}
+
+#[test]
+fn retracts_the_notifications_of_reverted_blocks() {
+    let main: Vec<Block> = blocks(&fixture("mary"), 0, 3)
+        .into_iter()
+        .map(|b| serde_json::from_value(b).unwrap())
+        .collect();
+    let address = main[0].outputs()[0].address.clone();
+    let mut payments = webhook("http://127.0.0.1:1/payments");
+    payments.addresses = vec![address.clone()];
+    let mut rollbacks = webhook("http://127.0.0.1:1/rollbacks");
+    rollbacks.rollback_depth = Some(2);
+    let mut notifier = Notifier::new(Rules {
+        webhooks: vec![payments, rollbacks],
+    });
+
+    let added = notifier.notify(&ChainEvent::Collection(main.clone()));
+    let shallow = notifier.notify(&ChainEvent::RevertFork(main[2..].to_vec()));
+    notifier.notify(&ChainEvent::Finalized(main[..1].to_vec()));
+    let deep = notifier.notify(&ChainEvent::RevertFork(main[..2].to_vec()));
+    let deeper = notifier.notify(&ChainEvent::RevertFork(main.clone()));
+
+    assert!(!added.is_empty());
+    assert!(added.iter().all(
+        |(i, n)| *i == 0 && matches!(n, Notification::Payment(p) if p.item.address == address)
+    ));
+    assert_eq!(
+        shallow,
+        vec![(
+            0,
+            Notification::Retraction {
+                block: main[2].hash(),
+                height: main[2].height(),
+            }
+        )]
+    );
+    // The first block was final, so only the second one is retracted.
+    assert_eq!(deep.len(), 1);
+    assert_eq!(
+        deep[0].1,
+        Notification::Retraction {
+            block: main[1].hash(),
+            height: main[1].height(),
+        }
+    );
+    assert!(matches!(
+        deeper.as_slice(),
+        [(1, Notification::Rollback { depth: 3, .. })]
+    ));
+}
+
+/// A notifier of the payments to the first output of `main`, and of the
+/// rollbacks deeper than `SECURITY_PARAM`.
+fn watching(main: &[Block]) -> Notifier {
+    let mut payments = webhook("http://127.0.0.1:1/payments");
+    payments.addresses = vec![main[0].outputs()[0].address.clone()];
+    let mut rollbacks = webhook("http://127.0.0.1:1/rollbacks");
+    rollbacks.rollback_depth = Some(SECURITY_PARAM);
+    Notifier::new(Rules {
+        webhooks: vec![payments, rollbacks],
+    })
+}
+
+fn retracted(due: &[(usize, Notification)]) -> Vec<String> {
+    due.iter()
+        .filter_map(|(_, n)| match n {
+            Notification::Retraction { block, .. } => Some(block.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn catches_up_with_the_chain_after_missed_events() {
+    // The chain stored the first three blocks, and the fourth was rolled
+    // back while the events were missed.
+    let (dir, _) = stored("notify-missed");
+    let chain = Chain::open(&dir).unwrap();
+    let main: Vec<Block> = blocks(&fixture("mary"), 0, 4)
+        .into_iter()
+        .map(|b| serde_json::from_value(b).unwrap())
+        .collect();
+    let mut notifier = watching(&main);
+
+    notifier.notify(&ChainEvent::Collection(main.clone()));
+    let caught_up = notifier.reconcile(&chain);
+    let reverted = notifier.notify(&ChainEvent::RevertFork(main.clone()));
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(retracted(&caught_up), vec![main[3].hash()]);
+    // The stored blocks are final, so they are not retracted anymore.
+    assert!(retracted(&reverted).is_empty());
+}
+
+#[test]
+fn notifies_rollbacks_too_deep_to_apply() {
+    let (dir, written) = stored("notify-deep");
+    let chain = Chain::open(&dir).unwrap();
+    let mut notifier = watching(&written);
+
+    notifier.notify(&ChainEvent::Collection(written[1..].to_vec()));
+    let point = PointOrOrigin::point(written[0].slot(), written[0].hash());
+    let due = notifier.too_deep(&point, &chain);
+    // The blocks cannot be read anymore, yet they are retracted.
+    notifier.notify(&ChainEvent::Collection(written[1..].to_vec()));
+    let reopened = Chain::open(&dir).unwrap();
+    fs::remove_file(Chunk::path(&dir, 251)).unwrap();
+    let unread = notifier.too_deep(&point, &reopened);
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(retracted(&due), vec![written[2].hash(), written[1].hash()]);
+    let rollback = due.last().unwrap();
+    assert_eq!(rollback.0, 1);
+    assert_eq!(
+        rollback.1,
+        Notification::Rollback {
+            hash: written[0].hash(),
+            height: written[0].height(),
+            depth: 2,
+            reverted: vec![written[1].hash(), written[2].hash()],
+        }
+    );
+    assert_eq!(
+        retracted(&unread),
+        vec![written[2].hash(), written[1].hash()]
+    );
+    assert_eq!(unread.len(), 2);
+}
+
+#[test]
+fn rejects_webhooks_which_are_not_http() {
+    let path = std::env::temp_dir().join(format!(
+        "mini-explorer-webhooks-{}.toml",
+        std::process::id()
+    ));
+    std::fs::write(&path, "[[webhook]]\nurl = \"https://example.com\"\n").unwrap();
+    let rules = Rules::read(&path);
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(rules.is_err());
+}
+
+#[tokio::test]
+async fn posts_the_notifications_with_retries() {
+    let main = blocks(&fixture("shelley"), 0, 3);
+    let fork = blocks(&main[0], 1, 2);
+    let mut steps: Vec<Step> = main.iter().cloned().map(Step::Forward).collect();
+    steps.push(Step::Backward(point(&main[0])));
+    steps.extend(fork.iter().cloned().map(Step::Forward));
+    let server = FakeOgmios::start(steps, Vec::new()).await;
+    let source = Ogmios::new(
+        server.uri.clone(),
+        OgmiosVersion::V5,
+        ErrorPolicy::Stop,
+        Pipelining::default(),
+    );
+    let (engine, _events) = Engine::new(
+        Arc::new(source),
+        100,
+        temp_chain(
+            "posts_the_notifications_with_retries",
+            SECURITY_PARAM,
+            LinkPolicy::Halt,
+        ),
+    );
+
+    let (addr, mut received) = endpoint().await;
+    let issuer: Block = serde_json::from_value(main[0].clone()).unwrap();
+    let mut tracked = webhook(&format!("http://{}/hook", addr));
+    tracked.pools = vec![issuer.issuer().unwrap()];
+    tracked.rollback_depth = Some(1);
+    let rules = Rules {
+        webhooks: vec![tracked],
+    };
+    tokio::spawn(notify::run(rules, engine.watch(), engine.chain.clone()));
+    engine.start().await;
+
+    let mut notifications = Vec::new();
+    while notifications.len() < 8 {
+        let next = tokio::time::timeout(Duration::from_secs(5), received.recv());
+        notifications.push(next.await.unwrap().unwrap());
+    }
+    let kinds: Vec<&str> = notifications
+        .iter()
+        .map(|n| n["type"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        kinds,
+        vec![
+            "blockMinted",
+            "blockMinted",
+            "blockMinted",
+            "retraction",
+            "retraction",
+            "rollback",
+            "blockMinted",
+            "blockMinted",
+        ]
+    );
+    assert_eq!(notifications[0]["hash"], hash(&main[0]));
+    assert_eq!(notifications[3]["block"], hash(&main[2]));
+    assert_eq!(notifications[4]["block"], hash(&main[1]));
+    assert_eq!(notifications[5]["hash"], hash(&main[0]));
+    assert_eq!(notifications[5]["depth"], 2);
+    assert_eq!(notifications[7]["hash"], hash(&fork[1]));
+}
@@ -0,0 +1,45 @@
+use mini_explorer::ws::Pipelining;
+
+fn pipelining() -> Pipelining {
+    Pipelining {
+        min_depth: 10,
+        max_depth: 40,
+        buffer: 100,
+    }
+}
+
+#[test]
+fn deepens_while_the_buffer_stays_mostly_free() {
+    let pipelining = pipelining();
+    assert_eq!(pipelining.adjust(10, 100), 11);
+    assert_eq!(pipelining.adjust(20, 51), 21);
+    assert_eq!(pipelining.adjust(40, 100), 40);
+}
+
+#[test]
+fn halves_when_the_consumer_falls_behind() {
+    let pipelining = pipelining();
+    assert_eq!(pipelining.adjust(40, 24), 20);
+    assert_eq!(pipelining.adjust(15, 0), 10);
+}
+
+#[test]
+fn keeps_the_depth_in_between() {
+    let pipelining = pipelining();
+    assert_eq!(pipelining.adjust(30, 25), 30);
+    assert_eq!(pipelining.adjust(30, 50), 30);
+}
+
+#[test]
+fn settles_between_the_bounds() {
+    let pipelining = pipelining();
+    let mut depth = pipelining.min_depth;
+    for _ in 0..100 {
+        depth = pipelining.adjust(depth, 100);
+    }
+    assert_eq!(depth, pipelining.max_depth);
+    for _ in 0..100 {
+        depth = pipelining.adjust(depth, 0);
+    }
+    assert_eq!(depth, pipelining.min_depth);
+}
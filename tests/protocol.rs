@@ -0,0 +1,144 @@
+mod support;
+
+use mini_explorer::data::protocol::{DecodeError, OgmiosVersion};
+use mini_explorer::data::{Args, EpochLayout, Era, PointOrOrigin, RResult};
+use serde_json::{json, Value};
+
+use support::v6_frame;
+
+fn encoded(version: OgmiosVersion, args: Args, id: u64) -> Value {
+    serde_json::from_str(&version.encode(args, id)).unwrap()
+}
+
+#[test]
+fn encodes_requests_as_json_rpc() {
+    let points = vec![
+        PointOrOrigin::point(72316896, "a812f3c0".to_string()),
+        PointOrOrigin::origin(),
+    ];
+    assert_eq!(
+        encoded(OgmiosVersion::V6, Args::FindIntersect(points), 0),
+        json!({
+            "jsonrpc": "2.0",
+            "method": "findIntersection",
+            "params": { "points": [{ "slot": 72316896, "id": "a812f3c0" }, "origin"] },
+            "id": 0,
+        })
+    );
+    assert_eq!(
+        encoded(OgmiosVersion::V6, Args::RequestNext, 12),
+        json!({ "jsonrpc": "2.0", "method": "nextBlock", "id": 12 })
+    );
+    assert_eq!(
+        encoded(OgmiosVersion::V6, Args::SubmitTx("84a3".to_string()), 3)["params"],
+        json!({ "transaction": { "cbor": "84a3" } })
+    );
+}
+
+#[test]
+fn encodes_requests_as_jsonwsp_otherwise() {
+    for version in [OgmiosVersion::V5, OgmiosVersion::Auto] {
+        let request = encoded(version, Args::RequestNext, 12);
+        assert_eq!(request["type"], "jsonwsp/request");
+        assert_eq!(request["methodname"], "RequestNext");
+        assert_eq!(request["mirror"], 12);
+    }
+}
+
+#[test]
+fn decodes_a_block_of_json_rpc() {
+    let tip = match OgmiosVersion::V6.decode::<RResult>(&v6_frame("next_block_forward")) {
+        Ok(RResult::RollForward { block, tip }) => {
+            assert!(matches!(block.era(), Era::Babbage));
+            assert_eq!(
+                block.hash(),
+                "a812f3c03eed4f064b16b94925376cb35f6a79090df9f19a4940b63591286251"
+            );
+            assert_eq!(block.height(), 7791699);
+            assert_eq!(block.slot(), 72316896);
+            assert!(block.issuer().unwrap().starts_with("pool1"));
+
+            let txs = block.transactions();
+            assert_eq!(txs.len(), 1);
+            assert_eq!(
+                (txs[0].inputs, txs[0].outputs, txs[0].coins, txs[0].fee),
+                (1, 2, 125000000, Some(174433))
+            );
+            let certificates = block.certificates();
+            assert_eq!(certificates.len(), 1, "unknown certificates are left out");
+            let mints = block.mints();
+            assert_eq!(mints.len(), 1);
+            assert_eq!(mints[0].quantity, 5);
+            assert!(mints[0].asset.ends_with(".4d494e54"));
+            tip
+        }
+        result => panic!("{:?}", result),
+    };
+    assert_eq!((tip.slot, tip.block_no), (72317000, 7791703));
+}
+
+#[test]
+fn decodes_rollbacks_and_intersections_of_json_rpc() {
+    match OgmiosVersion::V6.decode::<RResult>(&v6_frame("next_block_backward")) {
+        Ok(RResult::RollBackward {
+            point: PointOrOrigin::Point(point),
+            ..
+        }) => assert_eq!(point.slot, 72316896),
+        result => panic!("{:?}", result),
+    }
+    match OgmiosVersion::V6.decode::<RResult>(&v6_frame("find_intersection")) {
+        Ok(RResult::IntersectionFound {
+            point: PointOrOrigin::Origin(_),
+            tip,
+        }) => assert_eq!(tip.block_no, 7791703),
+        result => panic!("{:?}", result),
+    }
+}
+
+#[test]
+fn decodes_json_rpc_errors_as_faults() {
+    match OgmiosVersion::V6.decode::<RResult>(&v6_frame("find_intersection_error")) {
+        Err(DecodeError::Fault(fault)) => {
+            assert_eq!(fault.code, "1000");
+            assert_eq!(fault.message, "No intersection found.");
+        }
+        result => panic!("{:?}", result),
+    }
+    let empty = json!({ "jsonrpc": "2.0", "method": "nextBlock", "id": 1 }).to_string();
+    assert!(matches!(
+        OgmiosVersion::V6.decode::<RResult>(&empty),
+        Err(DecodeError::Malformed(_))
+    ));
+}
+
+#[test]
+fn keeps_the_epoch_of_byron_blocks_of_json_rpc() {
+    let mut frame: Value = serde_json::from_str(&v6_frame("next_block_forward")).unwrap();
+    frame["result"]["block"]["era"] = json!("byron");
+    frame["result"]["block"]["slot"] = json!(4492799);
+    match OgmiosVersion::V6.decode::<RResult>(&frame.to_string()) {
+        Ok(RResult::RollForward { block, .. }) => {
+            assert!(matches!(block.era(), Era::Byron));
+            assert_eq!(block.epoch(&EpochLayout::MAINNET), 207);
+            assert_eq!(block.epoch(&EpochLayout::PREPROD), 207);
+        }
+        result => panic!("{:?}", result),
+    }
+}
+
+#[test]
+fn rejects_blocks_of_unknown_eras() {
+    let mut frame: Value = serde_json::from_str(&v6_frame("next_block_forward")).unwrap();
+    frame["result"]["block"]["era"] = json!("dijkstra");
+    match OgmiosVersion::V6.decode::<RResult>(&frame.to_string()) {
+        Err(DecodeError::Malformed(e)) => assert!(e.contains("dijkstra"), "{}", e),
+        result => panic!("{:?}", result),
+    }
+}
+
+#[test]
+fn tells_the_envelopes_apart() {
+    assert!(OgmiosVersion::is_json_rpc(&v6_frame("find_intersection")));
+    let v5 = json!({ "type": "jsonwsp/response", "result": null }).to_string();
+    assert!(!OgmiosVersion::is_json_rpc(&v5));
+}
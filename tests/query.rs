@@ -0,0 +1,210 @@
+mod support;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::Result;
+use mini_explorer::chain::Chain;
+use mini_explorer::cli::{Command, Query, SyncOptions, CLI};
+use mini_explorer::data::{Block, EpochLayout, PointOrOrigin};
+use mini_explorer::query::{self, Lookup};
+use structopt::StructOpt;
+
+use support::{stored, stored_chunk};
+
+#[test]
+fn looks_up_stored_blocks_and_transactions() {
+    let (dir, written) = stored("query");
+    let chain = Chain::open(&dir).unwrap();
+
+    let by_hash = chain.get_block_by_hash(&written[1].hash()).unwrap();
+    let by_height = chain.get_block_by_height(written[2].height()).unwrap();
+    let missing = chain.get_block_by_height(written[2].height() + 1).unwrap();
+    let id = written[0].tx_ids()[0].clone();
+    let tx = query::tx(&chain, &id).unwrap();
+    let output = written[0].outputs()[0].clone();
+    let payments = query::address(&chain, &output.address).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(by_hash.map(|b| b.hash()), Some(written[1].hash()));
+    assert_eq!(by_height.map(|b| b.hash()), Some(written[2].hash()));
+    assert!(missing.is_none());
+    // Every block of the fixture repeats the same transactions, so the
+    // first one holds the transaction.
+    assert_eq!(tx.map(|(b, _)| b.hash()), Some(written[0].hash()));
+    assert!(payments
+        .iter()
+        .any(|p| p.block == written[2].hash() && p.item == output));
+}
+
+#[test]
+fn exports_blocks_between_heights() {
+    let (dir, written) = stored("export");
+    let chain = Chain::open(&dir).unwrap();
+
+    let mut out = Vec::new();
+    let exported = query::export(&chain, written[1].height().., &mut out).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    let hashes: Vec<String> = String::from_utf8(out)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str::<Block>(line).unwrap().hash())
+        .collect();
+    assert_eq!(exported, 2);
+    assert_eq!(hashes, vec![written[1].hash(), written[2].hash()]);
+}
+
+#[test]
+fn parses_subcommands_sharing_storage_options() {
+    let cli = CLI::from_iter(&[
+        "mini-explorer",
+        "query",
+        "--data-dir",
+        "chunks",
+        "block",
+        "--height",
+        "3",
+    ]);
+    match cli.command {
+        Command::Query {
+            storage,
+            query: Query::Block { height, .. },
+        } => {
+            assert_eq!(storage.data_dir, PathBuf::from("chunks"));
+            assert_eq!(height, Some(3));
+        }
+        command => panic!("{:?}", command),
+    }
+    assert!(CLI::from_iter_safe(&["mini-explorer", "query", "block"]).is_err());
+}
+
+fn sync_options(args: &[&str]) -> SyncOptions {
+    let mut all = vec!["mini-explorer", "sync", "--ws", "ws://127.0.0.1:1337"];
+    all.extend(args);
+    match CLI::from_iter(all).command {
+        Command::Sync(opt) => opt,
+        command => panic!("{:?}", command),
+    }
+}
+
+/// Points to start from with `args`, after the blocks stored in `dir`.
+fn points(dir: &Path, args: &[&str]) -> Result<Vec<PointOrOrigin>> {
+    let mut all = vec!["--data-dir", dir.to_str().unwrap()];
+    all.extend(args);
+    let opt = sync_options(&all);
+    opt.points(&opt.chain()?)
+}
+
+#[test]
+fn starts_an_epoch_from_the_last_stored_block() {
+    let (dir, written) = stored("epoch");
+    let next = (written[2].epoch(&EpochLayout::MAINNET) + 1).to_string();
+    let later = format!("{}.00", EpochLayout::MAINNET.first_slot(300));
+    let started = points(&dir, &["--from-epoch", &next]);
+    let given = points(&dir, &["--from-epoch", &next, "--point", &later]);
+    fs::remove_dir_all(&dir).unwrap();
+
+    // The blocks up to the epoch are followed from the stored ones, as a
+    // point needs the hash of a block.
+    let expected = vec![PointOrOrigin::point(written[2].slot(), written[2].hash())];
+    assert_eq!(format!("{:?}", started.unwrap()), format!("{:?}", expected));
+    let expected: Vec<PointOrOrigin> = vec![later.parse().unwrap()];
+    assert_eq!(format!("{:?}", given.unwrap()), format!("{:?}", expected));
+    assert!(CLI::from_iter_safe(&[
+        "mini-explorer",
+        "sync",
+        "--ws",
+        "ws://a",
+        "--from-tip",
+        "--point",
+        "origin"
+    ])
+    .is_err());
+}
+
+#[test]
+fn resumes_after_the_last_stored_block() {
+    let (dir, written) = stored("resume");
+    let first = written[0].slot().to_string();
+    let resumed = points(&dir, &[]);
+    let from_origin = points(&dir, &["--point", "origin"]);
+    let inside = points(&dir, &["--block", &written[0].hash(), "--slot", &first]);
+    let from_tip = points(&dir, &["--from-tip"]);
+    let empty = points(&dir.join("empty"), &[]);
+    fs::remove_dir_all(&dir).unwrap();
+
+    let expected = vec![PointOrOrigin::point(written[2].slot(), written[2].hash())];
+    assert_eq!(format!("{:?}", resumed.unwrap()), format!("{:?}", expected));
+    assert!(from_origin.unwrap_err().to_string().contains("epoch 251"));
+    assert!(inside.is_err());
+    assert!(from_tip.is_err());
+    assert_eq!(
+        format!("{:?}", empty.unwrap()),
+        format!("{:?}", vec![PointOrOrigin::origin()])
+    );
+}
+
+#[test]
+fn splits_slots_into_the_epochs_of_the_network() {
+    let preprod = sync_options(&["--shelley-epoch", "4"]).storage.layout();
+    let mainnet = EpochLayout::MAINNET;
+
+    assert_eq!(preprod, EpochLayout::PREPROD);
+    assert_eq!(mainnet.first_slot(208), 4492800);
+    assert_eq!(mainnet.epoch(4492799), 207);
+    assert_eq!(mainnet.epoch(4492800 + 432000), 209);
+    assert_eq!(preprod.first_slot(4), 86400);
+    assert_eq!(preprod.epoch(86400 + 432000 - 1), 4);
+}
+
+#[test]
+fn summarizes_byron_epochs_from_their_chunk() {
+    let (dir, written) = stored_chunk("byron", "byron", 207, 3);
+    let chain = Chain::open(&dir).unwrap();
+
+    let epoch = query::epoch(&chain, 207).unwrap();
+    let before = query::epoch(&chain, 206).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    // Found from the heights of the chunk, as Byron blocks are not indexed
+    // by slot.
+    assert_eq!(epoch.blocks, 3);
+    assert_eq!(epoch.first_block, Some(written[0].hash()));
+    assert_eq!(before.blocks, 0);
+}
+
+#[test]
+fn reads_a_bounded_number_of_blocks_per_page() {
+    let (dir, written) = stored_chunk("scan", "mary", 251, query::SCAN_BLOCKS + 2);
+    let chain = Chain::open(&dir).unwrap();
+    let address = written[0].outputs()[0].address.clone();
+    let paid = written
+        .iter()
+        .flat_map(Block::outputs)
+        .filter(|output| output.address == address)
+        .count();
+    let (first, cut) = (written[0].height(), written[query::SCAN_BLOCKS].height());
+
+    let page = query::payments(&chain, "addr_nowhere", first, 10).unwrap();
+    let last = query::payments(&chain, "addr_nowhere", cut, 10).unwrap();
+    let lookup = query::tx_from(&chain, "nowhere", first).unwrap();
+    let found = query::address(&chain, &address).unwrap();
+    let epoch = query::epoch_page(&chain, 251, 0).unwrap();
+    let whole = query::epoch(&chain, 251).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    // Empty, with where to carry on from.
+    assert!(page.data.is_empty());
+    assert_eq!(page.next, Some(cut.to_string()));
+    assert!(last.data.is_empty());
+    assert_eq!(last.next, None);
+    assert!(matches!(lookup, Lookup::Next(height) if height == cut));
+    assert_eq!(found.len(), paid);
+    let txs = written[0].tx_ids().len();
+    assert_eq!(epoch.blocks, written.len());
+    assert_eq!(epoch.txs, query::SCAN_BLOCKS * txs);
+    assert_eq!(epoch.next, Some(cut.to_string()));
+    assert_eq!(whole.txs, written.len() * txs);
+    assert_eq!(whole.next, None);
+}
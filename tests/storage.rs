@@ -0,0 +1,197 @@
+mod support;
+
+use std::fs;
+use std::sync::Arc;
+
+use mini_explorer::chain::index::Index;
+use mini_explorer::chain::{Chain, ChainEvent, Chunk, LinkPolicy, SyncProgress};
+use mini_explorer::data::protocol::OgmiosVersion;
+use mini_explorer::data::{Block, EpochLayout, PointOrOrigin};
+use mini_explorer::synchronization::Engine;
+use mini_explorer::ws::{ErrorPolicy, Ogmios, Pipelining};
+use serde_json::Value;
+
+use support::{blocks, collect_until, fixture, hash, height, next, slot, FakeOgmios, Step};
+
+fn synchronized(event: &ChainEvent) -> bool {
+    matches!(
+        event,
+        ChainEvent::Synchronizing(SyncProgress::Synchronized(_))
+    )
+}
+
+/// Three byron blocks of epoch 207 followed by two shelley blocks of 208.
+fn crossing_epochs() -> (Vec<Value>, Value, Value) {
+    let byron = blocks(&fixture("byron"), 0, 3);
+    let mut shelley = fixture("shelley");
+    shelley["shelley"]["header"]["prevHash"] = hash(&byron[2]).into();
+    let last = next(&shelley, 0);
+    (byron, shelley, last)
+}
+
+async fn sync(chain: Chain) -> (Box<Engine>, Vec<ChainEvent>) {
+    let (byron, shelley, last) = crossing_epochs();
+    let mut steps: Vec<Step> = byron.into_iter().map(Step::Forward).collect();
+    steps.push(Step::Forward(shelley));
+    steps.push(Step::Forward(last));
+
+    let server = FakeOgmios::start(steps, Vec::new()).await;
+    let source = Ogmios::new(
+        server.uri.clone(),
+        OgmiosVersion::V5,
+        ErrorPolicy::Stop,
+        Pipelining::default(),
+    );
+    let (engine, mut events) = Engine::new(Arc::new(source), 100, chain);
+    engine.start().await;
+    let collected = collect_until(&mut events, synchronized).await;
+    (engine, collected)
+}
+
+#[tokio::test]
+async fn dumps_an_epoch_once_a_block_of_the_next_one_is_final() {
+    let dir = std::env::temp_dir().join(format!("mini-explorer-{}", std::process::id()));
+    let (byron, shelley, last) = crossing_epochs();
+    let (engine, _) = sync(Chain::new(1, LinkPolicy::Halt).data_dir(dir.join("data"))).await;
+
+    let chain = engine.chain.lock().await;
+    assert_eq!(chain.current_epoch, 208);
+    let dumped = Chunk::load(&dir.join("data/207.bin")).unwrap();
+    let hashes: Vec<String> = dumped.iter().map(Block::hash).collect();
+    assert_eq!(hashes, byron.iter().map(hash).collect::<Vec<_>>());
+    let leftovers = fs::read_dir(dir.join("data"))
+        .unwrap()
+        .filter(|e| e.as_ref().unwrap().path().extension().unwrap() == "tmp")
+        .count();
+    assert_eq!(leftovers, 0);
+
+    // Lookups read dumped chunks as well as blocks still in memory.
+    let index = Index::load(&dir.join("data")).unwrap();
+    assert_eq!(index.len(), 3);
+    let block = chain.get_block_by_height(height(&byron[1])).unwrap();
+    assert_eq!(block.map(|b| b.hash()), Some(hash(&byron[1])));
+    let block = chain.get_block_by_slot(slot(&last)).unwrap();
+    assert_eq!(block.map(|b| b.hash()), Some(hash(&last)));
+    let hashes: Vec<String> = chain
+        .blocks_by_height(height(&byron[1])..)
+        .map(|b| b.unwrap().hash())
+        .collect();
+    assert_eq!(
+        hashes,
+        vec![
+            hash(&byron[1]),
+            hash(&byron[2]),
+            hash(&shelley),
+            hash(&last)
+        ]
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn writes_the_epoch_being_filled_every_few_blocks() {
+    let dir = std::env::temp_dir().join(format!("mini-explorer-partial-{}", std::process::id()));
+    let (byron, _, _) = crossing_epochs();
+    let chain = Chain::new(2, LinkPolicy::Halt)
+        .data_dir(dir.clone())
+        .flush_every(2);
+    let (_engine, _) = sync(chain).await;
+
+    // The third immutable block is only written at the next flush.
+    let written = Chunk::load(&dir.join("207.bin")).unwrap();
+    let hashes: Vec<String> = written.iter().map(Block::hash).collect();
+    fs::remove_dir_all(dir).unwrap();
+
+    assert_eq!(hashes, byron[..2].iter().map(hash).collect::<Vec<_>>());
+}
+
+#[tokio::test]
+async fn leaves_out_the_blocks_before_the_start() {
+    let dir = std::env::temp_dir().join(format!("mini-explorer-start-{}", std::process::id()));
+    let (byron, shelley, last) = crossing_epochs();
+    let chain = Chain::new(1, LinkPolicy::Halt)
+        .data_dir(dir.clone())
+        .flush_every(1)
+        .start_at(EpochLayout::MAINNET.first_slot(208));
+    let (engine, events) = sync(chain).await;
+
+    let chain = engine.chain.lock().await;
+    let skipped = chain.get_block_by_height(height(&byron[2])).unwrap();
+    let first = chain.get_block_by_height(height(&shelley)).unwrap();
+    let epochs = Chunk::epochs(&dir).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert!(skipped.is_none());
+    assert_eq!(first.map(|b| b.hash()), Some(hash(&shelley)));
+    assert_eq!(epochs, vec![208]);
+    assert_eq!(chain.height(), Some(height(&last)));
+    assert!(!events
+        .iter()
+        .any(|e| matches!(e, ChainEvent::LinkBroken(_))));
+}
+
+/// Syncs `chain` from `points` on a server serving `blocks`.
+async fn sync_from(chain: Chain, points: Vec<PointOrOrigin>, blocks: &[Value]) -> Box<Engine> {
+    let steps = blocks.iter().cloned().map(Step::Forward).collect();
+    let server = FakeOgmios::start(steps, Vec::new()).await;
+    let source = Ogmios::new(
+        server.uri.clone(),
+        OgmiosVersion::V5,
+        ErrorPolicy::Stop,
+        Pipelining::default(),
+    );
+    let (mut engine, mut events) = Engine::new(Arc::new(source), 100, chain);
+    engine.points = points;
+    engine.start().await;
+    collect_until(&mut events, synchronized).await;
+    engine
+}
+
+#[tokio::test]
+async fn resumes_within_a_stored_epoch_after_a_restart() {
+    let dir = std::env::temp_dir().join(format!("mini-explorer-resume-{}", std::process::id()));
+    let main = blocks(&fixture("mary"), 0, 6);
+    let chain = || {
+        Chain::new(1, LinkPolicy::Halt)
+            .data_dir(dir.clone())
+            .flush_every(1)
+            .resume()
+            .unwrap()
+    };
+
+    // Stopped without flushing, with three of the four blocks written.
+    let first = chain();
+    let origin = first.resume_point().unwrap();
+    drop(sync_from(first, vec![origin], &main[..4]).await);
+    let resumed = chain();
+    let start = resumed.resume_point().unwrap();
+    let engine = sync_from(resumed, vec![start.clone()], &main).await;
+
+    let written = Chunk::load(&dir.join("251.bin")).unwrap();
+    let hashes: Vec<String> = written.iter().map(Block::hash).collect();
+    let chain = engine.chain.lock().await;
+    let oldest = chain.get_block_by_height(height(&main[0])).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    let expected = PointOrOrigin::point(slot(&main[2]), hash(&main[2]));
+    assert_eq!(format!("{:?}", start), format!("{:?}", expected));
+    assert_eq!(hashes, main[..5].iter().map(hash).collect::<Vec<_>>());
+    assert_eq!(oldest.map(|b| b.hash()), Some(hash(&main[0])));
+}
+
+#[tokio::test]
+async fn reports_chunks_which_cannot_be_written() {
+    let file = std::env::temp_dir().join(format!("mini-explorer-file-{}", std::process::id()));
+    fs::write(&file, b"").unwrap();
+    let (engine, collected) =
+        sync(Chain::new(1, LinkPolicy::Halt).data_dir(file.join("data"))).await;
+    fs::remove_file(&file).unwrap();
+
+    assert!(collected
+        .iter()
+        .any(|e| matches!(e, ChainEvent::DumpFailed { epoch: 207, .. })));
+    // The blocks are kept in memory.
+    let chain = engine.chain.lock().await;
+    assert_eq!(chain.blocks_by_height(..).count(), 5);
+}
@@ -0,0 +1,559 @@
+//! Fake Ogmios v5 and v6 servers and block fixtures for integration tests.
+#![allow(dead_code)]
+
+use std::fs;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures_util::{SinkExt, StreamExt};
+use mini_explorer::chain::format;
+use mini_explorer::chain::index::{Entry, Index};
+use mini_explorer::chain::{Chain, ChainEvent, Chunk, LinkPolicy};
+use mini_explorer::data::Block;
+use mini_explorer::synchronization::Events;
+use serde_json::{json, Value};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_tungstenite::tungstenite::handshake::server::{Request, Response};
+use tokio_tungstenite::tungstenite::http::{HeaderValue, Uri};
+use tokio_tungstenite::tungstenite::Message;
+
+pub const ERAS: [&str; 7] = [
+    "byron", "shelley", "allegra", "mary", "alonzo", "babbage", "conway",
+];
+
+/// Block of `era`, as Ogmios v5 serves it in a `RollForward`.
+pub fn fixture(era: &str) -> Value {
+    let path = format!("{}/tests/fixtures/{}.json", env!("CARGO_MANIFEST_DIR"), era);
+    serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap()
+}
+
+/// A reply captured from an Ogmios v6 server.
+pub fn v6_frame(name: &str) -> String {
+    let path = format!(
+        "{}/tests/fixtures/v6/{}.json",
+        env!("CARGO_MANIFEST_DIR"),
+        name
+    );
+    fs::read_to_string(path).unwrap()
+}
+
+fn inner(block: &Value) -> (&str, &Value) {
+    let (era, inner) = block.as_object().unwrap().iter().next().unwrap();
+    (era, inner)
+}
+
+fn inner_mut(block: &mut Value) -> (String, &mut Value) {
+    let (era, inner) = block.as_object_mut().unwrap().iter_mut().next().unwrap();
+    (era.clone(), inner)
+}
+
+pub fn hash(block: &Value) -> String {
+    let (era, inner) = inner(block);
+    let key = if era == "byron" { "hash" } else { "headerHash" };
+    inner[key].as_str().unwrap().to_string()
+}
+
+pub fn slot(block: &Value) -> u64 {
+    let (era, inner) = inner(block);
+    let key = if era == "byron" {
+        "blockHeight"
+    } else {
+        "slot"
+    };
+    inner["header"][key].as_u64().unwrap()
+}
+
+pub fn height(block: &Value) -> u64 {
+    inner(block).1["header"]["blockHeight"].as_u64().unwrap()
+}
+
+pub fn point(block: &Value) -> Value {
+    json!({ "slot": slot(block), "hash": hash(block) })
+}
+
+/// Block following `block` on a chain identified by `fork`, so that two
+/// forks never share hashes.
+pub fn next(block: &Value, fork: u8) -> Value {
+    let mut next = block.clone();
+    let prev = hash(block);
+    let (era, inner) = inner_mut(&mut next);
+    let header = &mut inner["header"];
+    header["blockHeight"] = json!(header["blockHeight"].as_u64().unwrap() + 1);
+    header["prevHash"] = json!(prev);
+    if era != "byron" {
+        header["slot"] = json!(header["slot"].as_u64().unwrap() + 1);
+    }
+    let key = if era == "byron" { "hash" } else { "headerHash" };
+    let seed = slot(&next);
+    let (_, inner) = inner_mut(&mut next);
+    inner[key] = json!(format!("{:02x}{:062x}", fork, seed));
+    next
+}
+
+/// `len` consecutive blocks of `era` on `fork`, starting after `from`.
+pub fn blocks(from: &Value, fork: u8, len: usize) -> Vec<Value> {
+    let mut blocks: Vec<Value> = Vec::new();
+    for _ in 0..len {
+        let block = next(blocks.last().unwrap_or(from), fork);
+        blocks.push(block);
+    }
+    blocks
+}
+
+/// A temporary directory `name` holding `len` blocks of `era` in the chunk
+/// of `epoch`, with the blocks written.
+pub fn stored_chunk(name: &str, era: &str, epoch: u64, len: usize) -> (PathBuf, Vec<Block>) {
+    let dir = std::env::temp_dir().join(format!("mini-explorer-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let written: Vec<Block> = blocks(&fixture(era), 0, len)
+        .into_iter()
+        .map(|b| serde_json::from_value(b).unwrap())
+        .collect();
+    format::write(&Chunk::path(&dir, epoch), &written).unwrap();
+    let entries: Vec<Entry> = written.iter().map(Entry::from).collect();
+    Index::dump(&dir, epoch, &entries).unwrap();
+    (dir, written)
+}
+
+/// A chain writing its chunks to a temporary directory of its own, `name`,
+/// rather than to `data` in the repository.
+pub fn temp_chain(name: &str, security_param: usize, on_break: LinkPolicy) -> Chain {
+    let dir = std::env::temp_dir().join(format!("mini-explorer-{}-{}", name, std::process::id()));
+    Chain::new(security_param, on_break).data_dir(dir)
+}
+
+/// Three mary blocks written to the chunk of their epoch, 251.
+pub fn stored(name: &str) -> (PathBuf, Vec<Block>) {
+    stored_chunk(name, "mary", 251, 3)
+}
+
+/// Status and body answered to the HTTP `request`.
+pub async fn http(addr: SocketAddr, request: String) -> (u16, String) {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+    (status, body.to_string())
+}
+
+/// Status and body answered to `GET path`.
+pub async fn http_get(addr: SocketAddr, path: &str) -> (u16, String) {
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n",
+        path, addr
+    );
+    http(addr, request).await
+}
+
+/// What the server answers to the next `RequestNext`.
+#[derive(Debug, Clone)]
+pub enum Step {
+    Forward(Value),
+    /// A block only served on the first connection.
+    Stray(Value),
+    Backward(Value),
+    Fault(String),
+    Garbage,
+    Disconnect,
+}
+
+#[derive(Debug)]
+struct Script {
+    steps: Vec<Step>,
+    mempool: Vec<String>,
+}
+
+impl Script {
+    fn tip(&self) -> Value {
+        self.steps
+            .iter()
+            .rev()
+            .find_map(|step| match step {
+                Step::Forward(block) => Some(json!({
+                    "slot": slot(block),
+                    "hash": hash(block),
+                    "blockNo": height(block),
+                })),
+                _ => None,
+            })
+            .unwrap_or_else(|| json!("origin"))
+    }
+
+    /// Index of the step following the first of `points` on the script.
+    fn intersect(&self, points: &[Value]) -> Option<(Value, usize)> {
+        points.iter().find_map(|point| {
+            if point == "origin" {
+                return Some((point.clone(), 0));
+            }
+            self.steps
+                .iter()
+                .position(|step| match step {
+                    Step::Forward(block) => Some(hash(block).as_str()) == point["hash"].as_str(),
+                    _ => false,
+                })
+                .map(|i| (point.clone(), i + 1))
+        })
+    }
+}
+
+/// Envelope spoken by the fake server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    /// `jsonwsp` of Ogmios v5.
+    V5,
+    /// JSON-RPC 2.0 of Ogmios v6.
+    V6,
+}
+
+impl Protocol {
+    /// The request of `text` as its v5 method name and the id to echo, or
+    /// none when it uses the other envelope.
+    fn request(&self, text: &str) -> Option<(String, Value, Value)> {
+        let request: Value = serde_json::from_str(text).ok()?;
+        match self {
+            Self::V5 => {
+                let method = request["methodname"].as_str()?.to_string();
+                Some((method, request["args"].clone(), request["mirror"].clone()))
+            }
+            Self::V6 => {
+                let method = match request["method"].as_str()? {
+                    "findIntersection" => "FindIntersect",
+                    "nextBlock" => "RequestNext",
+                    "acquireMempool" => "AwaitAcquire",
+                    "nextTransaction" => "NextTx",
+                    "sizeOfMempool" => "SizeAndCapacity",
+                    "queryNetwork/startTime" => "Query",
+                    other => other,
+                };
+                let mut args = request["params"].clone();
+                if let Some(points) = args["points"].as_array_mut() {
+                    for point in points.iter_mut().filter(|p| p.is_object()) {
+                        *point = json!({ "slot": point["slot"], "hash": point["id"] });
+                    }
+                }
+                Some((method.to_string(), args, request["id"].clone()))
+            }
+        }
+    }
+
+    fn response(&self, method: &str, result: Value, id: &Value) -> Message {
+        let response = match self {
+            Self::V5 => json!({
+                "type": "jsonwsp/response",
+                "version": "1.0",
+                "servicename": "ogmios",
+                "methodname": method,
+                "result": result,
+                "reflection": id,
+            }),
+            Self::V6 => json!({ "jsonrpc": "2.0", "method": method, "result": result, "id": id }),
+        };
+        Message::Text(response.to_string())
+    }
+
+    fn fault(&self, code: i64, message: &str, id: &Value) -> Message {
+        let fault = match self {
+            Self::V5 => json!({
+                "type": "jsonwsp/fault",
+                "version": "1.0",
+                "servicename": "ogmios",
+                "fault": { "code": "client", "string": message },
+                "reflection": id,
+            }),
+            Self::V6 => json!({
+                "jsonrpc": "2.0",
+                "error": { "code": code, "message": message },
+                "id": id,
+            }),
+        };
+        Message::Text(fault.to_string())
+    }
+
+    fn point(&self, point: &Value) -> Value {
+        match (self, point.get("hash")) {
+            (Self::V6, Some(hash)) => json!({ "slot": point["slot"], "id": hash }),
+            _ => point.clone(),
+        }
+    }
+
+    fn tip(&self, script: &Script) -> Value {
+        match (self, script.tip()) {
+            (Self::V6, Value::Object(tip)) => {
+                json!({ "slot": tip["slot"], "id": tip["hash"], "height": tip["blockNo"] })
+            }
+            (_, tip) => tip,
+        }
+    }
+
+    fn intersection(&self, point: Option<Value>, script: &Script) -> Result<Value, String> {
+        let tip = self.tip(script);
+        match (self, point) {
+            (Self::V5, Some(point)) => {
+                Ok(json!({ "IntersectionFound": { "point": point, "tip": tip } }))
+            }
+            (Self::V5, None) => Ok(json!({ "IntersectionNotFound": { "tip": tip } })),
+            (Self::V6, Some(point)) => {
+                Ok(json!({ "intersection": self.point(&point), "tip": tip }))
+            }
+            (Self::V6, None) => Err("No intersection found.".to_string()),
+        }
+    }
+
+    fn forward(&self, block: &Value, script: &Script) -> Value {
+        let tip = self.tip(script);
+        match self {
+            Self::V5 => json!({ "RollForward": { "block": block, "tip": tip } }),
+            Self::V6 => json!({ "direction": "forward", "block": v6_block(block), "tip": tip }),
+        }
+    }
+
+    fn backward(&self, point: &Value, script: &Script) -> Value {
+        let tip = self.tip(script);
+        match self {
+            Self::V5 => json!({ "RollBackward": { "point": point, "tip": tip } }),
+            Self::V6 => json!({ "direction": "backward", "point": self.point(point), "tip": tip }),
+        }
+    }
+
+    fn garbage(&self) -> Value {
+        match self {
+            Self::V5 => json!({ "RollForward": { "block": { "unknown": {} } } }),
+            Self::V6 => {
+                let block = json!({ "era": "unknown", "id": "00", "ancestor": "00", "height": 0, "slot": 0 });
+                json!({ "direction": "forward", "block": block, "tip": "origin" })
+            }
+        }
+    }
+
+    fn acquired(&self, script: &Script) -> Value {
+        let slot = script.tip()["slot"].clone();
+        match self {
+            Self::V5 => json!({ "AwaitAcquired": { "slot": slot } }),
+            Self::V6 => json!({ "acquired": "mempool", "slot": slot }),
+        }
+    }
+
+    fn next_tx(&self, id: Option<&String>) -> Value {
+        match self {
+            Self::V5 => json!(id),
+            Self::V6 => json!({ "transaction": id.map(|id| json!({ "id": id })) }),
+        }
+    }
+
+    fn size(&self, script: &Script) -> Value {
+        let (size, count) = (script.mempool.len() * 300, script.mempool.len());
+        match self {
+            Self::V5 => json!({ "capacity": 180000, "currentSize": size, "numberOfTxs": count }),
+            Self::V6 => json!({
+                "maxCapacity": { "bytes": 180000 },
+                "currentSize": { "bytes": size },
+                "transactions": { "count": count },
+            }),
+        }
+    }
+}
+
+/// `block`, a v5 fixture, in the v6 schema, without its transactions.
+pub fn v6_block(block: &Value) -> Value {
+    let (era, inner) = inner(block);
+    let header = &inner["header"];
+    json!({
+        "era": era,
+        "id": hash(block),
+        "ancestor": header["prevHash"],
+        "height": height(block),
+        "slot": header["slot"].as_u64().unwrap_or_else(|| height(block)),
+        "issuer": { "verificationKey": header["issuerVk"] },
+        "transactions": [],
+    })
+}
+
+/// Ogmios server playing a script of chain-sync replies. Requests beyond
+/// the end of the script are left unanswered, as if waiting for new blocks.
+/// The mempool holds `mempool` once, then never changes.
+pub struct FakeOgmios {
+    pub uri: Uri,
+    /// Methods received so far, by their v5 name.
+    pub requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl FakeOgmios {
+    /// A v5 server.
+    pub async fn start(steps: Vec<Step>, mempool: Vec<String>) -> Self {
+        Self::speaking(Protocol::V5, steps, mempool).await
+    }
+
+    pub async fn speaking(protocol: Protocol, steps: Vec<Step>, mempool: Vec<String>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let uri = format!("ws://{}", listener.local_addr().unwrap())
+            .parse()
+            .unwrap();
+        let script = Arc::new(Script { steps, mempool });
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let received = requests.clone();
+        tokio::spawn(async move {
+            let mut first = true;
+            while let Ok((stream, _)) = listener.accept().await {
+                let server = Server {
+                    protocol,
+                    script: script.clone(),
+                    first,
+                    requests: received.clone(),
+                };
+                tokio::spawn(server.serve(stream));
+                first = false;
+            }
+        });
+
+        FakeOgmios { uri, requests }
+    }
+
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+/// One connection to the fake server.
+struct Server {
+    protocol: Protocol,
+    script: Arc<Script>,
+    first: bool,
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl Server {
+    async fn serve(self, stream: tokio::net::TcpStream) {
+        let accept = |request: &Request, mut response: Response| {
+            if let Some(protocol) = request.headers().get("Sec-WebSocket-Protocol") {
+                let protocol = HeaderValue::from_str(protocol.to_str().unwrap()).unwrap();
+                response
+                    .headers_mut()
+                    .insert("Sec-WebSocket-Protocol", protocol);
+            }
+            Ok(response)
+        };
+        let mut ws = tokio_tungstenite::accept_hdr_async(stream, accept)
+            .await
+            .unwrap();
+
+        let (protocol, script) = (self.protocol, &self.script);
+        let mut cursor = script.steps.len();
+        let mut acquired = false;
+        let mut mempool = script.mempool.iter();
+
+        while let Some(Ok(Message::Text(text))) = ws.next().await {
+            // Ogmios rejects the envelope of the other version.
+            let (method, args, id) = match protocol.request(&text) {
+                Some(request) => request,
+                None => {
+                    let fault = protocol.fault(-32600, "invalid request", &Value::Null);
+                    let _ = ws.send(fault).await;
+                    continue;
+                }
+            };
+            self.requests.lock().unwrap().push(method.clone());
+            let reply = match method.as_str() {
+                "FindIntersect" => {
+                    let points = args["points"].as_array().unwrap();
+                    let found = script.intersect(points).map(|(point, start)| {
+                        cursor = start;
+                        point
+                    });
+                    match protocol.intersection(found, script) {
+                        Ok(reply) => reply,
+                        Err(message) => {
+                            let _ = ws.send(protocol.fault(1000, &message, &id)).await;
+                            continue;
+                        }
+                    }
+                }
+                "RequestNext" => match script.steps[cursor.min(script.steps.len())..]
+                    .iter()
+                    .position(|step| self.first || !matches!(step, Step::Stray(_)))
+                    .map(|skipped| {
+                        cursor += skipped;
+                        &script.steps[cursor]
+                    }) {
+                    None => continue,
+                    Some(step) => {
+                        cursor += 1;
+                        match step {
+                            Step::Forward(block) | Step::Stray(block) => {
+                                protocol.forward(block, script)
+                            }
+                            Step::Backward(point) => protocol.backward(point, script),
+                            Step::Fault(message) => {
+                                let _ = ws.send(protocol.fault(2000, message, &id)).await;
+                                continue;
+                            }
+                            Step::Garbage => protocol.garbage(),
+                            Step::Disconnect => {
+                                let _ = ws.close(None).await;
+                                return;
+                            }
+                        }
+                    }
+                },
+                "AwaitAcquire" if acquired => continue,
+                "AwaitAcquire" => {
+                    acquired = true;
+                    protocol.acquired(script)
+                }
+                "NextTx" => protocol.next_tx(mempool.next()),
+                "SizeAndCapacity" => protocol.size(script),
+                "Query" => json!("2017-09-23T21:44:51Z"),
+                _ => {
+                    let _ = ws
+                        .send(protocol.fault(-32601, "unsupported method", &id))
+                        .await;
+                    continue;
+                }
+            };
+            let method = match protocol {
+                Protocol::V5 => method,
+                Protocol::V6 => serde_json::from_str::<Value>(&text).unwrap()["method"]
+                    .as_str()
+                    .unwrap()
+                    .to_string(),
+            };
+            if ws
+                .send(protocol.response(&method, reply, &id))
+                .await
+                .is_err()
+            {
+                return;
+            }
+        }
+    }
+}
+
+/// Collects engine events until `done` returns true, failing after a few
+/// seconds without it.
+pub async fn collect_until(
+    events: &mut Events,
+    mut done: impl FnMut(&ChainEvent) -> bool,
+) -> Vec<ChainEvent> {
+    let mut collected = Vec::new();
+    let collecting = async {
+        while let Some(event) = events.next().await {
+            let stop = done(&event);
+            collected.push(event);
+            if stop {
+                return;
+            }
+        }
+    };
+    if tokio::time::timeout(Duration::from_secs(10), collecting)
+        .await
+        .is_err()
+    {
+        panic!("timed out, events so far: {:#?}", collected);
+    }
+    collected
+}
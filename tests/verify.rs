@@ -0,0 +1,61 @@
+mod support;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use mini_explorer::chain::format;
+use mini_explorer::chain::verify::{verify, Issue};
+use mini_explorer::data::Block;
+use serde_json::Value;
+
+use support::{blocks, fixture};
+
+fn dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("mini-explorer-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn write_chunk(dir: &Path, epoch: u64, blocks: &[Value]) {
+    let blocks: Vec<Block> = blocks
+        .iter()
+        .map(|b| serde_json::from_value(b.clone()).unwrap())
+        .collect();
+    format::write(&dir.join(format!("{}.bin", epoch)), &blocks).unwrap();
+}
+
+#[test]
+fn accepts_linked_chunks() {
+    let dir = dir("linked");
+    let chain = blocks(&fixture("shelley"), 0, 6);
+    write_chunk(&dir, 208, &chain[..3]);
+    write_chunk(&dir, 209, &chain[3..]);
+
+    let report = verify(&dir).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!((report.chunks, report.blocks), (2, 6));
+    assert!(report.issues.is_empty(), "{:?}", report.issues);
+}
+
+#[test]
+fn finds_breaks_gaps_and_missing_epochs() {
+    let dir = dir("broken");
+    let chain = blocks(&fixture("shelley"), 0, 6);
+    let fork = blocks(&chain[0], 1, 1);
+    write_chunk(
+        &dir,
+        208,
+        &[chain[0].clone(), fork[0].clone(), chain[2].clone()],
+    );
+    write_chunk(&dir, 210, &[chain[4].clone(), chain[5].clone()]);
+
+    let report = verify(&dir).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(matches!(report.issues[0], Issue::Break(_)));
+    assert!(matches!(
+        report.issues[1],
+        Issue::MissingEpochs { from: 209, to: 209 }
+    ));
+    assert!(matches!(report.issues[2], Issue::Gap { missing: 1, .. }));
+    assert_eq!(report.issues.len(), 3);
+}